@@ -51,16 +51,16 @@ impl From<Assoc<Name, Ast>> for Ren {
 
 fn substitute_rec(node: &Ast, cur_node_contents: &EnvMBE<Ast>, env: &Ren) -> Ast {
     match *node {
-        Node(ref f, ref new_parts, ref export) => {
+        Node(ref f, ref new_parts, ref export, span) => {
             //let new_cnc = parts.clone();
             Node(f.clone(),
                  new_parts.marched_map(
                      &mut |_, marched_parts: &EnvMBE<Ast>, part: &Ast|
                          substitute_rec(part, marched_parts, env)),
-                 export.clone())
+                 export.clone(), span)
         }
-        VariableReference(n) => {
-            env.find(n).unwrap_or(&node.clone()).clone()
+        VariableReference(n, span) => {
+            env.find(n).map(|new_vr| new_vr.clone().with_span(span)).unwrap_or_else(|| node.clone())
         }
         ExtendEnv(ref body, ref beta) => {
             let mut new_env = env.clone();
@@ -95,7 +95,7 @@ pub fn substitute(node: &Ast, env: &Assoc<Name, Ast>) -> Ast {
 fn mentioned_in_import(parts: &EnvMBE<Ast>) -> Vec<Name> {
     fn process_ast(a: &Ast, v: &mut Vec<Name>) {
         match *a {
-            Node(_,_,_) => {} // new scope
+            Node(_,_,_,_) => {} // new scope
             ExtendEnv(ref body, ref beta) => {
                 let mut beta_mentions = beta.names_mentioned_and_bound();
                 v.append(&mut beta_mentions);
@@ -103,7 +103,7 @@ fn mentioned_in_import(parts: &EnvMBE<Ast>) -> Vec<Name> {
             }
             // TODO: does it make sense to mention a name underneath a quotation?
             QuoteMore(ref body, _) | QuoteLess(ref body, _) => { process_ast(body, v) }
-            Trivial | Atom(_) | VariableReference(_) => {} // no beta
+            Trivial | Atom(_, _) | VariableReference(_, _) => {} // no beta
             Shape(_) | IncompleteNode(_) => { panic!("ICE: shouldn't be needed") }
         }
     }
@@ -116,9 +116,9 @@ fn mentioned_in_import(parts: &EnvMBE<Ast>) -> Vec<Name> {
 fn freshen_rec(node: &Ast, renamings: &EnvMBE<(Ast, Ren)>, env: Ren) -> Ast {
     //  `env` is used to update the references to those atoms to match
     match *node {
-        Node(_, _, _) => { substitute_rec(node, &EnvMBE::new(), &env) }
-        VariableReference(n) => {
-            env.find(n).unwrap_or(&node.clone()).clone()
+        Node(_, _, _, _) => { substitute_rec(node, &EnvMBE::new(), &env) }
+        VariableReference(n, span) => {
+            env.find(n).map(|new_vr| new_vr.clone().with_span(span)).unwrap_or_else(|| node.clone())
         }
         ExtendEnv(ref body, ref beta) => {
             let new_env = env.set_assoc(&beta.extract_from_mbe(renamings, &|x: &(_, Ren)| &x.1));
@@ -132,7 +132,7 @@ fn freshen_rec(node: &Ast, renamings: &EnvMBE<(Ast, Ren)>, env: Ren) -> Ast {
         QuoteLess(ref body, depth) => {
             QuoteLess(Box::new(freshen_rec(body, renamings, env.q_less(depth))), depth)
         }
-        Atom(_) | Trivial | IncompleteNode(_) | Shape(_) => node.clone()
+        Atom(_, _) | Trivial | IncompleteNode(_) | Shape(_) => node.clone()
     }
 }
 
@@ -143,7 +143,7 @@ thread_local! {
 pub fn freshen(a: &Ast) -> Ast { // TODO: I think this shouldn't take a reference for performance
     if freshening_enabled.with(|f| *f.borrow()) {
         match a {
-            &Node(ref f, ref p, ref export) => {
+            &Node(ref f, ref p, ref export, span) => {
                 // Every part that gets mentioned inside this node...
                 let mentioned = mentioned_in_import(p);
                 // ...needs to have its binders freshend:
@@ -153,7 +153,7 @@ pub fn freshen(a: &Ast) -> Ast { // TODO: I think this shouldn't take a referenc
                     fresh_ast_and_rens.marched_map(
                         &mut |_, marched: &EnvMBE<(Ast, Ren)>, &(ref part, _)|
                             freshen_rec(part, marched, Ren::new())),
-                            export.clone())
+                            export.clone(), span)
             }
             non_node => non_node.clone()
         }
@@ -165,7 +165,8 @@ pub fn freshen(a: &Ast) -> Ast { // TODO: I think this shouldn't take a referenc
 pub fn freshen_with(lhs: &Ast, rhs: &Ast) -> (Ast, Ast) {
     if freshening_enabled.with(|f| *f.borrow()) {
         match (lhs, rhs) {
-            (&Node(ref f, ref p_lhs, ref export), &Node(ref f_rhs, ref p_rhs, ref export_rhs)) => {
+            (&Node(ref f, ref p_lhs, ref export, span_lhs),
+             &Node(ref f_rhs, ref p_rhs, ref export_rhs, span_rhs)) => {
                 if f != f_rhs || export != export_rhs { return (lhs.clone(), rhs.clone()); }
                 // Every part that gets mentioned inside this node...
                 let mentioned = mentioned_in_import(p_lhs);
@@ -183,8 +184,8 @@ pub fn freshen_with(lhs: &Ast, rhs: &Ast) -> (Ast, Ast) {
                                 freshen_rec(parts,
                                             &marched.map(&mut |q| (q.2.clone(), q.3.clone())),
                                             Ren::new()));
-                        (Node(f.clone(), new_p_lhs, export.clone()),
-                         Node(f.clone(), new_p_rhs, export.clone()))
+                        (Node(f.clone(), new_p_lhs, export.clone(), span_lhs),
+                         Node(f.clone(), new_p_rhs, export.clone(), span_rhs))
 
                     }
                     None => (lhs.clone(), rhs.clone()) // No destructuring will be performed!
@@ -223,12 +224,12 @@ pub fn freshen_binders_inside_node_with(p_lhs: &EnvMBE<Ast>, p_rhs: &EnvMBE<Ast>
 ///  and a map to change references in the same manner
 pub fn freshen_binders(a: &Ast) -> (Ast, Ren) {
     match *a {
-        Trivial | VariableReference(_) => (a.clone(), Ren::new()),
-        Atom(old_name) => {
+        Trivial | VariableReference(_, _) => (a.clone(), Ren::new()),
+        Atom(old_name, span) => {
             let new_name = old_name.freshen();
-            (Atom(new_name), Ren::single(old_name, VariableReference(new_name)))
+            (Atom(new_name, span), Ren::single(old_name, VariableReference(new_name, span)))
         }
-        Node(ref f, ref parts, ref export) => {
+        Node(ref f, ref parts, ref export, span) => {
             if export == &::beta::ExportBeta::Nothing {
                 return (a.clone(), Ren::new()); // short-circuit (should this at least warn?)
             }
@@ -238,7 +239,7 @@ pub fn freshen_binders(a: &Ast) -> (Ast, Ren) {
             let fresh_ast = fresh_pairs.map(&mut |&(ref a, _) : &(Ast, _)| a.clone());
             let renaming = export.extract_from_mbe(&fresh_pairs, &|&(_, ref r): &(_, Ren)| &r);
 
-            (Node(f.clone(), fresh_ast, export.clone()), renaming)
+            (Node(f.clone(), fresh_ast, export.clone(), span), renaming)
         }
         IncompleteNode(_) | Shape(_) => { panic!("ICE: didn't think this was needed") }
         QuoteMore(ref body, pos) => {
@@ -257,17 +258,19 @@ pub fn freshen_binders(a: &Ast) -> (Ast, Ren) {
 /// Like `freshen_binders`, but to unite two `Ast`s with identical structure (else returns `None`).
 pub fn freshen_binders_with(lhs: &Ast, rhs: &Ast) -> Option<(Ast, Ren, Ast, Ren)>{
     match (lhs, rhs) {
-        (&Trivial, &Trivial) | (&VariableReference(_), &VariableReference(_)) => {
+        (&Trivial, &Trivial) | (&VariableReference(_, _), &VariableReference(_, _)) => {
             Some((lhs.clone(), Ren::new(), rhs.clone(), Ren::new()))
         },
-        (&Atom(old_name_lhs), &Atom(old_name_rhs)) => {
+        (&Atom(old_name_lhs, span_lhs), &Atom(old_name_rhs, span_rhs)) => {
             let new_name = old_name_lhs.freshen();
-            Some((Atom(new_name), Ren::single(old_name_lhs, VariableReference(new_name)),
-                  Atom(new_name), Ren::single(old_name_rhs, VariableReference(new_name))))
+            Some((Atom(new_name, span_lhs),
+                  Ren::single(old_name_lhs, VariableReference(new_name, span_lhs)),
+                  Atom(new_name, span_rhs),
+                  Ren::single(old_name_rhs, VariableReference(new_name, span_rhs))))
         }
         // TODO: Handle matching `'[let (a,b) = ⋯]'` against the pattern `'[let ,[p], = ⋯]'` !!
-        (&Node(ref f, ref parts_lhs, ref export),
-         &Node(ref f_rhs, ref parts_rhs, ref export_rhs)) => {
+        (&Node(ref f, ref parts_lhs, ref export, span_lhs),
+         &Node(ref f_rhs, ref parts_rhs, ref export_rhs, span_rhs)) => {
             if f != f_rhs || export != export_rhs { return None }
 
             if export == &::beta::ExportBeta::Nothing { // short-circuit:
@@ -281,8 +284,8 @@ pub fn freshen_binders_with(lhs: &Ast, rhs: &Ast) -> Option<(Ast, Ren, Ast, Ren)
                     let fresh_ast_rhs = fresh_pairs.map(&mut |&(_, _, ref a, _)| a.clone());
                     let ren_lhs = export.extract_from_mbe(&fresh_pairs, &|t: &(_,Ren,_,_)| &t.1);
                     let ren_rhs = export.extract_from_mbe(&fresh_pairs, &|t: &(_,_,_,Ren)| &t.3);
-                    Some((Node(f.clone(), fresh_ast_lhs, export.clone()), ren_lhs,
-                          Node(f.clone(), fresh_ast_rhs, export.clone()), ren_rhs))
+                    Some((Node(f.clone(), fresh_ast_lhs, export.clone(), span_lhs), ren_lhs,
+                          Node(f.clone(), fresh_ast_rhs, export.clone(), span_rhs), ren_rhs))
                 }
                 None => { None }
             }
@@ -9,17 +9,19 @@
 use util::mbe::EnvMBE;
 use name::*;
 use beta::{Beta, ExportBeta};
+use util::err::Span;
 use std::iter;
 use std::fmt;
 
 // TODO: This really ought to be an `Rc` around an `enum`
+// (Not derived-`Reifiable`: see `reify.rs`)
 custom_derive! {
-    #[derive(Clone, PartialEq, Reifiable)]
+    #[derive(Clone, PartialEq)]
     pub enum Ast {
         Trivial,
-        /// Typically, a binder
-        Atom(Name),
-        VariableReference(Name),
+        /// Typically, a binder. (Leaves have `Span`s, too, so that errors can point at a name.)
+        Atom(Name, Span),
+        VariableReference(Name, Span),
 
         /// Shift environment to quote (a pos/neg piece of syntax) more
         QuoteMore(Box<Ast>, bool),
@@ -28,7 +30,8 @@ custom_derive! {
 
         /// A meaningful chunk of syntax, governed by a form, containing an environment,
         ///  potentially exporting some names.
-        Node(::std::rc::Rc<::form::Form>, EnvMBE<Ast>, ExportBeta),
+        /// The `Span` is where it came from (ignored by equality).
+        Node(::std::rc::Rc<::form::Form>, EnvMBE<Ast>, ExportBeta, Span),
 
         /// For parsing purposes.
         IncompleteNode(EnvMBE<Ast>),
@@ -46,8 +49,8 @@ impl fmt::Debug for Ast {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Trivial => { write!(f, "⨉") },
-            Atom(ref n) => { write!(f, "∘{:#?}∘", n) },
-            VariableReference(ref v) => { write!(f, "{:#?}", v) }
+            Atom(ref n, _) => { write!(f, "∘{:#?}∘", n) },
+            VariableReference(ref v, _) => { write!(f, "{:#?}", v) }
             Shape(ref v) => {
                 write!(f, "(")?;
                 let mut first = true;
@@ -58,7 +61,7 @@ impl fmt::Debug for Ast {
                 }
                 write!(f, ")")
             },
-            Node(ref form, ref body, ref export) => {
+            Node(ref form, ref body, ref export, _) => {
                 write!(f, "{{ ({}); {:#?}", form.name.sp(), body)?;
                 match *export {
                     ::beta::ExportBeta::Nothing => {}
@@ -90,9 +93,9 @@ impl fmt::Debug for Ast {
 impl fmt::Display for Ast {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Atom(ref n) => { write!(f, "{}", n.print()) },
-            VariableReference(ref v) => { write!(f, "{}", v.print()) }
-            Node(ref form, ref body, _) => {
+            Atom(ref n, _) => { write!(f, "{}", n.print()) },
+            VariableReference(ref v, _) => { write!(f, "{}", v.print()) }
+            Node(ref form, ref body, _, _) => {
                 let s = ::unparse::unparse_mbe(
                     &form.grammar, self, body, &::core_forms::get_core_forms());
                 write!(f, "{}", s)
@@ -108,8 +111,8 @@ impl Ast {
     // TODO: this ought to at least warn if we're losing anything other than `Shape`
     pub fn flatten(&self) -> EnvMBE<Ast> {
         match *self {
-            Trivial | Atom(_) => EnvMBE::new(),
-            VariableReference(_) => EnvMBE::new(),
+            Trivial | Atom(_, _) => EnvMBE::new(),
+            VariableReference(_, _) => EnvMBE::new(),
             Shape(ref v) => {
                 let mut accum = EnvMBE::new();
                 for sub_a in v {
@@ -118,7 +121,7 @@ impl Ast {
                 accum
             },
             IncompleteNode(ref env) => { env.clone() }
-            Node(ref _f, ref _body, ref _export) => {
+            Node(ref _f, ref _body, ref _export, _) => {
                 // TODO: think about what should happen when
                 //  `Scope` contains a `Scope` without an intervening `Named`
                 panic!("I don't know what to do here!")
@@ -133,7 +136,7 @@ impl Ast {
     pub fn destructure(&self, expd_form: ::std::rc::Rc<::form::Form>)
             -> Option<::util::mbe::EnvMBE<Ast>> {
         match self {
-            Node(ref f, ref parts, _) => {
+            Node(ref f, ref parts, _, _) => {
                 if f == &expd_form {
                     return Some(parts.clone());
                 }
@@ -148,16 +151,35 @@ impl Ast {
     // TODO: I think we have a lot of places where we ought to use this function:
     pub fn node_parts(&self) -> &EnvMBE<Ast> {
         match *self {
-            Node(_, ref body, _) => body,
+            Node(_, ref body, _, _) => body,
             _ => panic!("ICE")
         }
     }
     pub fn node_form(&self) -> &::form::Form {
         match *self {
-            Node(ref form, _, _) => form,
+            Node(ref form, _, _, _) => form,
             _ => panic!("ICE")
         }
     }
+
+    /// The same leaf or node, but from `span`. (Other `Ast`s don't have a place to put it.)
+    pub fn with_span(self, span: Span) -> Ast {
+        match self {
+            Node(f, parts, export, _) => Node(f, parts, export, span),
+            Atom(n, _) => Atom(n, span),
+            VariableReference(n, _) => VariableReference(n, span),
+            other => other
+        }
+    }
+
+    /// Where did this come from? (Looks through binding and quotation wrappers.)
+    pub fn span(&self) -> Span {
+        match *self {
+            Node(_, _, _, span) | Atom(_, span) | VariableReference(_, span) => span,
+            ExtendEnv(ref body, _) | QuoteMore(ref body, _) | QuoteLess(ref body, _) => body.span(),
+            _ => Span::nowhere()
+        }
+    }
 }
 
 // This is used by combine::many, which is used by the Star parser
@@ -223,3 +245,13 @@ fn mbe_r_and_r_roundtrip() {
     let mbe1 = mbe!( "a" => [@"duo" "1", "2"], "b" => [@"duo" "11", "22"]);
     assert_eq!(mbe1, EnvMBE::<Ast>::reflect(&mbe1.reify()));
 }
+
+#[test]
+fn ast_r_and_r_roundtrip() {
+    use runtime::reify::Reifiable;
+    let a = ast!({"Expr" "apply" : "rator" => (vr "f"), "rand" => [(vr "x")]});
+    // `Form`s are compared by identity, so reflection has to give back the same ones:
+    assert_eq!(a, Ast::reflect(&a.reify()));
+    // ...which works because syntax is already a value:
+    assert_eq!(a.reify(), ::runtime::eval::Value::AbstractSyntax(a.clone()));
+}
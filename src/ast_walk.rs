@@ -90,7 +90,7 @@ impl<Elt: WalkElt> Clo<Elt> {
         let o_different_env = other.env.cut_common(&self.env);
 
         let o_renaming = o_different_env.keyed_map_borrow_f(
-            &mut |name, _| VariableReference(name.freshen()));
+            &mut |name, _| VariableReference(name.freshen(), ::util::err::Span::nowhere()));
 
         // if !o_renaming.empty() { println!("MERGE: {}", o_renaming); }
 
@@ -141,7 +141,7 @@ pub fn walk<Mode: WalkMode>(a: &Ast, walk_ctxt: &LazyWalkReses<Mode>)
                 match walk_ctxt.this_ast {
                     // `this_ast` might be `NotWalked` (and non-literal) if under `switch_mode`.
                     // It's weird, but seems to be the right thing
-                    Node(ref f, _, _) => Some(Mode::get_walk_rule(f).is_literally()),
+                    Node(ref f, _, _, _) => Some(Mode::get_walk_rule(f).is_literally()),
                     _ => None
                 }
             }
@@ -149,7 +149,7 @@ pub fn walk<Mode: WalkMode>(a: &Ast, walk_ctxt: &LazyWalkReses<Mode>)
         };
 
     match a {
-        Node(ref f, ref parts, _) => {
+        Node(ref f, ref parts, _, _) => {
             let new_walk_ctxt = walk_ctxt.switch_ast(parts, a.clone());
             // certain walks only work on certain kinds of AST nodes
             match Mode::get_walk_rule(f) {
@@ -161,8 +161,11 @@ pub fn walk<Mode: WalkMode>(a: &Ast, walk_ctxt: &LazyWalkReses<Mode>)
         }
         IncompleteNode(ref parts) => { panic!("ICE: {:#?} isn't a complete node", parts)}
 
-        VariableReference(n) => { Mode::walk_var(n, &walk_ctxt) }
-        Atom(n) => { Mode::walk_atom(n, &walk_ctxt) }
+        // (Leaves are their own `this_ast`, so that errors can point at them)
+        VariableReference(n, _) => {
+            Mode::walk_var(n, &LazyWalkReses { this_ast: a.clone(), .. walk_ctxt })
+        }
+        Atom(n, _) => { Mode::walk_atom(n, &LazyWalkReses { this_ast: a.clone(), .. walk_ctxt }) }
 
         // TODO: we need to preserve these in LiteralLike contexts!!
 
@@ -387,7 +390,7 @@ impl<Mode: WalkMode> LazilyWalkedTerm<Mode> {
     /** Get the result of walking this term (memoized) */
     fn get_res(&self, cur_node_contents: &LazyWalkReses<Mode>)
             -> Result<<Mode::D as Dir>::Out, Mode::Err> {
-        self.memoized(&|| walk::<Mode>(&self.term, cur_node_contents))
+        self.memoized(&|| walk::<Mode>(&self.term, cur_node_contents).or_else(Mode::recover))
     }
 
     fn memoized(&self, f: &dyn Fn() -> Result<<Mode::D as Dir>::Out, Mode::Err>)
@@ -524,7 +527,7 @@ impl<Mode: WalkMode> LazyWalkReses<Mode> {
 
     pub fn this_form(&self) -> Rc<::form::Form> {
         match self.this_ast {
-            Node(ref f, _, _) => f.clone(),  _ => panic!("ICE")
+            Node(ref f, _, _, _) => f.clone(),  _ => panic!("ICE")
         }
     }
 
@@ -161,10 +161,14 @@ pub fn env_from_beta<Mode: ::walk_mode::WalkMode>(b: &Beta, parts: &LazyWalkRese
             Ok(res)
         }
         Basic(name_source, ty_source) => {
-            if let LazilyWalkedTerm {term: Atom(ref name), ..}
+            if let LazilyWalkedTerm {term: Atom(ref name, _), ..}
                     = **parts.parts.get_leaf_or_panic(&name_source) {
                 //let LazilyWalkedTerm {term: ref ty_stx, ..}
                 //    = **parts.parts.get_leaf_or_panic(ty_source);
+                if parts.parts.get_leaf(ty_source).is_none() {
+                    // The type was left off (to be inferred), so the form has to handle it.
+                    return Ok(Assoc::new().set(*name, Mode::underspecified(*name)));
+                }
                 let ty = parts.get_res(ty_source)?;
 
                 Ok(Assoc::new().set(*name, Mode::out_as_elt(ty.clone())))
@@ -195,9 +199,12 @@ pub fn env_from_beta<Mode: ::walk_mode::WalkMode>(b: &Beta, parts: &LazyWalkRese
             //   The latter have heavyweight logic systems that really aren't worth it,
             //    because the errors in question aren't that bad to debug.)
 
-            if let Ast::Node(_, ref sub_parts, ref export) = parts.get_term(name_source) {
+            if let Ast::Node(_, ref sub_parts, ref export, _) = parts.get_term(name_source) {
                 // For our purposes, this syntax is "real", so `quote_depth` is 0:
-                let expected_res_keys = bound_from_export_beta(export, sub_parts, 0);
+                let mut expected_res_keys = bound_from_export_beta(export, sub_parts, 0);
+                // (A name can be exported more than once, e.g. by each side of an or-pattern)
+                expected_res_keys.sort_by_key(|n| n.sp());
+                expected_res_keys.dedup();
 
                 let mut count = 0;
                 for (k, _) in res.iter_pairs() {
@@ -218,7 +225,7 @@ pub fn env_from_beta<Mode: ::walk_mode::WalkMode>(b: &Beta, parts: &LazyWalkRese
         }
 
         Underspecified(ref name_source) => {
-            if let LazilyWalkedTerm {term: Atom(ref name), ..}
+            if let LazilyWalkedTerm {term: Atom(ref name, _), ..}
                     = **parts.parts.get_leaf_or_panic(name_source) {
                 Ok(Assoc::new().set(*name, Mode::underspecified(*name)))
             } else {
@@ -314,12 +321,12 @@ impl ExportBeta {
 }
 
 // Helper for `bound_from_[export_]beta`:
-fn names_exported_by(ast: &Ast, quote_depth: i16) -> Vec<Name> {
+pub fn names_exported_by(ast: &Ast, quote_depth: i16) -> Vec<Name> {
     use tap::TapOps;
 
     match *ast {
-        Ast::Atom(n) => vec![n],
-        Ast::Node(_, ref sub_parts, ref export) => {
+        Ast::Atom(n, _) => vec![n],
+        Ast::Node(_, ref sub_parts, ref export, _) => {
             if quote_depth <= 0 {
                 bound_from_export_beta(export, sub_parts, quote_depth)
             } else {
@@ -330,6 +337,8 @@ fn names_exported_by(ast: &Ast, quote_depth: i16) -> Vec<Name> {
         }
         Ast::QuoteMore(ref body, _) => names_exported_by(body, quote_depth+1),
         Ast::QuoteLess(ref body, _) => names_exported_by(body, quote_depth-1),
+        // An import doesn't change what the body exports (e.g. `forall`-bound macro syntax):
+        Ast::ExtendEnv(ref body, _) => names_exported_by(body, quote_depth),
         ref ast if quote_depth <= 0 => {
             panic!("ICE: beta SameAs refers to an invalid AST node: {}", ast)
         }
@@ -417,7 +426,7 @@ pub fn freshening_from_beta(b: &Beta, parts: &EnvMBE<::ast::Ast>,
             let this_name = ::core_forms::ast_to_name(parts.get_leaf_or_panic(&n_s));
 
             Assoc::new().set(this_name, ::ast::VariableReference(*memo.entry((n_s, this_name))
-                .or_insert_with(||{ this_name.freshen() })))
+                .or_insert_with(||{ this_name.freshen() }), ::util::err::Span::nowhere()))
         }
     }
 }
@@ -22,10 +22,15 @@ use core_type_forms::*; // type forms are kinda bulky
 
 
 pub fn ast_to_name(ast: &Ast) -> Name {
-    match *ast { Atom(n) => n, _ => { panic!("ICE: {:#?} is not an atom", ast) } }
+    match *ast { Atom(n, _) => n, _ => { panic!("ICE: {:#?} is not an atom", ast) } }
+}
+/// The value of an integer literal (which is stored as an `Atom`)
+pub fn ast_to_int(ast: &Ast) -> ::num::BigInt {
+    ::read::int_literal(&ast_to_name(ast).orig_sp())
+        .unwrap_or_else(|| panic!("ICE: {:#?} is not an integer literal", ast))
 }
 pub fn vr_to_name(ast: &Ast) -> Name {
-    match *ast { VariableReference(n) => n, _ => { panic!("ICE: {:#?} is not a vr", ast) } }
+    match *ast { VariableReference(n, _) => n, _ => { panic!("ICE: {:#?} is not a vr", ast) } }
 }
 
 /// Remove an `ExtendEnv` without respecting its binding behavior.
@@ -35,6 +40,61 @@ pub fn strip_ee(a: &Ast) -> &Ast {
     match *a { ExtendEnv(ref body, _) => (&**body), _ => panic!("ICE: malformed thing") }
 }
 
+/// The environment for the definitions of a `letrec`: each name is bound to a function
+///  that re-evaluates its definition (in this same environment) when it gets called.
+/// This way, nothing needs to be cyclic.
+fn letrec_env(names: &[Name], defs: &[Ast], env: &Assoc<Name, Value>) -> Assoc<Name, Value> {
+    let mut res = env.clone();
+    for (i, name) in names.iter().enumerate() {
+        let (names, defs, env) = (names.to_vec(), defs.to_vec(), env.clone());
+        res = res.set(*name, BuiltInFunction(BIF(Rc::new(move |args: Vec<Value>| {
+            match eval(&defs[i], letrec_env(&names, &defs, &env))? {
+                Function(clos) => {
+                    let mut new_env = clos.env.clone();
+                    for (p, v) in clos.params.iter().zip(args) {
+                        new_env = new_env.set(*p, v);
+                    }
+                    eval(&clos.body, new_env)
+                }
+                BuiltInFunction(BIF(f)) => f(args),
+                other => Err(rt_err(RuntimeError::NotAFunction(other), &defs[i]))
+            }
+        }))));
+    }
+    res
+}
+
+/// Check that `+[arm component ⋯]+` (with `components` of the given types) fits `enum_ty`.
+fn check_enum_arm(enum_ty: &Ty, arm: &Ast, components: &[Ty], env: &Assoc<Name, Ty>, loc: &Ast)
+        -> Result<(), TypeError> {
+    let enum_ty = enum_ty.known(loc)?;
+    expect_ty_node!( (enum_ty ; find("Type", "enum") ; loc)
+        enum_type_parts;
+        {
+            for enum_type_part in enum_type_parts.march_all(&[n("name")]) {
+                if arm != enum_type_part.get_leaf_or_panic(&n("name")) {
+                    continue; // not the right arm
+                }
+
+                let component_types : Vec<Ty> =
+                    enum_type_part.get_rep_leaf_or_panic(n("component"))
+                        .iter().map(|a| Ty::new((*a).clone())).collect();
+
+                // TODO: check that they're the same length!
+
+                for (t, expected_t) in components.iter().zip(component_types) {
+                    // (Either might mention inferred types, so this isn't just `ty_exp!`)
+                    let _ = ::ty_compare::must_subtype(t, &expected_t, env.clone())
+                        .map_err(|e| ::util::err::sp(e, loc.clone()))?;
+                }
+                return Ok(());
+            }
+
+            ty_err!(NonexistentEnumArm(ast_to_name(arm), enum_ty.clone()) at loc);
+        }
+    )
+}
+
 /// This is the Unseemly language.
 pub fn make_core_syn_env() -> SynEnv {
     color_backtrace::install(); // HACK: this is around the first thing that happens in any test.
@@ -44,29 +104,65 @@ pub fn make_core_syn_env() -> SynEnv {
 
     // This seems to be necessary to get separate `Rc`s into the closures.
     let ctf_0 = ctf.clone();
-    let ctf_2 = ctf.clone();
     let ctf_3 = ctf.clone();
     let ctf_4 = ctf.clone();
     let ctf_5 = ctf.clone();
     let ctf_6 = ctf.clone();
     let ctf_7 = ctf.clone();
+    let ctf_8 = ctf.clone();
+    let ctf_9 = ctf.clone();
+    let ctf_10 = ctf.clone();
+    let ctf_11 = ctf.clone();
 
     // Unseemly expressions
     let main_expr_forms = forms_to_form_pat![
         typed_form!("lambda",
             /* syntax */ /* TODO: add comma separators to the syntax! */
             (delim ".[", "[", [
-                               (star [(named "param", aat), (lit ":"),
-                                      (named "p_t", (call "Type"))]), (lit "."),
+                // Either all the parameters are annotated, or none are (else it's ambiguous)
+                (biased (star [(named "param", aat), (lit ":"), (named "p_t", (call "Type"))]),
+                        (star (named "param", aat))),
+                (lit "."),
                 (named "body",
                     (import [* ["param" : "p_t"]], (call "Expr")))]),
             /* type */
             cust_rc_box!( move | part_types | {
+                use walk_mode::WalkMode;
+                let mut body_env = part_types.env.clone();
+                let mut param_types = vec![];
+                let mut inferred = vec![];
+                for param_parts in part_types.march_parts(&[n("param")]) {
+                    let param = param_parts.get_term(n("param"));
+                    let p_t = match param_parts.maybe_get_term(n("p_t")) {
+                        Some(_) => ::ty::synth_annotation(&param_parts, n("p_t"))?,
+                        None => { // Left off; the way the `lambda` gets used has to determine it
+                            let p_t = ::ty_compare::Subtype::underspecified(
+                                n(&ast_to_name(&param).orig_sp()));
+                            let (p_t_for_check, loc) = (p_t.clone(), part_types.this_ast.clone());
+                            ::ty::defer_check(Box::new(move || {
+                                p_t_for_check.known(&loc).map(|_| ())
+                            }));
+                            inferred.push(param_types.len());
+                            p_t
+                        }
+                    };
+                    body_env = body_env.set(ast_to_name(&param), p_t.clone());
+                    param_types.push(p_t);
+                }
+                // The `import` can't bind inferred parameters, so we do all the binding here:
+                let body_type = ::ast_walk::walk::<SynthTy>(
+                    strip_ee(part_types.get_term_ref(n("body"))),
+                    &part_types.with_environment(body_env))?;
+                // The body might have taught us something about the parameters:
+                for i in inferred {
+                    param_types[i] = synth_type(&param_types[i].0, part_types.env.clone())?;
+                }
+
                 let lambda_type : Ty =
                     ty!({ find_type(&ctf_0, "fn") ;
-                         "param" => [* part_types =>("param") part_types :
-                                       (, part_types.get_res(n("p_t"))?.concrete() )],
-                         "ret" => (, part_types.get_res(n("body"))?.concrete() )});
+                         "param" => (,seq param_types.iter().map(Ty::concrete)
+                                         .collect::<Vec<_>>()),
+                         "ret" => (, body_type.concrete() )});
                 Ok(lambda_type)}),
             /* evaluation */
             cust_rc_box!( move | part_values | {
@@ -84,12 +180,15 @@ pub fn make_core_syn_env() -> SynEnv {
             cust_rc_box!(move | part_types | {
                 use walk_mode::WalkMode;
                 let return_type = ::ty_compare::Subtype::underspecified(n("<return_type>"));
+                let rand_types = part_types.get_rep_res(n("rand"))?;
+                // We can't know what a broken function returns:
+                if is_error_ty(&part_types.get_res(n("rator"))?) { return Ok(error_ty()); }
 
                 // The `rator` must be a function that takes the `rand`s as arguments:
                 let _ = ::ty_compare::must_subtype(
                     &ty!({ "Type" "fn" :
-                        "param" => (,seq part_types.get_rep_res(n("rand"))?
-                             .iter().map(|t| t.concrete()).collect::<Vec<_>>() ),
+                        "param" => (,seq rand_types.iter().map(|t| t.concrete())
+                                         .collect::<Vec<_>>() ),
                         "ret" => (, return_type.concrete() )}),
                     &part_types.get_res(n("rator"))?,
                     part_types.env.clone())
@@ -98,17 +197,25 @@ pub fn make_core_syn_env() -> SynEnv {
 
                 // TODO: write a test that exercises this (it's used in the prelude)
                 // What return type made that work?
-                ::ty_compare::unification.with(|unif| {
-                    let res = ::ty_compare::resolve(
+                let res = ::ty_compare::unification.with(|unif| {
+                    ::ty_compare::resolve(
                         ::ast_walk::Clo{ it: return_type, env: part_types.env.clone()},
-                        &unif.borrow());
+                        &unif.borrow())
+                });
+                // If the `rator`'s type is being inferred, so is what it returns
+                //  (and the context has to determine it):
+                if ::ty_compare::underdetermined_source(&res.it).is_some() {
+                    let (ret, loc) = (res.it.clone(), part_types.this_ast.clone());
+                    ::ty::defer_check(Box::new(move || ret.known(&loc).map(|_| ())));
+                    return Ok(res.it);
+                }
 
-                    // Canonicalize the type in its environment:
-                    let res = ::ty_compare::canonicalize(&res.it, res.env);
-                    res.map_err(|e| ::util::err::sp(e, part_types.this_ast.clone()))
-                })
+                // Canonicalize the type in its environment:
+                ::ty_compare::canonicalize(&res.it, res.env)
+                    .map_err(|e| ::util::err::sp(e, part_types.this_ast.clone()))
             }),
             cust_rc_box!( move | part_values | {
+                let this_ast = &part_values.this_ast;
                 match part_values.get_res(n("rator"))? {
                     Function(clos) => {
                         let mut new_env = clos.env.clone();
@@ -117,97 +224,152 @@ pub fn make_core_syn_env() -> SynEnv {
                             new_env = new_env.set(*p, v);
                         }
 
-                        ::runtime::eval::eval(&clos.body, new_env)
+                        with_frame(this_ast, || ::runtime::eval::eval(&clos.body, new_env))
                     },
                     BuiltInFunction(::runtime::eval::BIF(f)) => {
-                        Ok(f(part_values.get_rep_res(n("rand"))?))
+                        let args = part_values.get_rep_res(n("rand"))?;
+                        with_frame(this_ast, || f(args)).map_err(|e| e.or_at(this_ast))
                     }
-                    other => {
-                        panic!("Type soundness bug: attempted to invoke {:#?}
-                        as if it were a function", other)
+                    other => { // (only possible without typechecking)
+                        Err(rt_err(RuntimeError::NotAFunction(other), this_ast))
                     }
                 }
             })),
         typed_form!("match",
             [(lit "match"), (named "scrutinee", (call "Expr")),
              (delim "{", "{",
-                 (plus [(named "p", (call "Pat")), (lit "=>"),
+                 (plus [(named "p", (call "Pat")),
+                        (alt [(lit "if"),
+                              (named "guard", (import ["p" = "scrutinee"], (call "Expr")))],
+                             []),
+                        (lit "=>"),
                         (named "arm", (import ["p" = "scrutinee"], (call "Expr")))]))],
             /* Typesynth: */
             cust_rc_box!(move | part_types | {
                 let mut res : Option<Ty> = None;
+                let mut arm_failed = false;
 
                 for arm_part_types in part_types.march_parts(&[n("arm")]) {
                     // We don't need to manually typecheck
                     //  that the arm patterns match the scrutinee;
                     //  the import handles that for us.
 
+                    if let Some(guard) = arm_part_types.maybe_get_term(n("guard")) {
+                        let _ = ::ty_compare::must_subtype(
+                                &arm_part_types.get_res(n("guard"))?, &ty!((vr "Bool")),
+                                arm_part_types.env.clone())
+                            .map_err(|e| ::util::err::sp(e, strip_ee(&guard).clone()))?;
+                    }
+
                     let arm_res = arm_part_types.get_res(n("arm"))?;
+                    arm_failed = arm_failed || is_error_ty(&arm_res);
 
                     match res {
                         None => { res = Some(arm_res) }
                         Some(ref old_res) => {
-                            ty_exp!(old_res, &arm_res, arm_part_types.get_term(n("arm")));
+                            // (Either might mention inferred types, so this isn't just `ty_exp!`)
+                            let _ = ::ty_compare::must_subtype(
+                                    &arm_res, old_res, arm_part_types.env.clone())
+                                .map_err(|e| ::util::err::sp(
+                                    e, strip_ee(&arm_part_types.get_term(n("arm"))).clone()))?;
                         }
                     }
                 }
-                match res {
-                    None => { // TODO #2: this isn't anywhere near exhaustive
-                        ty_err!(NonExhaustiveMatch(part_types.get_res(n("scrutinee")).unwrap())
-                            at Trivial /* TODO */)
-                    },
-                    Some(ty_res) => Ok(ty_res)
+
+                // Is every possibility covered, and is every arm reachable?
+                let scrutinee_ty = part_types.get_res(n("scrutinee"))?;
+                // (Not worth asking if the arms have already gone wrong)
+                if arm_failed || is_error_ty(&scrutinee_ty) {
+                    return Ok(res.expect("ICE: `match` with no arms"));
+                }
+                let pats = part_types.get_rep_term(n("p"));
+                let guarded = part_types.march_parts(&[n("arm")]).iter()
+                    .map(|arm| arm.maybe_get_term(n("guard")).is_some()).collect::<Vec<_>>();
+                // (If the scrutinee's type is being inferred, context has to determine it first)
+                let inferring = ::ty_compare::underdetermined_source(&scrutinee_ty).is_some();
+                let (env, scrutinee) =
+                    (part_types.env.clone(), part_types.get_term(n("scrutinee")));
+                let check_coverage = move || {
+                    let scrutinee_ty = scrutinee_ty.known(&scrutinee)?;
+                    let arms : Vec<(::exhaustiveness::Pat, bool)> = pats.iter().zip(&guarded)
+                        .map(|(p, guarded)| {
+                            (::exhaustiveness::translate(p, &scrutinee_ty, &env), *guarded)
+                        }).collect();
+                    let coverage = ::exhaustiveness::check_match(&arms, &scrutinee_ty, &env);
+                    for unreachable in coverage.unreachable {
+                        warn(TyWarning::UnreachableArm, pats[unreachable].clone());
+                    }
+                    if let Some(missing) = coverage.missing {
+                        ty_err!(NonExhaustiveMatch(scrutinee_ty, format!("{}", missing))
+                            at scrutinee);
+                    }
+                    Ok(())
+                };
+                if inferring {
+                    ::ty::defer_check(Box::new(check_coverage));
+                } else {
+                    check_coverage()?;
                 }
+
+                Ok(res.expect("ICE: `match` with no arms"))
             }),
             /* Evaluation: */
             cust_rc_box!( move | part_values | {
+                let scrutinee = part_values.get_res(n("scrutinee"))?;
                 for arm_values in part_values.march_all(&[n("arm")]) {
-                    // TODO: don't we need to set a context?
-                    match arm_values.get_res(n("arm")) {
-                        Ok(res) => { return Ok(res); }
-                        Err(()) => { /* try the next one */ }
+                    // Only the pattern failing to match means "try the next arm";
+                    //  a `MatchFailure` from inside the guard or the arm is a real error.
+                    match ::ast_walk::walk::<Destructure>(&arm_values.get_term(n("p")),
+                            &::ast_walk::LazyWalkReses::new_wrapper(arm_values.env.clone())
+                                .with_context(scrutinee.clone())) {
+                        Ok(_) => {}
+                        Err(RuntimeErr { body: RuntimeError::MatchFailure(_), .. }) => continue,
+                        Err(e) => return Err(e)
+                    }
+                    // (The guard and the arm destructure the scrutinee again, via their imports)
+                    if arm_values.maybe_get_term(n("guard")).is_some() {
+                        match arm_values.get_res(n("guard"))? {
+                            Enum(b, _) if b.is("True") => { /* the arm applies */ }
+                            _ => { continue; }
+                        }
                     }
+                    return arm_values.get_res(n("arm"));
                 }
-                panic!("No arms matched! This ought to be a type error, but isn't.");
+                // TODO #2: this ought to be a type error, but isn't.
+                Err(rt_err(RuntimeError::NonExhaustiveMatch(scrutinee), &part_values.this_ast))
             })
         ),
-        /* Note that we inconveniently require the user to specify the type.
+        /* The type can be left off when context determines it
+           (e.g. an argument to a function that expects a particular enum).
            "real" languages infer the type from the (required-to-be-unique)
            component name. */
         typed_form!("enum_expr",
              [(delim "+[", "[", [(named "name", aat),
                                  (star (named "component", (call "Expr")))]),
-              (lit ":"), (named "t", (call "Type"))],
+              (alt [(lit ":"), (named "t", (call "Type"))], [])],
             /* Typesynth: */
             cust_rc_box!( move | part_types | {
-                let res : Ty = part_types.get_res(n("t"))?;
-                expect_ty_node!( (res ; find_type(&ctf_2, "enum") ; &part_types.this_ast)
-                    enum_type_parts;
-                    {
-                        for enum_type_part in enum_type_parts.march_all(&[n("name")]) {
-                            if &part_types.get_term(n("name"))
-                                    != enum_type_part.get_leaf_or_panic(&n("name")) {
-                                continue; // not the right arm
-                            }
-
-                            let component_types : Vec<Ty> =
-                                enum_type_part.get_rep_leaf_or_panic(n("component"))
-                                    .iter().map(|a| Ty::new((*a).clone())).collect();
-
-                            // TODO: check that they're the same length!
-
-                            for (t, expected_t) in part_types.get_rep_res(n("component"))?
-                                    .iter().zip(component_types) {
-                                ty_exp!(t, &expected_t, part_types.this_ast);
-                            }
-                        return Ok(res.clone());
-                        }
-
-                        ty_err!(NonexistentEnumArm
-                                    (ast_to_name(&part_types.get_term(n("name"))), res)
-                                at part_types.this_ast);
+                let arm = part_types.get_term(n("name"));
+                match part_types.maybe_get_term(n("t")) {
+                    Some(_) => {
+                        let res : Ty = ::ty::synth_annotation(&part_types, n("t"))?;
+                        check_enum_arm(&res, &arm, &part_types.get_rep_res(n("component"))?,
+                                       &part_types.env, &part_types.this_ast)?;
+                        Ok(res)
                     }
-                )
+                    None => {
+                        use walk_mode::WalkMode;
+                        let res = ::ty_compare::Subtype::underspecified(
+                            n(&format!("+[{}]+", ast_to_name(&arm).orig_sp())));
+                        let components = part_types.get_rep_res(n("component"))?;
+                        let (res_for_check, env, loc)
+                            = (res.clone(), part_types.env.clone(), part_types.this_ast.clone());
+                        ::ty::defer_check(Box::new(move || {
+                            check_enum_arm(&res_for_check, &arm, &components, &env, &loc)
+                        }));
+                        Ok(res)
+                    }
+                }
             }),
             /* Evaluate: */
             cust_rc_box!( move | part_values | {
@@ -236,6 +398,81 @@ pub fn make_core_syn_env() -> SynEnv {
                 Ok(Struct(res))
             })),
 
+        /* e.g.
+         * **[ one true ]**
+         */
+        typed_form!("tuple_expr",
+            (delim "**[", "[", (star (named "component", (call "Expr")))),
+            cust_rc_box!( move | part_types | {
+                Ok(ty!({ "Type" "tuple" :
+                    "component" => (,seq part_types.get_rep_res(n("component"))?
+                        .into_iter().map(|c : Ty| c.concrete()))
+                }))
+            }),
+            cust_rc_box!( move | part_values | {
+                Ok(Tuple(part_values.get_rep_res(n("component"))?
+                    .into_iter().map(Rc::new).collect()))
+            })),
+
+        /* e.g.
+         * *[ p with x : zero ]*
+         * Makes a copy of `p`, replacing some components. The type doesn't change.
+         */
+        typed_form!("struct_update",
+            (delim "*[", "[",
+                [(named "base", (call "Expr")), (lit "with"),
+                 (plus [(named "component_name", aat), (lit ":"),
+                        (named "component", (call "Expr"))])]),
+            cust_rc_box!( move | part_types | {
+                let struct_ty = part_types.get_res(n("base"))?;
+                expect_ty_node!( (struct_ty ; find_type(&ctf_10, "struct") ;
+                                  &part_types.this_ast)
+                    struct_type_parts;
+                    {
+                        for component_parts in part_types.march_parts(&[n("component")]) {
+                            let component_name = component_parts.get_term(n("component_name"));
+                            let expected_ty = struct_type_parts.march_all(&[n("component")])
+                                .into_iter().find(|struct_type_part|
+                                    &component_name
+                                        == struct_type_part.get_leaf_or_panic(
+                                            &n("component_name")))
+                                .map(|struct_type_part| Ty::new(
+                                    struct_type_part.get_leaf_or_panic(&n("component")).clone()));
+                            match expected_ty {
+                                Some(expected_ty) => {
+                                    ty_exp!(&component_parts.get_res(n("component"))?,
+                                            &expected_ty,
+                                            component_parts.get_term(n("component")));
+                                }
+                                None => {
+                                    ty_err!(NonexistentStructField(
+                                            ast_to_name(&component_name), struct_ty)
+                                        at component_name);
+                                }
+                            }
+                        }
+                        Ok(struct_ty.clone())
+                    })
+            }),
+            cust_rc_box!( move | part_values | {
+                match part_values.get_res(n("base"))? {
+                    Struct(mut contents) => {
+                        for component_parts in part_values.march_parts(&[n("component")]) {
+                            contents = contents.set(
+                                ast_to_name(&component_parts.get_term(n("component_name"))),
+                                component_parts.get_res(n("component"))?);
+                        }
+                        Ok(Struct(contents))
+                    }
+                    other => { // (only possible without typechecking)
+                        let component_name = part_values.get_rep_term(n("component_name"))[0]
+                            .clone();
+                        Err(rt_err(RuntimeError::NoSuchField(ast_to_name(&component_name), other),
+                                   &part_values.this_ast))
+                    }
+                }
+            })),
+
         /* e.g.
          * let_type
          *   pair = mu lhs rhs. {l: lhs, r: rhs}
@@ -250,13 +487,101 @@ pub fn make_core_syn_env() -> SynEnv {
                     (named "type_def", (import [* ["type_name" = "type_def"]], (call "Type")))]),
              (lit "in"),
              (named "body", (import [* ["type_name" = "type_def"]], (call "Expr")))],
-            Body(n("body")),
+            cust_rc_box!( move | let_type_parts | {
+                // The definitions can refer to each other (and themselves), so kind-check them
+                //  with all of the names in scope:
+                // (Like the binding, the `ExtendEnv` on each definition is ignored.)
+                let defs: Vec<Ast> = let_type_parts.get_rep_term(n("type_def")).into_iter()
+                    .map(|d| match d { ExtendEnv(body, _) => (*body).clone(), d => d }).collect();
+                let mut def_env = let_type_parts.env.clone();
+                for (name, def) in let_type_parts.get_rep_term(n("type_name")).iter().zip(&defs) {
+                    def_env = def_env.set(ast_to_name(name), Ty(def.clone()));
+                }
+                for def in &defs {
+                    let _ = ::kind::synth_kind(def, &def_env)?;
+                }
+                // In the body, the names are types, not values:
+                let mut type_names = let_type_parts.extra_info.type_names.clone();
+                for def_parts in ::ty::in_type(&let_type_parts).march_parts(&[n("type_def")]) {
+                    type_names = type_names.set(ast_to_name(&def_parts.get_term(n("type_name"))),
+                                                def_parts.get_res(n("type_def"))?);
+                }
+                let extra_info = ::ty::TyCtxt {
+                    type_names: type_names, .. let_type_parts.extra_info.clone() };
+                ::ast_walk::LazyWalkReses { extra_info: extra_info, .. let_type_parts }
+                    .get_res(n("body"))
+            }),
             // HACK: like `Body(n("body"))`, but ignoring the binding, since it's type-level.
             // This feels like it ought to be better-handled by `beta`, or maybe a kind system.
             cust_rc_box!( move | let_type_parts | {
                 eval(strip_ee(&let_type_parts.get_term(n("body"))), let_type_parts.env)
             })),
 
+        /* e.g.
+         * let x = (plus one one)
+         *     +[Cons h t]+ = l
+         * in ...
+         * The definitions are evaluated in parallel (none of them can see the others).
+         */
+        typed_form!("let",
+            [(lit "let"),
+             (star [(named "pat", (call "Pat")),
+                    (lit "="),
+                    (named "def", (call "Expr"))]),
+             (lit "in"),
+             (named "body", (import [* ["pat" = "def"]], (call "Expr")))],
+            Body(n("body")),
+            Body(n("body"))),
+
+        /* e.g.
+         * letrec
+         *   even? : [Int -> Bool] = .[n : Int . match (zero? n) { ... (odd? (minus n one)) }].
+         *   odd? : [Int -> Bool] = .[n : Int . match (zero? n) { ... (even? (minus n one)) }].
+         * in (even? five)
+         * The definitions must be functions, since they can refer to each other (and themselves).
+         */
+        typed_form!("letrec",
+            [(lit "letrec"),
+             (star [(named "name", aat),
+                    (lit ":"),
+                    (named "t", (call "Type")),
+                    (lit "="),
+                    (named "def", (import [* ["name" : "t"]], (call "Expr")))]),
+             (lit "in"),
+             (named "body", (import [* ["name" : "t"]], (call "Expr")))],
+            cust_rc_box!( move | letrec_parts | {
+                // HACK: `march_parts` doesn't keep the other repetitions around,
+                //  so the imports on the `def`s can't see all the names. Do it by hand:
+                let mut rec_env = letrec_parts.env.clone();
+                for def_parts in letrec_parts.march_parts(&[n("def")]) {
+                    let t = ::ty::synth_annotation(&def_parts, n("t"))?;
+                    let _ = expect_ty_node!( (t ; find_type(&ctf_8, "fn") ;
+                                              &def_parts.get_term(n("t")))
+                        fn_parts; fn_parts);
+                    rec_env = rec_env.set(ast_to_name(&def_parts.get_term(n("name"))), t);
+                }
+                for def_parts in letrec_parts.march_parts(&[n("def")]) {
+                    let def = def_parts.get_term(n("def"));
+                    let def_t = ::ast_walk::walk::<SynthTy>(
+                        strip_ee(&def), &letrec_parts.with_environment(rec_env.clone()))?;
+                    ty_exp!(&def_t, &def_parts.get_res(n("t"))?, def);
+                }
+                letrec_parts.get_res(n("body"))
+            }),
+            cust_rc_box!( move | letrec_parts | {
+                let names: Vec<Name> =
+                    letrec_parts.get_rep_term(n("name")).iter().map(ast_to_name).collect();
+                let defs: Vec<Ast> = letrec_parts.get_rep_term(n("def")).iter()
+                    .map(|d| strip_ee(d).clone()).collect();
+
+                let rec_env = letrec_env(&names, &defs, &letrec_parts.env);
+                let mut body_env = letrec_parts.env.clone();
+                for (name, def) in names.iter().zip(defs.iter()) {
+                    body_env = body_env.set(*name, eval(def, rec_env.clone())?);
+                }
+                eval(strip_ee(&letrec_parts.get_term(n("body"))), body_env)
+            })),
+
         /* e.g. where List = ∀ X. μ List. enum { Nil(), Cons(X, List<[X]<) }
          * .[x : List <[X]<  . match (unfold x) ... ].
          * (unfold is needed because `match` wants an `enum`, not a `μ`)
@@ -290,7 +615,7 @@ pub fn make_core_syn_env() -> SynEnv {
         typed_form!("fold",
             [(lit "fold"), (named "body", (call "Expr")), (lit ":"), (named "t", (call "Type"))],
             cust_rc_box!( move |fold_parts| {
-                let goal_type = fold_parts.get_res(n("t"))?;
+                let goal_type = ::ty::synth_annotation(&fold_parts, n("t"))?;
                 // TODO: I can't figure out how to pull this out into a function
                 //  to invoke both here and above, since `mu_type_0` needs cloning...
                 let folded_goal = expect_ty_node!(
@@ -321,16 +646,119 @@ pub fn make_core_syn_env() -> SynEnv {
             }),
             Body(n("body"))),
 
-        ::core_qq_forms::quote(/*positive=*/true)
+        typed_form!("int_literal",
+            (named "n", int_lit),
+            cust_rc_box!( move | _ | { Ok(ty!({ "Type" "Int" : })) }),
+            cust_rc_box!( move | part_values | {
+                Ok(Int(ast_to_int(&part_values.get_term(n("n")))))
+            })),
+
+        typed_form!("string_literal",
+            (named "s", string_lit),
+            cust_rc_box!( move | _ | { Ok(ty!({ "Type" "String" : })) }),
+            cust_rc_box!( move | part_values | {
+                Ok(Text(::read::string_literal(
+                    &ast_to_name(&part_values.get_term(n("s"))).orig_sp()).unwrap()))
+            })),
+
+        typed_form!("char_literal",
+            (named "c", char_lit),
+            cust_rc_box!( move | _ | { Ok(ty!({ "Type" "Char" : })) }),
+            cust_rc_box!( move | part_values | {
+                Ok(Char(::read::char_literal(
+                    &ast_to_name(&part_values.get_term(n("c"))).orig_sp()).unwrap()))
+            })),
+
+        ::core_qq_forms::quote(/*positive=*/true),
+
+        ::core_macro_forms::extend_syntax(),
+
+        ::core_macro_forms::let_syntax_helper()
+    ];
+
+    // Left-recursive expressions. These are only used if nothing above applies,
+    //  so that `unfold l . x` means `unfold (l . x)`, just like `let ... in l . x`.
+    let postfix_expr_forms = forms_to_form_pat![
+        /* e.g.
+         * (make_point one two) . x
+         */
+        typed_form!("project",
+            [(named "struct", (call "Expr")), (lit "."), (named "component_name", aat)],
+            cust_rc_box!( move | part_types | {
+                let struct_ty = part_types.get_res(n("struct"))?;
+                let component_name = part_types.get_term(n("component_name"));
+                expect_ty_node!( (struct_ty ; find_type(&ctf_9, "struct") ; &part_types.this_ast)
+                    struct_type_parts;
+                    {
+                        for struct_type_part in struct_type_parts.march_all(&[n("component")]) {
+                            if &component_name
+                                    == struct_type_part.get_leaf_or_panic(&n("component_name")) {
+                                return Ok(Ty::new(
+                                    struct_type_part.get_leaf_or_panic(&n("component")).clone()));
+                            }
+                        }
+                        ty_err!(NonexistentStructField(ast_to_name(&component_name), struct_ty)
+                            at part_types.this_ast);
+                    })
+            }),
+            cust_rc_box!( move | part_values | {
+                let component_name = ast_to_name(&part_values.get_term(n("component_name")));
+                match part_values.get_res(n("struct"))? {
+                    Struct(ref contents) if contents.find(&component_name).is_some() => {
+                        Ok(contents.find_or_panic(&component_name).clone())
+                    }
+                    other => { // (only possible without typechecking)
+                        Err(rt_err(RuntimeError::NoSuchField(component_name, other),
+                                   &part_values.this_ast))
+                    }
+                }
+            }))
     ];
 
+    /* e.g.
+     * (.[x . x]. : [Int -> Int])
+     * This is on its own so that it wins over `apply` (which would treat `:` as an argument).
+     */
+    let ascription_form = form_pat!((scope typed_form!("ascription",
+        (delim "(", "(", [(named "body", (call "Expr")), (lit ":"), (named "t", (call "Type"))]),
+        cust_rc_box!( move | part_types | {
+            let t = ::ty::synth_annotation(&part_types, n("t"))?;
+            // (This can also determine types that are being inferred)
+            let _ = ::ty_compare::must_subtype(
+                    &part_types.get_res(n("body"))?, &t, part_types.env.clone())
+                .map_err(|e| ::util::err::sp(e, part_types.this_ast.clone()))?;
+            Ok(t)
+        }),
+        Body(n("body")))));
 
     let main_pat_forms = forms_to_form_pat_export![
         negative_typed_form!("enum_pat",
             (delim "+[", "[", [(named "name", aat),
                                (star (named "component", (call "Pat")))]),
             /* (Negatively) Typecheck: */
-            cust_rc_box!( move | part_types |
+            cust_rc_box!( move | part_types | {
+                // Like with an unannotated `enum_expr`, if the type is still being inferred,
+                //  give the components types of their own, and check them once it's determined:
+                let context = part_types.context_elt().clone();
+                if ::ty_compare::underdetermined_source(&context).is_some()
+                        && context.known(&part_types.this_ast).is_err() {
+                    use walk_mode::WalkMode;
+                    let arm = part_types.get_term(n("name"));
+                    let component_types = part_types.get_rep_term(n("component")).iter().map(
+                        |_| ::ty_compare::Subtype::underspecified(
+                            n(&format!("+[{}]+", ast_to_name(&arm).orig_sp()))))
+                        .collect::<Vec<_>>();
+                    let mut res = Assoc::new();
+                    for sub_res in &part_types
+                            .get_rep_res_with(n("component"), component_types.clone())? {
+                        res = res.set_assoc(sub_res);
+                    }
+                    let (env, loc) = (part_types.env.clone(), part_types.this_ast.clone());
+                    ::ty::defer_check(Box::new(move || {
+                        check_enum_arm(&context, &arm, &component_types, &env, &loc)
+                    }));
+                    return Ok(res);
+                }
                 expect_ty_node!( (part_types.context_elt() ; find_type(&ctf_6, "enum") ;
                                       &part_types.this_ast)
                     enum_type_parts;
@@ -356,17 +784,19 @@ pub fn make_core_syn_env() -> SynEnv {
                             return Ok(res);
                         }
                         ty_err!(NonexistentEnumArm(ast_to_name(arm_name),
-                            Ty::new(Trivial)) /* TODO `LazyWalkReses` needs more information */
+                                                   part_types.context_elt().clone())
                             at arm_name.clone())
-                }
-            )),
+                })
+            }),
             /* (Negatively) Evaluate: */
             cust_rc_box!( move | part_values | {
                 match *part_values.context_elt() /* : Value */ {
                     Enum(ref name, ref elts) => {
                         // "Try another branch"
                         if name != &ast_to_name(&part_values.get_term(n("name"))) {
-                            return Err(());
+                            return Err(rt_err(
+                                RuntimeError::MatchFailure(part_values.context_elt().clone()),
+                                &part_values.this_ast));
                         }
 
                         let mut res = Assoc::new();
@@ -377,7 +807,11 @@ pub fn make_core_syn_env() -> SynEnv {
 
                         Ok(res)
                     }
-                    _ => panic!("Type ICE: non-enum")
+                    // (only possible without typechecking)
+                    ref other => {
+                        Err(rt_err(RuntimeError::MatchFailure(other.clone()),
+                                   &part_values.this_ast))
+                    }
                 }
             })) => [* ["component"]],
         negative_typed_form!("struct_pat",
@@ -423,28 +857,211 @@ pub fn make_core_syn_env() -> SynEnv {
                         let mut res = Assoc::new();
 
                         for component_ctx in part_values.march_parts(&[n("component")]) {
+                            let component = match contents.find(&ast_to_name(
+                                    &component_ctx.get_term(n("component_name")))) {
+                                Some(c) => c.clone(),
+                                None => return Err(rt_err(
+                                    RuntimeError::MatchFailure(part_values.context_elt().clone()),
+                                    &part_values.this_ast))
+                            };
                             res = res.set_assoc(
-                                &component_ctx
-                                    .with_context(contents.find_or_panic(
-                                        &ast_to_name(
-                                            &component_ctx.get_term(n("component_name"))))
-                                            .clone())
-                                    .get_res(n("component"))?);
+                                &component_ctx.with_context(component).get_res(n("component"))?);
                         }
 
                         Ok(res)
                     }
-                    _ => panic!("Type ICE: non-struct")
+                    // (only possible without typechecking)
+                    ref other => {
+                        Err(rt_err(RuntimeError::MatchFailure(other.clone()),
+                                   &part_values.this_ast))
+                    }
                 }
             }))  => [* ["component"]],
 
+        negative_typed_form!("tuple_pat",
+            (delim "**[", "[", (star (named "component", (call "Pat")))),
+            /* (Negatively) Typecheck: */
+            cust_rc_box!( move | part_types |
+                expect_ty_node!( (part_types.context_elt() ; find_type(&ctf_11, "tuple") ;
+                                      &part_types.this_ast)
+                    tuple_type_parts;
+                    {
+                        let component_types: Vec<Ty> =
+                            tuple_type_parts.get_rep_leaf_or_panic(n("component"))
+                                .into_iter().map(|c| Ty::new(c.clone())).collect();
+                        let component_count = part_types.get_rep_term(n("component")).len();
+                        if component_types.len() != component_count {
+                            ty_err!(LengthMismatch(component_types, component_count)
+                                at part_types.this_ast);
+                        }
+                        let mut res = Assoc::new();
+                        for component_env in
+                                part_types.get_rep_res_with(n("component"), component_types)? {
+                            res = res.set_assoc(&component_env);
+                        }
+                        Ok(res)
+                    })),
+            /* (Negatively) Evaluate: */
+            cust_rc_box!( move | part_values | {
+                match *part_values.context_elt() {
+                    Tuple(ref contents)
+                            if contents.len() == part_values.get_rep_term(n("component")).len() => {
+                        let mut res = Assoc::new();
+                        for component_env in part_values.get_rep_res_with(n("component"),
+                                contents.iter().map(|c| (**c).clone()).collect())? {
+                            res = res.set_assoc(&component_env);
+                        }
+                        Ok(res)
+                    }
+                    // (only possible without typechecking)
+                    ref other => {
+                        Err(rt_err(RuntimeError::MatchFailure(other.clone()),
+                                   &part_values.this_ast))
+                    }
+                }
+            })) => [* ["component"]],
+
+        negative_typed_form!("int_pat",
+            (named "n", int_lit),
+            /* (Negatively) Typecheck: */
+            cust_rc_box!( move | part_types | {
+                let _ = ::ty_compare::must_subtype(
+                    part_types.context_elt(), &ty!({ "Type" "Int" : }), part_types.env.clone())
+                    .map_err(|e| ::util::err::sp(e, part_types.this_ast.clone()))?;
+                Ok(Assoc::new())
+            }),
+            /* (Negatively) Evaluate: */
+            cust_rc_box!( move | part_values | {
+                if part_values.context_elt() == &Int(ast_to_int(&part_values.get_term(n("n")))) {
+                    Ok(Assoc::new())
+                } else { // "Try another branch"
+                    Err(rt_err(RuntimeError::MatchFailure(part_values.context_elt().clone()),
+                               &part_values.this_ast))
+                }
+            })) => [],
+
+        negative_typed_form!("string_pat",
+            (named "s", string_lit),
+            cust_rc_box!( move | part_types | {
+                let _ = ::ty_compare::must_subtype(
+                    part_types.context_elt(), &ty!({ "Type" "String" : }), part_types.env.clone())
+                    .map_err(|e| ::util::err::sp(e, part_types.this_ast.clone()))?;
+                Ok(Assoc::new())
+            }),
+            cust_rc_box!( move | part_values | {
+                let s = ::read::string_literal(
+                    &ast_to_name(&part_values.get_term(n("s"))).orig_sp()).unwrap();
+                if part_values.context_elt() == &Text(s) {
+                    Ok(Assoc::new())
+                } else {
+                    Err(rt_err(RuntimeError::MatchFailure(part_values.context_elt().clone()),
+                               &part_values.this_ast))
+                }
+            })) => [],
+
+        negative_typed_form!("char_pat",
+            (named "c", char_lit),
+            cust_rc_box!( move | part_types | {
+                let _ = ::ty_compare::must_subtype(
+                    part_types.context_elt(), &ty!({ "Type" "Char" : }), part_types.env.clone())
+                    .map_err(|e| ::util::err::sp(e, part_types.this_ast.clone()))?;
+                Ok(Assoc::new())
+            }),
+            cust_rc_box!( move | part_values | {
+                let c = ::read::char_literal(
+                    &ast_to_name(&part_values.get_term(n("c"))).orig_sp()).unwrap();
+                if part_values.context_elt() == &Char(c) {
+                    Ok(Assoc::new())
+                } else {
+                    Err(rt_err(RuntimeError::MatchFailure(part_values.context_elt().clone()),
+                               &part_values.this_ast))
+                }
+            })) => [],
+
+        // Matches anything, binding nothing:
+        negative_typed_form!("wildcard_pat",
+            (lit "_"),
+            cust_rc_box!( move | _ | { Ok(Assoc::new()) }),
+            cust_rc_box!( move | _ | { Ok(Assoc::new()) })) => [],
+
             ::core_qq_forms::quote(/*positive=*/false) => ["body"]];
 
+    /* e.g.
+     * +[Some 0]+ | +[None]+
+     * Every alternative has to bind the same names (with the same types).
+     * The alternatives are `AtomicPat`s, so that `a | b | c` is unambiguous.
+     */
+    let or_pat_forms = forms_to_form_pat_export![
+        negative_typed_form!("or_pat",
+            [(named "first", (call "AtomicPat")),
+             (plus [(lit "|"), (named "alt", (call "AtomicPat"))])],
+            /* (Negatively) Typecheck: */
+            cust_rc_box!( move | part_types | {
+                let first = part_types.get_res(n("first"))?;
+                let mut res = first.clone();
+                for (alt, alt_term) in part_types.get_rep_res(n("alt"))?.into_iter()
+                        .zip(part_types.get_rep_term(n("alt"))) {
+                    // Freshening gives each alternative its own version of each name,
+                    //  so line them up by their original spelling:
+                    for (name, ty) in first.iter_pairs() {
+                        match alt.iter_pairs().find(|&(n, _)| n.orig_sp() == name.orig_sp()) {
+                            Some((_, alt_ty)) => {
+                                ::ty_compare::must_equal(alt_ty, ty, part_types.env.clone())
+                                    .map_err(|e| ::util::err::sp(e, alt_term.clone()))?;
+                            }
+                            None => { ty_err!(OrPatBindingMismatch(*name) at alt_term); }
+                        }
+                    }
+                    for (name, _) in alt.iter_pairs() {
+                        if first.iter_keys().find(|n| n.orig_sp() == name.orig_sp()).is_none() {
+                            ty_err!(OrPatBindingMismatch(*name)
+                                    at part_types.get_term(n("first")));
+                        }
+                    }
+                    res = res.set_assoc(&alt);
+                }
+                Ok(res)
+            }),
+            /* (Negatively) Evaluate: */
+            cust_rc_box!( move | part_values | {
+                let mut alt_terms = vec![part_values.get_term(n("first"))];
+                alt_terms.append(&mut part_values.get_rep_term(n("alt")));
+
+                for alt_term in &alt_terms {
+                    let matched = match ::ast_walk::walk::<Destructure>(alt_term, &part_values) {
+                        Ok(matched) => matched,
+                        Err(RuntimeErr { body: RuntimeError::MatchFailure(_), .. }) => continue,
+                        Err(e) => return Err(e)
+                    };
+                    // Bind every alternative's version of each name:
+                    let mut res = Assoc::new();
+                    for other_term in &alt_terms {
+                        for name in ::beta::names_exported_by(other_term, 0) {
+                            if let Some((_, v)) = matched.iter_pairs()
+                                    .find(|&(n, _)| n.orig_sp() == name.orig_sp()) {
+                                res = res.set(name, v.clone());
+                            }
+                        }
+                    }
+                    return Ok(res);
+                }
+                Err(rt_err(RuntimeError::MatchFailure(part_values.context_elt().clone()),
+                           &part_values.this_ast))
+            })) => ["first" [* ["alt"]]]];
+
+    let main_pat_forms = Rc::new(main_pat_forms);
+
     assoc_n!(
         // special case; allow repetition (defined in earley.rs):
         "dotdotdot" => Rc::new(form_pat!([(delim "...(", "(", (call "body"))])),
-        "Pat" => Rc::new(Biased(Rc::new(main_pat_forms), Rc::new(AnyAtomicToken))),
-        "Expr" => Rc::new(Biased(Rc::new(main_expr_forms), Rc::new(VarRef))),
+        "Pat" => Rc::new(Biased(Rc::new(Alt(vec![main_pat_forms.clone(),
+                                                 Rc::new(or_pat_forms)])),
+                                Rc::new(AnyAtomicToken))),
+        "AtomicPat" => Rc::new(Biased(main_pat_forms.clone(), Rc::new(AnyAtomicToken))),
+        "Expr" => Rc::new(Biased(Rc::new(Biased(Rc::new(ascription_form),
+                                                Rc::new(main_expr_forms))),
+                                 Rc::new(Alt(vec![Rc::new(postfix_expr_forms),
+                                                  Rc::new(VarRef)])))),
         "Ident" => Rc::new(AnyAtomicToken)
     ).set_assoc(&ctf).set_assoc(&cmf) /* throw in the types and macros! */
 
@@ -619,6 +1236,12 @@ fn type_apply_with_subtype() { // Application can perform subtyping
             ty_env.clone()),
         Ok(nat_ty.clone()));
 
+    // Parameters are contravariant, but complaints about them shouldn't be backwards:
+    assert_m!(synth_type(&ast!(
+            { "Expr" "apply" : "rator" => (vr "nat_to_nat") , "rand" => [ (vr "I") ]}),
+            ty_env.set(n("I"), ty!({ "Type" "Int" : }))),
+        ty_err_p!(Mismatch(got, expected)),
+        got == ty!({ "Type" "Int" : }) && expected == nat_ty);
 }
 
 #[test]
@@ -790,8 +1413,8 @@ fn alg_eval() {
             "name" => "choice1",
             "component" => ["abc", "def"]
         }),
-        mt_env.set(negative_ret_val(), val!(enum "choice0", (i 12321)))),
-        Err(()));
+        mt_env.set(negative_ret_val(), val!(enum "choice0", (i 12321)))).map_err(|e| e.body),
+        Err(RuntimeError::MatchFailure(val!(enum "choice0", (i 12321)))));
 
     // Evaluate enum expression
 
@@ -868,7 +1491,8 @@ fn recursive_types() {
 
     let ty_env = assoc_n!(
         "IntList" => int_list_ty.clone(),  // this is a type definition...
-        "il_direct" => int_list_ty.clone()  // ...and this is a value with a type
+        "il_direct" => int_list_ty.clone(),  // ...and this is a value with a type
+        "an_int" => ty!({"Type" "Int" :})
         // TODO #3: ... distinguish between these kinds in the environment!
 
         // We should never have `vr`s in the environment unless "protected" by a μ.
@@ -905,8 +1529,12 @@ fn recursive_types() {
                 "name" => "Cons",
                 "component" => ["car", "cdr"],
                 "t" => (vr "IntList")
+            }, { "Pat" "enum_pat" => [* ["component"]] :
+                "name" => "Nil",
+                "component" => []
             }],
-            "arm" => [@"arm" (import ["p" = "scrutinee"] (vr "car"))]
+            "arm" => [@"arm" (import ["p" = "scrutinee"] (vr "car")),
+                             (import ["p" = "scrutinee"] (vr "an_int"))]
         }),
         ty_env.clone()),
         Ok(ty!({"Type" "Int":})));
@@ -919,8 +1547,9 @@ fn recursive_types() {
                 "name" => "Cons",
                 "component" => ["car", "cdr"],
                 "t" => (vr "IntList")
-            }],
-            "arm" => [@"arm" (import ["p" = "scrutinee"] (vr "cdr"))]
+            }, "other"],
+            "arm" => [@"arm" (import ["p" = "scrutinee"] (vr "cdr")),
+                             (import ["p" = "scrutinee"] (vr "il_direct"))]
         }),
         ty_env.clone()),
         Ok(int_list_ty.clone())
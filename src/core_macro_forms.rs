@@ -4,17 +4,19 @@ use grammar::FormPat;
 use grammar::SynEnv;
 use std::rc::Rc;
 use name::*;
+use util::err::Span;
 use form::Form;
 use form::EitherPN::{Both};
 use ast::{Ast, Node, Atom};
 use ast_walk::WalkRule::{NotWalked, LiteralLike, Custom};
 use runtime::reify::Reifiable;
-use runtime::eval::Closure;
+use runtime::eval::{Closure, RuntimeErr, RuntimeError, rt_err};
 use util::assoc::Assoc;
 use ty::{Ty, SynthTy};
 use walk_mode::{WalkElt, WalkMode};
-use core_type_forms::{more_quoted_ty, less_quoted_ty};
+use core_type_forms::{more_quoted_ty, less_quoted_ty, nt_to_type};
 use core_forms::ast_to_name;
+use beta::{Beta, ExportBeta};
 
 // Macros!
 //
@@ -35,7 +37,8 @@ use core_forms::ast_to_name;
 //  It spits out an `Ast` in which the `extend` binds `conditional` and `if ⋯` references it.
 //   Under the hood, `conditional` has the type
 //    `∀ T . [ *[ cond : Expr <[Bool]<  then : Expr <[T]<  else : Expr <[T]<   -> Expr <[T]< ]* ]
-//   ... even though it's a macro, not a function. (A kind-checker is needed here!)
+//   ... even though it's a macro, not a function.
+//   (The syntax types of its parts, like `Expr <[Bool]<`, get kind-checked like other types.)
 //
 // Everything is typechecked (including the `.{ ⋯ }.` implementation and the invocation).
 //  The macro name (`conditional`) is a bit of a hack
@@ -49,6 +52,9 @@ use core_forms::ast_to_name;
 //
 // Finally, phase-0 evaluation produces a result of `8`!
 
+// Macro implementations run at phase 1 (expansion time), so they can't see phase-0 definitions.
+// They can see the core values, plus anything defined by `let_syntax_helper`.
+
 // It's best to read this file in the voice of the /Pushing Daisies/ narrator.
 // This macro is a helper for generating `FormPat`-generating syntactic forms.
 macro_rules! syntax_syntax {
@@ -59,6 +65,7 @@ macro_rules! syntax_syntax {
             grammar: Rc::new(form_pat!( $($gram)* )),
             type_compare: ::form::Both(NotWalked,NotWalked), // Not a type
             // Binds nothing
+            synth_kind: ::form::Positive(NotWalked),
             synth_type: ::form::Both(NotWalked, cust_rc_box!(|_parts| { Ok(Assoc::new())}) ),
             eval: ::form::Positive(cust_rc_box!(|_parts| {
                 Ok(::grammar::FormPat::$syntax_name.reify())}
@@ -73,16 +80,23 @@ macro_rules! syntax_syntax {
             name: n(&stringify!($syntax_name).to_lowercase()),
             grammar: Rc::new(form_pat!( $($gram)* )),
             type_compare: ::form::Both(NotWalked,NotWalked), // Not a type
+            synth_kind: ::form::Positive(NotWalked),
             synth_type: ::form::Negative(cust_rc_box!(|parts| {
                 let mut out = ::util::assoc::Assoc::<Name, ::ty::Ty>::new();
                 $(
-                    out = out.set_assoc(&parts.get_res(n(&stringify!($arg)))?);
+                    // Atoms are just tokens (e.g. for `lit`), not binders:
+                    if let Atom(_, _) = parts.get_term(n(&stringify!($arg))) {} else {
+                        out = out.set_assoc(&parts.get_res(n(&stringify!($arg)))?);
+                    }
                 )*
                 Ok(out)
             })),
             eval: ::form::Positive(cust_rc_box!(|parts| {
                 Ok(::grammar::FormPat::$syntax_name(
-                    $( { let $arg = parts.get_res(n(&stringify!($arg)))?; $e } ),*
+                    $( { let $arg = match parts.get_term(n(&stringify!($arg))) {
+                        Atom(a, _) => a.reify(), // (atoms stand for themselves)
+                        _ => parts.get_res(n(&stringify!($arg)))?
+                    }; $e } ),*
                 ).reify())}
             )),
             quasiquote: ::form::Both(LiteralLike, LiteralLike)
@@ -94,6 +108,7 @@ macro_rules! syntax_syntax {
             name: n(&stringify!($syntax_name).to_lowercase()),
             grammar: Rc::new(form_pat!( $($gram)* )),
             type_compare: ::form::Both(NotWalked,NotWalked), // Not a type
+            synth_kind: ::form::Positive(NotWalked),
             synth_type: ::form::Negative(cust_rc_box!( $type )), // Produces a typed value
             eval: ::form::Positive(cust_rc_box!( $eval )),
             quasiquote: ::form::Both(LiteralLike, LiteralLike)
@@ -101,16 +116,37 @@ macro_rules! syntax_syntax {
     };
 }
 
+// This macro is a helper for generating `Beta`-generating and `ExportBeta`-generating forms.
+// These are only ever evaluated (at macro definition time), never typechecked.
+macro_rules! beta_syntax {
+    ($name:expr, $gram:tt |$parts:ident| $beta:expr) => {
+        Rc::new(Form {
+            name: n($name),
+            grammar: Rc::new(form_pat!( $gram )),
+            type_compare: ::form::Both(NotWalked, NotWalked), // Not a type
+            synth_kind: ::form::Positive(NotWalked),
+            synth_type: ::form::Both(NotWalked, NotWalked),
+            eval: ::form::Positive(cust_rc_box!(|$parts| { Ok($beta.reify()) })),
+            quasiquote: ::form::Both(LiteralLike, LiteralLike)
+        })
+    };
+}
+
 // Macros have types!
 // ...but they're not higher-order (i.e., you can't do anything with a macro other than invoke it).
 // This means that we can just generate a type for them at the location of invocation.
 fn macro_type(forall_ty_vars: &[Name], arguments: Assoc<Name, Ty>, output: Ty) -> Ty {
     let mut components = vec![];
     for (k,v) in arguments.iter_pairs() {
-        components.push(mbe!("component_name" => (, Atom(*k)), "component" => (, v.to_ast())));
+        // The definition and the invocation freshen the part names differently,
+        //  but they need to agree on the argument type:
+        let k = n(&k.orig_sp());
+        components.push(mbe!("component_name" => (, Atom(k, Span::nowhere())),
+                             "component" => (, v.to_ast())));
     }
     let argument_struct = Node(::core_forms::find_core_form("Type", "struct"),
-        ::util::mbe::EnvMBE::new_from_anon_repeat(components), ::beta::ExportBeta::Nothing);
+        ::util::mbe::EnvMBE::new_from_anon_repeat(components), ::beta::ExportBeta::Nothing,
+        Span::nowhere());
     let mac_fn = ast!({"Type" "fn" :
         "param" => [(, argument_struct)],
         "ret" => (, output.to_ast())
@@ -121,7 +157,8 @@ fn macro_type(forall_ty_vars: &[Name], arguments: Assoc<Name, Ty>, output: Ty) -
     } else {
         ty!({"Type" "forall_type" :
             "body" => (import [* [forall "param"]] (, mac_fn)),
-            "param" => (,seq forall_ty_vars.iter().map(|n| { Atom(*n) }).collect::<Vec<_>>())
+            "param" => (,seq forall_ty_vars.iter().map(|n| { Atom(*n, Span::nowhere()) })
+                .collect::<Vec<_>>())
         })
     }
 }
@@ -134,16 +171,18 @@ fn type_macro_invocation(macro_name: Name, parts: &LazyWalkReses<::ty::SynthTy>,
 
     for (binder, depth) in grammar.binders() {
         if let Some(nt) = grammar.find_named_call(binder).unwrap() {
-            let term_ty = if ::core_type_forms::nt_is_positive(nt) {
-                parts.flatten_res_at_depth(binder, depth, &|ty_vec: Vec<Ty>| {
-                    ty!({"Type" "tuple" :
-                        "component" => (,seq ty_vec.iter().map(|ty| ty.concrete()))
-                    })
-                })?
+            let q_term_ty = if ::core_type_forms::nt_is_positive(nt) {
+                argument_ty(parts, binder, nt, depth)?
             } else {
-                ::ty_compare::Subtype::underspecified(binder)
+                // (All the repetitions get the same type.)
+                let mut q_term_ty =
+                    more_quoted_ty(&::ty_compare::Subtype::underspecified(binder), nt);
+                for _ in 0..depth {
+                    q_term_ty = ::runtime::reify::sequence_type__of(&q_term_ty);
+                }
+                q_term_ty
             };
-            q_arguments = q_arguments.set(binder, more_quoted_ty(&term_ty, nt));
+            q_arguments = q_arguments.set(binder, q_term_ty);
         } // Otherwise, it's not a call (should we treat this more like `Pat`?)
     }
 
@@ -158,9 +197,66 @@ fn type_macro_invocation(macro_name: Name, parts: &LazyWalkReses<::ty::SynthTy>,
     Ok(q_arguments)
 }
 
+/// The (quoted) type of an argument. Repeated arguments are `Sequence`s,
+///  so all the repetitions need to have the same type.
+fn argument_ty(parts: &LazyWalkReses<::ty::SynthTy>, binder: Name, nt: Name, depth: u8)
+        -> Result<Ty, ::ty::TypeError> {
+    if depth == 0 { return Ok(more_quoted_ty(&parts.get_res(binder)?, nt)); }
+
+    let elt_ty = ::ty_compare::Subtype::underspecified(binder);
+    for sub_parts in parts.march_parts(&[binder]) {
+        let sub_ty = argument_ty(&sub_parts, binder, nt, depth - 1)?;
+        let _ = ::ty_compare::must_subtype(&sub_ty, &elt_ty, parts.env.clone())
+            .map_err(|e| ::util::err::sp(e, parts.this_ast.clone()))?;
+    }
+    Ok(::runtime::reify::sequence_type__of(&elt_ty))
+}
+
+/// The types of the names exported by a (negative) macro invocation,
+///  given the (quoted) types of its arguments.
+fn export_tys(export: &ExportBeta, parts: &LazyWalkReses<::ty::UnpackTy>,
+              arguments: &Assoc<Name, Ty>) -> Result<Assoc<Name, Ty>, ::ty::TypeError> {
+    // A `Beta` can mention a part that isn't an argument (e.g. a `lit`):
+    let argument = |binder: &Name| -> Result<Ty, ::ty::TypeError> {
+        match arguments.find(binder) {
+            Some(ty) => Ok(ty.clone()),
+            None => { ty_err!(UnboundName(*binder, None) at parts.this_ast) }
+        }
+    };
+    match *export {
+        ExportBeta::Nothing => Ok(Assoc::new()),
+        ExportBeta::Use(binder) => {
+            let mut res = Assoc::new();
+            for (ty_n, ty) in parts.with_context(argument(&binder)?)
+                    .get_res(binder)?.iter_pairs() {
+                res = res.set(*ty_n, less_quoted_ty(ty, Some(n("Pat")), &parts.this_ast)?);
+            }
+            Ok(res)
+        }
+        ExportBeta::Shadow(ref lhs, ref rhs) => {
+            Ok(export_tys(lhs, parts, arguments)?.set_assoc(&export_tys(rhs, parts, arguments)?))
+        }
+        ExportBeta::ShadowAll(ref sub, ref drivers) => {
+            // Each repetition gets the element type of the repeated arguments:
+            let mut sub_arguments = arguments.clone();
+            for driver in drivers {
+                sub_arguments = sub_arguments.set(*driver, ::runtime::reify::un__sequence_type(
+                    &argument(driver)?, &parts.this_ast)?);
+            }
+            let mut res = Assoc::new();
+            for sub_parts in parts.march_parts(drivers) {
+                res = res.set_assoc(&export_tys(sub, &sub_parts, &sub_arguments)?);
+            }
+            Ok(res)
+        }
+    }
+}
+
 // This will be called at parse-time to generate the `Ast` for a macro invocation.
 // The form it emits is analogous to the "Expr" "apply" form.
-fn macro_invocation(grammar: FormPat, macro_name: Name, export_names: Vec<Name>) -> Rc<Form> {
+// `nt` is the nonterminal that the macro extends.
+fn macro_invocation(grammar: FormPat, macro_name: Name, export: ExportBeta, nt: Name)
+        -> Rc<Form> {
     use walk_mode::WalkMode;
 
     let grammar1 = grammar.clone();
@@ -168,7 +264,18 @@ fn macro_invocation(grammar: FormPat, macro_name: Name, export_names: Vec<Name>)
     Rc::new(Form {
         name: n("macro_invocation"), // TODO: maybe generate a fresh name?
         grammar: Rc::new(grammar.clone()), // For pretty-printing
-        type_compare: ::form::Both(NotWalked, NotWalked),
+        // For type macros, the invocation stands for the type that its type says it produces:
+        type_compare: ::form::Both(
+            cust_rc_box!( |parts| {
+                let ty = ::ty::synth_type(&parts.this_ast, parts.env.clone()).map_err(|e| e.body)?;
+                ::ty_compare::canonicalize(&ty, parts.env.clone())
+            }),
+            cust_rc_box!( |parts| {
+                let ty = ::ty::synth_type(&parts.this_ast, parts.env.clone()).map_err(|e| e.body)?;
+                ::ast_walk::walk::<::ty_compare::Subtype>(&ty.concrete(), &parts)
+            })),
+        // A type macro could expand to anything, so we don't know its kind:
+        synth_kind: ::form::Positive(::kind::unknown_kind_rule()),
         // Invoked at typechecking time.
         // `macro_name` will be bound to a type of the form
         //     ∀ T . [*[x : Nt <[T]< ⋯ ]* -> Nt <[T]<]
@@ -189,40 +296,33 @@ fn macro_invocation(grammar: FormPat, macro_name: Name, export_names: Vec<Name>)
                     resolved.map_err(|e| ::util::err::sp(e, parts.this_ast.clone()))
                 })?;
 
-                less_quoted_ty(&q_result, Some(n("Expr")), &parts.this_ast)
+                less_quoted_ty(&q_result, Some(::core_type_forms::nt_base(nt)), &parts.this_ast)
             }),
             cust_rc_box!( move |parts| {
                 // From the macro's point of view, its parts are all positive;
                 // they all produce (well, expand to), rather than consume, syntax.
                 let parts_positive = parts.switch_mode::<SynthTy>();
-                let expected_return_type = more_quoted_ty(parts.context_elt(), n("Pat"));
+                let expected_return_type =
+                    more_quoted_ty(parts.context_elt(), ::core_type_forms::nt_base(nt));
 
                 let arguments = type_macro_invocation(macro_name, &parts_positive,
                                                       expected_return_type, &grammar2)?;
 
                 // What argument types made that work?
-                let mut res : Assoc<Name, Ty> = Assoc::new();
+                let mut resolved_arguments = Assoc::new();
                 ::ty_compare::unification.with(|unif| {
-                    for binder in &export_names {
-                        let ty = arguments.find_or_panic(binder);
+                    for (binder, ty) in arguments.iter_pairs() {
                         let binder_clo = ::ty_compare::resolve(
-                                ::ast_walk::Clo{
-                                    it: ty.clone(),
-                                    env: parts.env.clone()
-                                },
+                            ::ast_walk::Clo{ it: ty.clone(), env: parts.env.clone() },
                             &unif.borrow());
                         let binder_ty = ::ty_compare::canonicalize(&binder_clo.it, binder_clo.env)
                             .map_err(|e| ::util::err::sp(e, parts.this_ast.clone()))?;
-
-                        for (ty_n, ty)
-                                in parts.with_context(binder_ty).get_res(*binder)?.iter_pairs() {
-                            res = res.set(*ty_n,
-                                          less_quoted_ty(ty, Some(n("Pat")), &parts.this_ast)?);
-                        }
+                        resolved_arguments = resolved_arguments.set(*binder, binder_ty);
                     }
+                    Ok(())
+                })?;
 
-                    Ok(res)
-                })
+                export_tys(&export, &parts, &resolved_arguments)
             })
         ),
         eval: ::form::Both(NotWalked, NotWalked), // Macros should be expanded first!
@@ -242,7 +342,7 @@ pub fn make_core_macro_forms() -> SynEnv {
         syntax_syntax!( ((lit "impossible")) Impossible ) => [],
         syntax_syntax!( ((delim "{", "{", [(lit "lit"), (named "body", aat)]) ) Literal (
             body => ::name::Name::reflect(&body)
-        )) => ["body"],
+        )) => [], // (the literal isn't a binder, so don't let it get freshened)
         syntax_syntax!( ((lit "any_token")) AnyToken ) => [],
         syntax_syntax!( ((lit "atom")) AnyToken ) => [],
         syntax_syntax!( ((lit "vr")) VarRef ) => [],
@@ -336,7 +436,7 @@ pub fn make_core_macro_forms() -> SynEnv {
         } {
             |parts| {
                 Ok(Named(
-                    ::name::Name::reflect(&parts.get_res(n("part_name"))?),
+                    ast_to_name(&parts.get_term(n("part_name"))),
                     Rc::new(FormPat::reflect(&parts.get_res(n("body"))?))).reify())
             }
         }) => ["part_name"],
@@ -347,53 +447,111 @@ pub fn make_core_macro_forms() -> SynEnv {
                 (delim ",{", "{",
                     [(named "nt", aat), (delim "<[", "[", (named "ty_annot", (call "Type")))]))),
             type_compare: ::form::Both(NotWalked,NotWalked), // Not a type
+            synth_kind: ::form::Positive(NotWalked),
             synth_type: Both(cust_rc_box!(|parts| {
-                let expected_type = parts.get_res(n("ty_annot"))?;
+                let expected_type = ::ty::synth_annotation(&parts, n("ty_annot"))?;
                 let nt = ast_to_name(&parts.get_term(n("nt")));
 
                 Ok(more_quoted_ty(&expected_type, nt))
             }), NotWalked),
             eval: ::form::Positive(cust_rc_box!(|parts| {
-                Ok(Rc::new(Call(::name::Name::reflect(&parts.get_res(n("nt"))?))).reify())
+                Ok(Rc::new(Call(ast_to_name(&parts.get_term(n("nt"))))).reify())
             })),
             quasiquote: ::form::Both(LiteralLike, LiteralLike)
         }) => [],
         // `Import` is positive (has to be under a `Named`)
+        // The `beta` (e.g. `[p = e]`) says what the other parts bind in this one.
+        // (The `import` keyword is what distinguishes it from `call` when there's no `beta`.)
         Rc::new(Form {
             name: n("import"),
             grammar: Rc::new(form_pat!(
                 (delim ",{", "{",
-                    [(named "nt", aat), (delim "<[", "[", (named "ty_annot", (call "Type")))]))),
+                    [(lit "import"), (alt [], (named "beta", (call "Beta"))), (named "nt", aat),
+                     (delim "<[", "[", (named "ty_annot", (call "Type")))]))),
             type_compare: ::form::Both(NotWalked,NotWalked), // Not a type
+            synth_kind: ::form::Positive(NotWalked),
             synth_type: Both(cust_rc_box!(|parts| {
-                let expected_type = parts.get_res(n("ty_annot"))?;
+                let expected_type = ::ty::synth_annotation(&parts, n("ty_annot"))?;
                 let nt = ast_to_name(&parts.get_term(n("nt")));
 
                 Ok(more_quoted_ty(&expected_type, nt))
             }), NotWalked),
             eval: ::form::Positive(cust_rc_box!(|parts| {
-                Ok(Rc::new(Call(::name::Name::reflect(&parts.get_res(n("nt"))?))).reify())
+                let call = Rc::new(Call(ast_to_name(&parts.get_term(n("nt")))));
+                match parts.maybe_get_term(n("beta")) {
+                    Some(_) => Ok(Rc::new(NameImport(
+                        call, Beta::reflect(&parts.get_res(n("beta"))?))).reify()),
+                    None => Ok(call.reify())
+                }
+            })),
+            quasiquote: ::form::Both(LiteralLike, LiteralLike)
+        }) => [],
+        // `ComputeSyntax` is positive (has to be under a `Named`)
+        // The phase-1 function in `body` transforms the syntax environment for parsing `nt`.
+        Rc::new(Form {
+            name: n("compute_syntax"),
+            grammar: Rc::new(form_pat!(
+                (delim ",{", "{",
+                    [(lit "compute"), (delim ".{", "{", (named "body", (call "Expr"))),
+                     (named "nt", aat),
+                     (delim "<[", "[", (named "ty_annot", (call "Type")))]))),
+            type_compare: ::form::Both(NotWalked,NotWalked), // Not a type
+            synth_kind: ::form::Positive(NotWalked),
+            synth_type: Both(cust_rc_box!(|parts| {
+                // It runs during parsing, so only the core values are available:
+                let phase_1_env = ::runtime::core_values::core_types();
+                let body_ty = ::ty::synth_type(&parts.get_term(n("body")), phase_1_env.clone())?;
+                let _ = ::ty_compare::must_subtype(&body_ty, &syn_env_transformer_ty(),
+                                                   phase_1_env)
+                    .map_err(|e| ::util::err::sp(e, parts.get_term(n("body"))))?;
+
+                let expected_type = ::ty::synth_annotation(&parts, n("ty_annot"))?;
+                let nt = ast_to_name(&parts.get_term(n("nt")));
+                Ok(more_quoted_ty(&expected_type, nt))
+            }), NotWalked),
+            eval: ::form::Positive(cust_rc_box!(|parts| {
+                // The function is evaluated during parsing, when it's needed:
+                Ok(Rc::new(ComputeSyntax(ast_to_name(&parts.get_term(n("nt"))),
+                                         Rc::new(Anyways(parts.get_term(n("body")))))).reify())
             })),
             quasiquote: ::form::Both(LiteralLike, LiteralLike)
         }) => [],
-        // TODO: implement syntax for ComputeSyntax
         // Not sure if `Scope` syntax should be positive or negative.
-        syntax_syntax!( ([(lit "forall"), (star (named "param", aat)), (lit "."),
+        // `Nt ::=` says which nonterminal the macro extends (the default is `Expr`).
+        syntax_syntax!( ([(alt [], [(named "nt", aat), (lit "::=")]),
+                          (lit "forall"), (star (named "param", aat)), (lit "."),
                           (delim "'{", "{",
-                              (import [* [forall "param"]], (named "syntax", (call "Syntax")))),
+                              (named "syntax", (import [* [forall "param"]], (call "Syntax")))),
                           // We need an arbitrary negative_ret_val:
                           (named "unused_type", (anyways {trivial_type_form ; } )),
-                          (named "macro_name", aat), (lit "->"),
-                          (delim ".{", "{", (named "implementation",
-                              // TODO `beta!` needs `Shadow` so we can combine these `import`s:
-                              (import [* [forall "param"]],
-                                  (import ["syntax" = "unused_type"], (call "Expr"))))),
-                          (alt [], // TODO: needs proper `beta` structure, not just a name list:
-                               [(lit "=>"), (star (named "export", aat))])])
+                          (named "macro_name", aat),
+                          (alt [(lit "->"), (delim ".{", "{", (named "implementation",
+                                   // TODO `beta!` needs `Shadow` so we can combine these `import`s:
+                                   (import [* [forall "param"]],
+                                       (import ["syntax" = "unused_type"], (call "Expr")))))],
+                               // Pattern-based: the implementation is just a quotation:
+                               [(lit "==>"), (named "template",
+                                   (import [* [forall "param"]],
+                                       (import ["syntax" = "unused_type"], (call "Expr"))))]),
+                          (alt [], [(lit "=>"), (star (named "export", (call "ExportBeta")))])])
         Scope {
             |parts| {
-                let return_ty = parts.switch_mode::<::ty::SynthTy>().get_res(n("implementation"))?;
+                let return_ty = ::ast_walk::walk::<SynthTy>(
+                    &macro_implementation(&parts.this_ast), &parts.switch_mode::<SynthTy>())?;
+                // The implementation has to produce the right kind of syntax
+                //  (for a user-defined NT, that's the kind it expands into):
+                let _ = less_quoted_ty(
+                    &return_ty, Some(::core_type_forms::nt_base(macro_nt(&parts.this_ast))),
+                    &macro_implementation(&parts.this_ast))?;
                 let arguments = parts.get_res(n("syntax"))?;
+                // A macro can only export its arguments (its other parts don't bind anything):
+                for export in parts.get_rep_term(n("export")) {
+                    for name in beta_names(&export) {
+                        if !arguments.iter_keys().any(|arg| arg.is_freshening_of(name)) {
+                            ty_err!(UnboundName(name, None) at export);
+                        }
+                    }
+                }
                 let ty_params = &parts.get_rep_term(n("param")).iter().map(
                             |p| ast_to_name(p)).collect::<Vec<_>>();
                 Ok(Assoc::new().set(ast_to_name(&parts.get_term(n("macro_name"))),
@@ -406,27 +564,498 @@ pub fn make_core_macro_forms() -> SynEnv {
                 let _macro_params = ::beta::bound_from_export_beta(
                     &ebeta!(["syntax"]), &parts.this_ast.node_parts(), 0);
 
-                let mut export = ::beta::ExportBeta::Nothing;
-                let export_names = parts.get_rep_term(n("export")).iter()
-                    .map(ast_to_name).collect::<Vec<Name>>();
-                for name in &export_names {
-                    export = ::beta::ExportBeta::Shadow(
-                        Box::new(::beta::ExportBeta::Use(*name)),
-                        Box::new(export));
+                // The `Beta`s and `ExportBeta`s refer to parts by name:
+                let mut part_names = vec![];
+                syntax_parts(&parts.get_term(n("syntax")), &mut part_names);
+                let part_names = part_names.into_iter().map(|(part, _)| part).collect::<Vec<_>>();
+                let syntax = ::ast_walk::walk::<::runtime::eval::Eval>(
+                    &resolve_import_betas(&parts.get_term(n("syntax")), &part_names), &parts)?;
+
+                let mut exports = vec![];
+                for export in parts.get_rep_term(n("export")) {
+                    // If this mentions a non-part, typechecking will complain, so just skip it:
+                    if !beta_names(&export).iter().all(
+                            |name| part_names.iter().any(|part| part.is_freshening_of(*name))) {
+                        continue;
+                    }
+                    exports.push(ExportBeta::reflect(&::ast_walk::walk::<::runtime::eval::Eval>(
+                        &resolve_part_names(&export, &part_names), &parts)?));
                 }
+                let export = shadow_all_of(exports, ExportBeta::Nothing,
+                                           |l, r| ExportBeta::Shadow(Box::new(l), Box::new(r)));
+
+                // Smuggle the implementation into the invocation (without making it a binder),
+                //  so that expansion can find it:
+                let mut implementation = macro_implementation(&parts.this_ast);
+                while let ::ast::ExtendEnv(body, _) = implementation { implementation = *body; }
+                //  (and the name, for error messages and tracing)
+                let macro_name = ast_to_name(&parts.get_term(n("macro_name")));
+                let grammar = Seq(vec![
+                    Rc::new(FormPat::reflect(&syntax)),
+                    Rc::new(Anyways(::ast::IncompleteNode(::util::mbe::EnvMBE::new_from_leaves(
+                        Assoc::single(n("implementation"), implementation)
+                            .set(n("macro_name"), Atom(macro_name, Span::nowhere()))))))]);
 
                 // This macro invocation (will replace `syntax`)
-                Ok(Scope(macro_invocation(
-                        FormPat::reflect(&parts.get_res(n("syntax"))?),
-                        ast_to_name(&parts.get_term(n("macro_name"))),
-                        export_names),
-                    export).reify())
+                Ok(Scope(macro_invocation(grammar, macro_name, export.clone(),
+                                          macro_nt(&parts.this_ast)),
+                         export).reify())
             }
         }) => ["macro_name"] // This exports a macro, not syntax (like `binders` does)!
 
     ];
 
-    assoc_n!("Syntax" => Rc::new(grammar_grammar))
+    // How the parts of a macro invocation bind names (like the `beta!` and `ebeta!` macros):
+    let beta_grammar = forms_to_form_pat![
+        beta_syntax!("beta_seq", (delim "[", "[", (star (named "elt", (call "Beta"))))
+            |parts| shadow_all_of(
+                parts.get_rep_res(n("elt"))?.iter().map(Beta::reflect).collect(),
+                Beta::Nothing, |l, r| Beta::Shadow(Box::new(l), Box::new(r)))),
+        beta_syntax!("beta_shadow_all", (delim "*[", "[", (star (named "elt", (call "Beta"))))
+            |parts| {
+                let sub = shadow_all_of(
+                    parts.get_rep_res(n("elt"))?.iter().map(Beta::reflect).collect(),
+                    Beta::Nothing, |l, r| Beta::Shadow(Box::new(l), Box::new(r)));
+                let drivers = sub.names_mentioned();
+                Beta::ShadowAll(Box::new(sub), drivers)
+            }),
+        beta_syntax!("beta_underspecified", [(lit "forall"), (named "name", aat)]
+            |parts| Beta::Underspecified(ast_to_name(&parts.get_term(n("name"))))),
+        beta_syntax!("beta_protected", [(lit "prot"), (named "name", aat)]
+            |parts| Beta::Protected(ast_to_name(&parts.get_term(n("name"))))),
+        beta_syntax!("beta_basic", [(named "name", aat), (lit ":"), (named "ty", aat)]
+            |parts| Beta::Basic(ast_to_name(&parts.get_term(n("name"))),
+                                ast_to_name(&parts.get_term(n("ty"))))),
+        beta_syntax!("beta_same_as", [(named "name", aat), (lit "="), (named "src", aat)]
+            |parts| Beta::SameAs(ast_to_name(&parts.get_term(n("name"))),
+                                 ast_to_name(&parts.get_term(n("src")))))
+    ];
+
+    let export_beta_grammar = forms_to_form_pat![
+        beta_syntax!("export_beta_seq", (delim "[", "[", (star (named "elt", (call "ExportBeta"))))
+            |parts| shadow_all_of(
+                parts.get_rep_res(n("elt"))?.iter().map(ExportBeta::reflect).collect(),
+                ExportBeta::Nothing, |l, r| ExportBeta::Shadow(Box::new(l), Box::new(r)))),
+        beta_syntax!("export_beta_shadow_all",
+                     (delim "*[", "[", (star (named "elt", (call "ExportBeta"))))
+            |parts| {
+                let sub = shadow_all_of(
+                    parts.get_rep_res(n("elt"))?.iter().map(ExportBeta::reflect).collect(),
+                    ExportBeta::Nothing, |l, r| ExportBeta::Shadow(Box::new(l), Box::new(r)));
+                let drivers = sub.names_mentioned();
+                ExportBeta::ShadowAll(Box::new(sub), drivers)
+            }),
+        beta_syntax!("export_beta_use", (named "name", aat)
+            |parts| ExportBeta::Use(ast_to_name(&parts.get_term(n("name")))))
+    ];
+
+    assoc_n!("Syntax" => Rc::new(grammar_grammar),
+             "Beta" => Rc::new(beta_grammar),
+             "ExportBeta" => Rc::new(export_beta_grammar))
+}
+
+/// Combine `Beta`s (or `ExportBeta`s) the same way that `beta!` does.
+fn shadow_all_of<B>(betas: Vec<B>, nothing: B, shadow: fn(B, B) -> B) -> B {
+    betas.into_iter().rev().fold(nothing, |rest, beta| shadow(beta, rest))
+}
+
+/// The nonterminal that a macro definition extends.
+fn macro_nt(macro_def: &Ast) -> Name {
+    match *macro_def {
+        Node(_, ref parts, _, _) => parts.get_leaf(n("nt")).map_or(n("Expr"), ast_to_name),
+        _ => n("Expr")
+    }
+}
+
+/// The implementation of a macro definition.
+/// For a pattern-based definition, that's its template with the arguments unquoted.
+fn macro_implementation(macro_def: &Ast) -> Ast {
+    let def_parts = macro_def.node_parts();
+    match def_parts.get_leaf(n("implementation")) {
+        Some(implementation) => implementation.clone(),
+        None => {
+            let mut arguments = vec![];
+            syntax_parts(def_parts.get_leaf_or_panic(&n("syntax")), &mut arguments);
+            let arguments = arguments.into_iter().filter_map(
+                |(part, nt)| nt.map(|nt| (part, nt))).collect::<Vec<_>>();
+            implicitly_unquote(def_parts.get_leaf_or_panic(&n("template")), &arguments, 0)
+        }
+    }
+}
+
+/// Find the parts that some `Syntax` binds,
+///  and the nonterminals of the ones that are arguments (i.e. `call`s, `import`s, etc.).
+fn syntax_parts(syntax: &Ast, res: &mut Vec<(Name, Option<Name>)>) {
+    match *syntax {
+        Node(ref f, ref parts, _, _) => {
+            if f.name == n("named") {
+                let mut body = parts.get_leaf_or_panic(&n("body"));
+                while let ::ast::ExtendEnv(ref sub, _) = *body { body = sub; }
+                let nt = match *body {
+                    Node(ref body_f, ref body_parts, _, _)
+                            if body_f.name == n("call") || body_f.name == n("import")
+                                || body_f.name == n("compute_syntax") => {
+                        Some(ast_to_name(body_parts.get_leaf_or_panic(&n("nt"))))
+                    }
+                    _ => None
+                };
+                res.push((ast_to_name(parts.get_leaf_or_panic(&n("part_name"))), nt));
+            }
+            parts.map(&mut |sub| syntax_parts(sub, res));
+        }
+        ::ast::ExtendEnv(ref body, _) => syntax_parts(body, res),
+        _ => {}
+    }
+}
+
+/// `Beta`s mention parts by name, but (not being binders) they don't get freshened along with
+///  the parts. So point them at the parts that are freshened versions of them.
+fn resolve_part_names(beta: &Ast, part_names: &[Name]) -> Ast {
+    match *beta {
+        Atom(name, span) => Atom(part_names.iter().find(|part| part.is_freshening_of(name))
+            .cloned().unwrap_or(name), span),
+        Node(ref f, ref parts, ref export, ref span) => {
+            Node(f.clone(), parts.map(&mut |sub| resolve_part_names(sub, part_names)),
+                 export.clone(), span.clone())
+        }
+        _ => beta.clone()
+    }
+}
+
+/// The part names that a `Beta` (or `ExportBeta`) mentions.
+fn beta_names(beta: &Ast) -> Vec<Name> {
+    match *beta {
+        Atom(name, _) => vec![name],
+        Node(_, ref parts, _, _) => {
+            parts.map_reduce(&|sub| beta_names(sub), &|a, b| a.iter().chain(b).cloned().collect(),
+                             vec![])
+        }
+        _ => vec![]
+    }
+}
+
+/// Apply `resolve_part_names` to the `Beta` of each `import` in some `Syntax`.
+fn resolve_import_betas(syntax: &Ast, part_names: &[Name]) -> Ast {
+    match *syntax {
+        Node(ref f, ref parts, ref export, ref span) => {
+            let is_import = f.name == n("import");
+            Node(f.clone(), parts.named_map(&mut |part_name, sub| {
+                if is_import && *part_name == n("beta") {
+                    resolve_part_names(sub, part_names)
+                } else {
+                    resolve_import_betas(sub, part_names)
+                }
+            }), export.clone(), span.clone())
+        }
+        ::ast::ExtendEnv(ref body, ref beta) => {
+            ::ast::ExtendEnv(Box::new(resolve_import_betas(body, part_names)), beta.clone())
+        }
+        _ => syntax.clone()
+    }
+}
+
+/// Inside the template's quotation, a reference to an argument means its syntax,
+///  so turn it into an unquotation. (Freshening may have renamed the argument, but not the
+///  quoted references to it, so we look for the argument that's a freshened version of them.)
+fn implicitly_unquote(a: &Ast, arguments: &[(Name, Name)], depth: u8) -> Ast {
+    let find_argument = |name: Name| {
+        arguments.iter().find(|&&(arg, _)| arg.is_freshening_of(name)).cloned()
+    };
+    // (Errors in the template should point at the reference.)
+    let unquotation = |arg: Name, nt: Name, span: Span| {
+        ast!({::core_qq_forms::unquote_form(nt, true, 1) ;
+              "nt" => (, ::ast::VariableReference(nt, Span::nowhere())),
+              "body" => (-- 1 (, ::ast::VariableReference(arg, span)))}).with_span(span)
+    };
+    match *a {
+        ::ast::VariableReference(name, span) if depth == 1 => {
+            match find_argument(name) {
+                Some((arg, nt)) => unquotation(arg, nt, span),
+                None => a.clone()
+            }
+        }
+        // Patterns bind with atoms:
+        Atom(name, span) if depth == 1 => {
+            match find_argument(name) {
+                Some((arg, nt)) if nt == n("Pat") => unquotation(arg, nt, span),
+                _ => a.clone()
+            }
+        }
+        Node(ref f, ref parts, ref export, ref span) => {
+            let is_ddd = depth == 1 && f.name == n("dotdotdot");
+            Node(f.clone(), parts.named_map(&mut |part_name, sub| {
+                match *sub {
+                    // `...[arg >> ⋯]...` marches over the argument itself:
+                    ::ast::VariableReference(driver, _) if is_ddd && *part_name == n("driver") => {
+                        ::ast::VariableReference(
+                            find_argument(driver).map_or(driver, |(arg, _)| arg), sub.span())
+                    }
+                    _ => implicitly_unquote(sub, arguments, depth)
+                }
+            }), export.clone(), span.clone())
+        }
+        ::ast::QuoteMore(ref body, pos) => {
+            ::ast::QuoteMore(Box::new(implicitly_unquote(body, arguments, depth + 1)), pos)
+        }
+        ::ast::QuoteLess(ref body, less) => {
+            ::ast::QuoteLess(
+                Box::new(implicitly_unquote(body, arguments, depth.saturating_sub(less))), less)
+        }
+        ::ast::ExtendEnv(ref body, ref beta) => {
+            ::ast::ExtendEnv(Box::new(implicitly_unquote(body, arguments, depth)), beta.clone())
+        }
+        _ => a.clone()
+    }
+}
+
+/// The type of the functions that `ComputeSyntax` uses.
+fn syn_env_transformer_ty() -> Ty {
+    ty!({"Type" "fn" : "param" => [(vr "SynEnv")], "ret" => (vr "SynEnv")})
+}
+
+/// Run (at phase 1) the function that `f` evaluates to on `se`.
+pub fn compute_syntax(se: &SynEnv, f: &Ast) -> Result<SynEnv, RuntimeErr> {
+    use runtime::eval::Value::*;
+    let f_val = ::runtime::eval::eval(f, ::runtime::core_values::core_values())?;
+    let se_val = ::grammar::reify_syn_env(se);
+    let res = match f_val {
+        Function(ref closure) => {
+            ::runtime::eval::eval(&closure.body, closure.env.set(closure.params[0], se_val))?
+        }
+        BuiltInFunction(ref bif) => (*bif.0)(vec![se_val])?,
+        other => return Err(rt_err(RuntimeError::NotAFunction(other), f))
+    };
+    ::grammar::reflect_syn_env(&res, f)
+}
+
+/// `extend_syntax <nonterminal declarations> <macro definitions> in <body>`
+/// `nonterminal Nt of Base` declares a new nonterminal, whose forms (macros) expand into `Base`.
+/// The macros are added to their nonterminals (`Expr` unless otherwise specified) while parsing
+/// `body`, and are in scope while typechecking it.
+/// (Expansion gets rid of this form entirely.)
+pub fn extend_syntax() -> Rc<Form> {
+    let perform_extension = move |se: SynEnv, extension_info: Ast| -> SynEnv {
+        let ext_parts = match extension_info {
+            // The nonterminal declarations, then the macro definitions:
+            ::ast::Shape(ref subs) => subs.iter().fold(::util::mbe::EnvMBE::new(), |acc, sub| {
+                match *sub {
+                    ::ast::IncompleteNode(ref parts) => acc.merge(parts),
+                    _ => panic!("ICE: malformed syntax extension")
+                }
+            }),
+            _ => panic!("ICE: malformed syntax extension")
+        };
+        let mut new_se = se.clone();
+        let mut new_nts = ::core_type_forms::user_nts_in(&se);
+        for nt_decl in ext_parts.march_all(&[n("new_nt"), n("new_nt_base")]) {
+            let new_nt = ast_to_name(nt_decl.get_leaf_or_panic(&n("new_nt")));
+            let base = ast_to_name(nt_decl.get_leaf_or_panic(&n("new_nt_base")));
+            // Typechecking will complain about these:
+            if base != n("Expr") && base != n("Pat") && base != n("Type") { continue; }
+            if ::core_forms::get_core_forms().find(&new_nt).is_some() { continue; }
+            new_nts = new_nts.set(new_nt, base);
+            if new_se.find(&new_nt).is_none() {
+                new_se = new_se.set(new_nt, Rc::new(Alt(vec![])));
+            }
+        }
+        for macro_def in ext_parts.get_rep_leaf_or_panic(n("macro")) {
+            // Typechecking will complain about an unknown nonterminal:
+            if new_se.find(&macro_nt(macro_def)).is_none() { continue; }
+            // If this fails, so will typechecking (with a better error), so just skip it:
+            let syntax = ::core_type_forms::with_user_nts(
+                new_nts.clone(), || ::runtime::eval::eval_top(macro_def));
+            if let Ok(syntax) = syntax {
+                new_se = ::core_forms::insert_form_pat(&new_se, macro_nt(macro_def),
+                                                       &FormPat::reflect(&syntax));
+            }
+        }
+        // Like "extend_syntax_body", this isn't really a nonterminal;
+        //  it's where nested extensions can find the nonterminals declared so far:
+        new_se = new_se.set(n("user_nts"), Rc::new(Seq(new_nts.iter_pairs().map(
+            |(nt, base)| Rc::new(Named(*nt, Rc::new(Call(*base))))).collect())));
+        new_se.set(n("extend_syntax_body"), Rc::new(form_pat!(
+            // HACK: like with quotation, the LHS isn't in our `Scope`, so put it back:
+            [(anyways (, extension_info.clone())),
+             (lit "in"), (named "body", (call "Expr"))])))
+    };
+
+    Rc::new(Form {
+        name: n("extend_syntax"),
+        grammar: Rc::new(form_pat!([(lit "extend_syntax"),
+            (extend [(star [(lit "nonterminal"), (named "new_nt", aat),
+                            (lit "of"), (named "new_nt_base", aat)]),
+                     (star (named "macro", (call "Syntax")))], "extend_syntax_body",
+                    perform_extension)])),
+        type_compare: ::form::Both(NotWalked, NotWalked),
+        synth_kind: ::form::Positive(NotWalked),
+        synth_type: ::form::Positive(cust_rc_box!(|parts| {
+            // New nonterminals have to expand into something we know how to typecheck:
+            for base in parts.get_rep_term(n("new_nt_base")) {
+                let base_name = ast_to_name(&base);
+                if base_name != n("Expr") && base_name != n("Pat") && base_name != n("Type") {
+                    let suggestion = ::util::err::closest_spelling(
+                        &base_name.orig_sp(), vec!["Expr", "Pat", "Type"]).map(n);
+                    ty_err!(UnboundName(base_name, suggestion) at base);
+                }
+            }
+            // ...and can't replace the built-in ones:
+            let mut new_nts = Assoc::new();
+            for nt_decl in parts.march_parts(&[n("new_nt")]) {
+                let new_nt = nt_decl.get_term(n("new_nt"));
+                if ::core_forms::get_core_forms().find(&ast_to_name(&new_nt)).is_some() {
+                    ty_err!(NtRedefinition(ast_to_name(&new_nt)) at new_nt);
+                }
+                new_nts = new_nts.set(ast_to_name(&new_nt),
+                                      ast_to_name(&nt_decl.get_term(n("new_nt_base"))));
+            }
+            // The new nonterminals only mean anything inside this form:
+            ::core_type_forms::with_user_nts(new_nts, || type_syntax_extension(&parts))
+        })),
+        eval: ::form::Positive(cust_rc_box!(|parts| {
+            // Expansion should have removed us, but we know what to do anyways:
+            parts.get_res(n("body"))
+        })),
+        quasiquote: ::form::Both(LiteralLike, LiteralLike)
+    })
+}
+
+/// Typecheck the macro definitions and body of an `extend_syntax`
+///  (with its nonterminals declared).
+fn type_syntax_extension(parts: &LazyWalkReses<SynthTy>) -> Result<Ty, ::ty::TypeError> {
+    // Macros have to extend a nonterminal that exists:
+    for macro_def in parts.get_rep_term(n("macro")) {
+        let nt = macro_nt(&macro_def);
+        let base = ::core_type_forms::nt_base(nt);
+        if base != n("Expr") && base != n("Pat") && base != n("Type") {
+            let mut known = vec!["Expr", "Pat", "Type"];
+            let new_nts = parts.get_rep_term(n("new_nt")).iter().map(ast_to_name)
+                .map(|new_nt| new_nt.orig_sp()).collect::<Vec<_>>();
+            known.extend(new_nts.iter().map(String::as_str));
+            let suggestion = ::util::err::closest_spelling(&nt.orig_sp(), known).map(n);
+            ty_err!(UnboundName(nt, suggestion)
+                    at macro_def.node_parts().get_leaf(n("nt")).unwrap_or(&macro_def));
+        }
+    }
+    // The new nonterminals' syntax types (e.g. `Arith <[Int]<`) can be written at any phase:
+    let mut nt_tys = Assoc::new();
+    for new_nt in parts.get_rep_term(n("new_nt")) {
+        nt_tys = nt_tys.set(ast_to_name(&new_nt), nt_to_type(ast_to_name(&new_nt)));
+    }
+    let mut body_env = parts.env.set_assoc(&nt_tys);
+    // Like `Syntax` in general, macro definitions are typechecked negatively,
+    //  but don't care about the context.
+    // Their implementations run at phase 1, so they can't see anything from phase 0,
+    //  but the syntax they quote can:
+    let def_env = ::ty::phase_1_tys(parts).set_assoc(&nt_tys)
+        .set(negative_ret_val(), ty!((trivial)));
+    let mut macro_tys = Assoc::new();
+    for macro_def in parts.get_rep_term(n("macro")) {
+        macro_tys = macro_tys.set_assoc(&::ast_walk::walk::<::ty::UnpackTy>(&macro_def,
+            &LazyWalkReses::new_mq_wrapper(def_env.clone(), vec![parts.env.clone()]))?);
+    }
+    body_env = body_env.set_assoc(&macro_tys);
+    // The new syntax can be used by macro implementations in `body`, and in quoted syntax, too:
+    let extra_info = ::ty::TyCtxt {
+        type_names: parts.extra_info.type_names.set_assoc(&nt_tys),
+        phase_1: parts.extra_info.phase_1.set_assoc(&nt_tys).set_assoc(&macro_tys) };
+    let mut more_quoted_env = parts.more_quoted_env.clone();
+    if more_quoted_env.is_empty() { more_quoted_env.push(Ty::core_env()); }
+    let more_quoted_env = more_quoted_env.into_iter()
+        .map(|env| env.set_assoc(&nt_tys).set_assoc(&macro_tys)).collect();
+    ::ast_walk::walk::<SynthTy>(&parts.get_term(n("body")),
+                                &LazyWalkReses { extra_info: extra_info,
+                                                 more_quoted_env: more_quoted_env,
+                                                 .. parts.clone() }
+                                    .with_environment(body_env))
+}
+
+/// `let_syntax_helper name = <expr> in <body>`
+/// `name` is only visible to macro implementations (in `body`), not to `body` itself.
+/// `<expr>` is evaluated during macro expansion, and can only see phase-1 things.
+pub fn let_syntax_helper() -> Rc<Form> {
+    Rc::new(Form {
+        name: n("let_syntax_helper"),
+        grammar: Rc::new(form_pat!([(lit "let_syntax_helper"), (named "name", aat), (lit "="),
+                                    (named "def", (call "Expr")), (lit "in"),
+                                    (named "body", (call "Expr"))])),
+        type_compare: ::form::Both(NotWalked, NotWalked),
+        synth_kind: ::form::Positive(NotWalked),
+        synth_type: ::form::Positive(cust_rc_box!(|parts| {
+            let def_ty = ::ty::synth_type(&parts.get_term(n("def")), ::ty::phase_1_tys(&parts))?;
+            let extra_info = ::ty::TyCtxt {
+                phase_1: parts.extra_info.phase_1.set(ast_to_name(&parts.get_term(n("name"))),
+                                                      def_ty),
+                .. parts.extra_info.clone() };
+            LazyWalkReses { extra_info: extra_info, .. parts }.get_res(n("body"))
+        })),
+        eval: ::form::Positive(cust_rc_box!(|parts| {
+            // Expansion should have removed us, and phase-0 code can't see the helper anyways:
+            parts.get_res(n("body"))
+        })),
+        quasiquote: ::form::Both(LiteralLike, LiteralLike)
+    })
+}
+
+fn expand_let_syntax_helper(parts: LazyWalkReses<ExpandMacros>) -> Result<Ast, RuntimeErr> {
+    let def_val = ::runtime::eval::eval(&parts.get_term(n("def")), phase_1_vals(&parts))?;
+    let body = LazyWalkReses {
+        extra_info: parts.extra_info.set(ast_to_name(&parts.get_term(n("name"))), def_val),
+        .. parts.clone() }.get_res(n("body"))?;
+    if !expanding_one_layer.with(|o| *o.borrow()) { return Ok(body); }
+
+    // The helper will be needed for the next layer, so keep it around:
+    match parts.this_ast {
+        Node(ref f, ref helper_parts, ref export, span) => {
+            let mut helper_parts = helper_parts.clone();
+            helper_parts.add_leaf(n("body"), body);
+            Ok(Node(f.clone(), helper_parts, export.clone(), span))
+        }
+        _ => panic!("ICE: `let_syntax_helper` isn't a node")
+    }
+}
+
+/// A record of one macro invocation being expanded (for debugging macros).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpansionStep {
+    pub macro_name: Name,
+    pub arguments: Vec<(Name, Ast)>,
+    pub result: Ast,
+    /// How many expansions this one happened inside of.
+    pub depth: usize
+}
+
+impl ::std::fmt::Display for ExpansionStep {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let indent = "  ".repeat(self.depth);
+        write!(f, "{}`{}`", indent, self.macro_name)?;
+        fn write_arg(f: &mut ::std::fmt::Formatter, arg: &Ast) -> ::std::fmt::Result {
+            match *arg {
+                ::ast::Shape(ref reps) => {
+                    write!(f, "[")?;
+                    for (i, rep) in reps.iter().enumerate() {
+                        if i > 0 { write!(f, " ")?; }
+                        write_arg(f, rep)?;
+                    }
+                    write!(f, "]")
+                }
+                _ => write!(f, "{}", arg)
+            }
+        }
+        for &(ref arg_name, ref arg) in &self.arguments {
+            write!(f, "  {} = ", arg_name.orig_sp())?;
+            write_arg(f, arg)?;
+        }
+        write!(f, "\n{}  ⟿ {}", indent, self.result)
+    }
+}
+
+thread_local! {
+    /// When this is `Some`, expansion records every macro invocation it expands.
+    pub static expansion_trace : ::std::cell::RefCell<Option<Vec<ExpansionStep>>>
+        = ::std::cell::RefCell::new(None);
+    /// When this is set, the results of expanding macros aren't themselves expanded.
+    pub static expanding_one_layer : ::std::cell::RefCell<bool> = ::std::cell::RefCell::new(false);
+    static expansion_depth : ::std::cell::RefCell<usize> = ::std::cell::RefCell::new(0);
 }
 
 custom_derive!{
@@ -438,55 +1067,134 @@ custom_derive!{
     pub struct UnusedNegativeExpandMacros {}
 }
 
-fn expand_macro(parts: ::ast_walk::LazyWalkReses<ExpandMacros>) -> Result<Ast, ()> {
-    let mut env = Assoc::new(); // TODO: there should probably be something in scope here...
+/// Repeated arguments become (possibly nested) `Sequence`s, marching in lockstep with the syntax.
+/// (The `Ast` is for tracing; repetitions are `Shape`s.)
+fn argument_value(terms: &::util::mbe::EnvMBE<Ast>, binder: Name, depth: u8)
+        -> (::runtime::eval::Value, Ast) {
+    if depth == 0 {
+        // The invocation's `import`s are for typechecking it;
+        //  the expansion will have its own binding structure.
+        let mut argument = terms.get_leaf_or_panic(&binder).clone();
+        while let ::ast::ExtendEnv(body, _) = argument { argument = *body; }
+        return (::runtime::eval::Value::from_ast(&argument), argument);
+    }
+    let (values, arguments) : (Vec<_>, Vec<_>) = terms.march_all(&[binder]).iter()
+        .map(|sub_terms| argument_value(sub_terms, binder, depth - 1)).unzip();
+    (::runtime::eval::Value::Sequence(values.into_iter().map(Rc::new).collect()),
+     ::ast::Shape(arguments))
+}
+
+/// The values that macro implementations (which run at phase 1) can refer to.
+fn phase_1_vals(parts: &LazyWalkReses<ExpandMacros>) -> Assoc<Name, ::runtime::eval::Value> {
+    ::runtime::core_values::core_values().set_assoc(&parts.extra_info)
+}
+
+fn expand_macro(parts: ::ast_walk::LazyWalkReses<ExpandMacros>) -> Result<Ast, RuntimeErr> {
+    let mut env = phase_1_vals(&parts);
 
     let macro_form: &Form = parts.this_ast.node_form();
+    let macro_name = ast_to_name(&parts.get_term(n("macro_name")));
 
     // Turn the subterms into values
-    for (binder, _depth) in macro_form.grammar.binders() {
+    let mut arguments = vec![];
+    let terms = parts.parts.map(&mut |part| part.term.clone());
+    for (binder, depth) in macro_form.grammar.binders() {
         if let Some(_nt) = macro_form.grammar.find_named_call(binder).unwrap() {
-            env = env.set(binder, ::runtime::eval::Value::from_ast(&parts.get_term(binder)));
+            let (value, argument) = argument_value(&terms, binder, depth);
+            env = env.set(binder, value);
+            arguments.push((binder, argument));
         } // Otherwise, it's not a call (presumably a binder)
     }
 
-    let expanded = ::runtime::eval::eval(&parts.get_term(n("implementation")), env)?.to_ast();
+    let expanded = match ::runtime::eval::eval(&parts.get_term(n("implementation")), env)
+            .map_err(|e| rt_err(RuntimeError::MacroExpansionFailure(macro_name, Box::new(e)),
+                                &parts.this_ast))? {
+        ::runtime::eval::Value::AbstractSyntax(expanded) => expanded,
+        // (Typechecking rules this out, but we might not have typechecked)
+        other => return Err(rt_err(RuntimeError::NotSyntax(other), &parts.this_ast))
+    };
+
+    let depth = expansion_depth.with(|d| *d.borrow());
+    expansion_trace.with(|trace| {
+        if let Some(ref mut trace) = *trace.borrow_mut() {
+            trace.push(ExpansionStep {
+                macro_name: macro_name, arguments: arguments, result: expanded.clone(),
+                depth: depth });
+        }
+    });
+
+    if expanding_one_layer.with(|o| *o.borrow()) { return Ok(expanded); }
 
-    expand(&expanded, parts.env)
+    expansion_depth.with(|d| *d.borrow_mut() += 1);
+    let res = ::ast_walk::walk::<ExpandMacros>(&expanded, &LazyWalkReses {
+        extra_info: parts.extra_info.clone(), .. LazyWalkReses::new_wrapper(parts.env) });
+    expansion_depth.with(|d| *d.borrow_mut() -= 1);
+    res
 }
 
 impl WalkMode for ExpandMacros {
     fn name() -> &'static str { "MExpand" }
     type Elt = Ast;
     type Negated = UnusedNegativeExpandMacros;
-    type Err = ();  // TODO: should be the same as runtime errors
+    type Err = RuntimeErr;
     type D = ::walk_mode::Positive<ExpandMacros>;
-    type ExtraInfo = ();
+    /// The values of what macro implementations can see, besides the core values.
+    type ExtraInfo = Assoc<Name, ::runtime::eval::Value>;
 
     fn get_walk_rule(f: &Form) -> ::ast_walk::WalkRule<ExpandMacros> {
         if f.name == n("macro_invocation") {
             cust_rc_box!(expand_macro)
+        } else if f.name == n("extend_syntax") {
+            // The macros have done their job by now:
+            cust_rc_box!(|parts: LazyWalkReses<ExpandMacros>| parts.get_res(n("body")))
+        } else if f.name == n("let_syntax_helper") {
+            cust_rc_box!(expand_let_syntax_helper)
         } else {
             LiteralLike
         }
     }
-    fn automatically_extend_env() -> bool { true }
+    // Expansion just rebuilds everything that isn't a macro invocation:
+    fn walk_var(name: Name, cnc: &LazyWalkReses<ExpandMacros>) -> Result<Ast, RuntimeErr> {
+        Ok(::ast::VariableReference(name, cnc.this_ast.span()))
+    }
+    fn walk_atom(name: Name, cnc: &LazyWalkReses<ExpandMacros>) -> Result<Ast, RuntimeErr> {
+        Ok(Atom(name, cnc.this_ast.span()))
+    }
+    fn automatically_extend_env() -> bool { false }
 }
 impl WalkMode for UnusedNegativeExpandMacros {
     fn name() -> &'static str { "XXXXX" }
     type Elt = Ast;
     type Negated = ExpandMacros;
-    type Err = ();
+    type Err = RuntimeErr;
     type D = ::walk_mode::Positive<UnusedNegativeExpandMacros>;
-    type ExtraInfo = ();
+    type ExtraInfo = Assoc<Name, ::runtime::eval::Value>;
     fn get_walk_rule(_: &Form) -> ::ast_walk::WalkRule<UnusedNegativeExpandMacros> { panic!("ICE") }
     fn automatically_extend_env() -> bool { panic!("ICE") }
 }
 
-pub fn expand(ast: &Ast, env: Assoc<Name, Ast>) -> Result<Ast, ()> {
+pub fn expand(ast: &Ast, env: Assoc<Name, Ast>) -> Result<Ast, RuntimeErr> {
     ::ast_walk::walk::<ExpandMacros>(ast, &LazyWalkReses::new_wrapper(env))
 }
 
+/// Expand `ast`, and also return a record of every expansion that happened.
+pub fn expand_traced(ast: &Ast) -> Result<(Ast, Vec<ExpansionStep>), RuntimeErr> {
+    let old_trace = expansion_trace.with(|t| t.replace(Some(vec![])));
+    let res = expand(ast, Assoc::new());
+    let trace = expansion_trace.with(|t| t.replace(old_trace)).unwrap();
+    res.map(|expanded| (expanded, trace))
+}
+
+/// Expand only the outermost macro invocations in `ast`
+///  (so the result might have more macro invocations in it).
+/// If there are no steps, `ast` is fully expanded.
+pub fn expand_one_layer(ast: &Ast) -> Result<(Ast, Vec<ExpansionStep>), RuntimeErr> {
+    let old_one_layer = expanding_one_layer.with(|o| o.replace(true));
+    let res = expand_traced(ast);
+    expanding_one_layer.with(|o| *o.borrow_mut() = old_one_layer);
+    res
+}
+
 
 #[test]
 fn formpat_reflection() {
@@ -507,6 +1215,54 @@ fn formpat_reflection() {
         Literal(n("<--->")));
 }
 
+#[test]
+fn beta_reflection() {
+    use ::runtime::eval::eval_top;
+    use core_forms::find_form;
+    let macro_forms = make_core_macro_forms();
+
+    assert_eq!(
+        Beta::reflect(&eval_top(
+            &ast!({find_form(&macro_forms, "Beta", "beta_shadow_all");
+                "elt" => [{find_form(&macro_forms, "Beta", "beta_same_as");
+                    "name" => "p", "src" => "e"}]})).unwrap()),
+        beta!([* ["p" = "e"]]));
+
+    assert_eq!(
+        ExportBeta::reflect(&eval_top(
+            &ast!({find_form(&macro_forms, "ExportBeta", "export_beta_seq");
+                "elt" => [{find_form(&macro_forms, "ExportBeta", "export_beta_use");
+                              "name" => "a"},
+                          {find_form(&macro_forms, "ExportBeta", "export_beta_shadow_all");
+                              "elt" => [{find_form(&macro_forms, "ExportBeta", "export_beta_use");
+                                  "name" => "b"}]}]})).unwrap()),
+        ebeta!(["a" [* ["b"]]]));
+
+    // An `import` with a `Beta` becomes a `NameImport`:
+    assert_eq!(
+        FormPat::reflect(&eval_top(
+            &ast!({find_form(&macro_forms, "Syntax", "import");
+                "beta" => {find_form(&macro_forms, "Beta", "beta_seq");
+                    "elt" => [{find_form(&macro_forms, "Beta", "beta_basic");
+                        "name" => "x", "ty" => "t"}]},
+                "nt" => "Expr", "ty_annot" => {"Type" "Int" :}})).unwrap()),
+        NameImport(Rc::new(Call(n("Expr"))), beta!(["x" : "t"])));
+}
+
+#[test]
+fn call_and_import_syntax() {
+    use read::{TokenTree, Group, Simple};
+    let form_name = |toks| {
+        match ::earley::parse(&form_pat!((call "Syntax")), &::core_forms::get_core_forms(), &toks) {
+            Ok(Node(ref f, _, _, _)) => f.name,
+            other => panic!("{:#?}", other)
+        }
+    };
+    // Without its keyword, `import` would be ambiguous with `call`:
+    assert_eq!(form_name(tokens!({","; "Expr" ["<"; "Int"]})), n("call"));
+    assert_eq!(form_name(tokens!({","; "import" "Expr" ["<"; "Int"]})), n("import"));
+}
+
 #[test]
 fn macro_definitions() {
     let expr_type = ast!({::core_type_forms::get__abstract_parametric_type() ; "name" => "Expr" });
@@ -646,7 +1402,7 @@ fn type_basic_macro_invocation() {
             &ast!({
                 macro_invocation(
                     form_pat!([(lit "invoke basic_int_macro"), (named "a", (call "Expr"))]),
-                    n("basic_int_macro"), vec![]) ;
+                    n("basic_int_macro"), ebeta!([]), n("Expr")) ;
                 "a" => (vr "int_var")
             }),
             env.clone()),
@@ -657,7 +1413,7 @@ fn type_basic_macro_invocation() {
             &ast!({
                 macro_invocation(
                     form_pat!([(lit "invoke basic_t_macro"), (named "a", (call "Expr"))]),
-                    n("basic_t_macro"), vec![]) ;
+                    n("basic_t_macro"), ebeta!([]), n("Expr")) ;
                 "a" => (vr "nat_var")
             }),
             env.clone()),
@@ -668,7 +1424,7 @@ fn type_basic_macro_invocation() {
             &ast!({
                 macro_invocation(
                     form_pat!([(lit "invoke basic_int_macro"), (named "a", (call "Expr"))]),
-                    n("basic_int_macro"), vec![]) ;
+                    n("basic_int_macro"), ebeta!([]), n("Expr")) ;
                 "a" => (vr "nat_var")
             }),
             env.clone()),
@@ -679,7 +1435,7 @@ fn type_basic_macro_invocation() {
             &ast!({
                 macro_invocation(
                     form_pat!([(lit "invoke basic_pattern_macro"), (named "a", (call "Pat"))]),
-                    n("basic_pattern_macro"), vec![n("a")]) => ["a"];
+                    n("basic_pattern_macro"), ebeta!(["a"]), n("Pat")) => ["a"];
                 "a" => "should_be_nat"
             }),
             env.clone().set(negative_ret_val(), ty!({"Type" "Nat" :}))),
@@ -693,7 +1449,7 @@ fn type_basic_macro_invocation() {
                                (named "val", (call "Expr")),
                                (named "binding", (call "Pat")),
                                (named "body", (import ["binding" = "val"], (call "Expr")))]),
-                    n("let_like_macro"), vec![]) ;
+                    n("let_like_macro"), ebeta!([]), n("Expr")) ;
                 "val" => (vr "nat_var"),
                 "binding" => "x",
                 "body" => (import ["binding" = "val"] (vr "x"))
@@ -709,7 +1465,8 @@ fn type_basic_macro_invocation() {
                                (named "t", (call "Type")),
                                (named "body", (call "Pat")),
                                (named "cond_expr", (import ["body" : "t"], (call "Expr")))]),
-                    n("pattern_cond_like_macro"), vec![n("body")]) ;
+                    n("pattern_cond_like_macro"), ebeta!(["body"]),
+                    n("Pat")) ;
                 "t" => {"Type" "Int" :},
                 "body" => "x",
                 "cond_expr" => (import ["body" : "t"] (vr "x"))
@@ -750,13 +1507,30 @@ fn type_ddd_macro() {
                             (star (named "val", (call "Expr"))),
                             (star (named "binding", (call "Pat"))),
                             (named "body", (import [* ["binding" = "val"]], (call "Expr")))]),
-                n("let_like_macro"), vec![]) ;
+                n("let_like_macro"), ebeta!([]), n("Expr")) ;
             "val" => [@"arm" (vr "nat_var"), (vr "nat_var")],
             "binding" => [@"arm" "x1", "x2"],
             "body" => (import [* ["binding" = "val"]] (vr "x1"))
         }),
         env.clone()),
     Ok(ty!({ "Type" "Nat" :})));
+
+    // Repeated patterns export all of their names:
+    let env = env.set(n("many_pattern_macro"),
+        macro_type(&vec![n("T")],
+                   assoc_n!("pats" => ::runtime::reify::sequence_type__of(&t_pat_type)),
+                   t_pat_type.clone()));
+    assert_eq!(
+        ::ty::neg_synth_type(
+            &ast!({
+                macro_invocation(
+                    form_pat!([(lit "invoke many_pattern_macro"),
+                               (star (named "pats", (call "Pat")))]),
+                    n("many_pattern_macro"), ebeta!([* ["pats"]]), n("Pat")) => [* ["pats"]];
+                "pats" => [@"arm" "x1", "x2"]
+            }),
+            env.set(negative_ret_val(), ty!({"Type" "Nat" :}))),
+        Ok(assoc_n!("x1" => ty!({"Type" "Nat" :}), "x2" => ty!({"Type" "Nat" :}))));
 }
 
 
@@ -1,11 +1,14 @@
 use std::rc::Rc;
 use ty::Ty;
 use name::*;
-use runtime::eval::{Eval, Destructure, QQuote, QQuoteDestr};
+use util::err::Span;
+use runtime::eval::{Eval, Destructure, QQuote, QQuoteDestr, Value, Sequence, RuntimeErr,
+                    RuntimeError, rt_err};
 use grammar::{SynEnv, FormPat};
 use form::{Form, Positive, Negative, Both};
 use core_forms::vr_to_name;
-use core_type_forms::{nt_to_type, nt_is_positive, less_quoted_ty, more_quoted_ty};
+use core_type_forms::{nt_to_type, nt_is_positive, nt_direction, with_user_nts, less_quoted_ty,
+                      more_quoted_ty};
 use ast_walk::WalkRule::*;
 use ast_walk::squirrel_away;
 use ast::Ast;
@@ -112,12 +115,12 @@ fn change_mu_opacity(parts: ::ast_walk::LazyWalkReses<MuProtect>) -> Result<Ty,
         }
     }
     match parts.this_ast {
-        Ast::Node(f, mut mu_parts, export) => {
+        Ast::Node(f, mut mu_parts, export, span) => {
             if let Some(opacity) = opacity  {
                 mu_parts.add_leaf(n("opacity_for_different_phase"),
-                    Ast::Atom(n(&(opacity+delta).to_string())));
+                    Ast::Atom(n(&(opacity+delta).to_string()), Span::nowhere()));
             }
-            Ok(Ty(Ast::Node(f, mu_parts, export)))
+            Ok(Ty(Ast::Node(f, mu_parts, export, span)))
         }
         _ => panic!("ICE")
     }
@@ -142,12 +145,14 @@ impl WalkMode for MuProtect {
     fn automatically_extend_env() -> bool { true }
 
     fn walk_var(name: Name, parts: &::ast_walk::LazyWalkReses<MuProtect>) -> Result<Ty, ()> {
-        if parts.extra_info <= 0 { return Ok(Ty(::ast::VariableReference(name))) }
+        let vr = Ast::VariableReference(name, Span::nowhere());
+        if parts.extra_info <= 0 { return Ok(Ty(vr)) }
         Ok(parts.env.find(&name).map(Clone::clone).unwrap_or_else(||
             ty!({"Type" "mu_type" :
-                "opacity_for_different_phase" => (, Ast::Atom(n(&parts.extra_info.to_string()))),
-                "param" => [(import [prot "param"] (, Ast::VariableReference(name)))],
-                "body" => (import [* [prot "param"]] (, Ast::VariableReference(name)))})))
+                "opacity_for_different_phase" =>
+                    (, Ast::Atom(n(&parts.extra_info.to_string()), Span::nowhere())),
+                "param" => [(import [prot "param"] (, vr.clone()))],
+                "body" => (import [* [prot "param"]] (, vr.clone()))})))
     }
 }
 impl WalkMode for UnusedNegativeMuProtect {
@@ -185,18 +190,22 @@ pub fn unquote_form(nt: Name, pos_quot: bool, depth: u8) -> Rc<Form> {
             //  so it's optional
             Rc::new(if pos_quot {
                 form_pat!((delim form_delim_start, "[",
-                    [(lit_by_name nt), (named "nt", (anyways (, ::ast::VariableReference(nt)))),
+                    [(lit_by_name nt),
+                     (named "nt", (anyways (, ::ast::VariableReference(nt, Span::nowhere())))),
                      (alt [], (delim "<[", "[", (named "ty_annot", (call "Type")))),
                      (lit "|"),
                      (named "body", (-- depth (call "Expr")))]))
             } else {
                 form_pat!((delim form_delim_start, "[",
-                    [(lit_by_name nt), (named "nt", (anyways (, ::ast::VariableReference(nt)))),
+                    [(lit_by_name nt),
+                     (named "nt", (anyways (, ::ast::VariableReference(nt, Span::nowhere())))),
                      (alt [], (delim "<[", "[", (named "ty_annot", (call "Type")))),
                      (lit "|"),
                      (named "body", (-- depth (call "Pat")))]))
             }),
         type_compare: Positive(NotWalked), // this is not a type form
+        // ...but it can stand in for one, whose kind we won't know until it's interpolated:
+        synth_kind: Positive(::kind::unknown_kind_rule()),
         synth_type:
             // `nt_is_positive` and `pos_quot` have opposite roles from `quote`
             if nt_is_positive(nt) {
@@ -219,7 +228,8 @@ pub fn unquote_form(nt: Name, pos_quot: bool, depth: u8) -> Rc<Form> {
                             less_quoted_ty(&res, Some(nt), &ast_for_errors)?
                         } else {
                             // need a type annotation
-                            let expected_type = unquote_parts.get_res(n("ty_annot"))?;
+                            let expected_type =
+                                ::ty::in_type(&unquote_parts).get_res(n("ty_annot"))?;
 
                             let mut ctxt_elt = expected_type.clone();
                             for _ in 0..(depth-1) {
@@ -299,36 +309,92 @@ pub fn dotdotdot(nt: Name) -> Rc<FormPat> {
     Rc::new(FormPat::Scope(dotdotdot_form(nt), ::beta::ExportBeta::Nothing))
 }
 
+/// Find the drivers of a `dotdotdot` in a less-quoted environment.
+/// The drivers are quoted, so freshening the binders they refer to doesn't rename them;
+///  fall back to the freshened versions of them.
+fn find_drivers<T>(drivers: &[Ast], lq_env: &Assoc<Name, T>) -> Option<Vec<Name>> {
+    let mut res = vec![];
+    for driver in drivers {
+        let driver = vr_to_name(driver);
+        if lq_env.find(&driver).is_some() {
+            res.push(driver);
+        } else {
+            res.push(lq_env.iter_keys().find(|k| k.is_freshening_of(driver))?);
+        }
+    }
+    Some(res)
+}
+
+/// The elements of the value of the `...[ ]...` driver `driver`.
+fn driver_elts<'a>(driver: Name, env: &'a Assoc<Name, Value>, loc: &Ast)
+        -> Result<&'a Vec<Rc<Value>>, RuntimeErr> {
+    match env.find(&driver) {
+        Some(&Sequence(ref contents)) => Ok(contents),
+        Some(other) => Err(rt_err(RuntimeError::BadDriver(driver, other.clone()), loc)),
+        None => Err(rt_err(RuntimeError::UnboundVariable(driver), loc))
+    }
+}
+
+/// The `i`th element of the value of the `...[ ]...` driver `driver`.
+fn driver_elt(driver: Name, env: &Assoc<Name, Value>, i: usize, loc: &Ast)
+        -> Result<Value, RuntimeErr> {
+    match driver_elts(driver, env, loc)?.get(i) {
+        Some(elt) => Ok((**elt).clone()),
+        None => Err(rt_err(RuntimeError::BadDriver(driver, env.find_or_panic(&driver).clone()),
+                           loc))
+    }
+}
+
 pub fn dotdotdot_form(nt: Name) -> Rc<Form> {
     Rc::new(Form {
         name: n("dotdotdot"),
         grammar: Rc::new(form_pat!((delim "...[", "[",
             [(star (named "driver", varref)), (lit ">>"), (named "body", (call_by_name nt))]))),
         type_compare: Positive(NotWalked), // this is not a type form
+        // ...but it can stand in for one, whose kind we won't know until it's interpolated:
+        synth_kind: Positive(::kind::unknown_kind_rule()),
         synth_type: Positive(
             cust_rc_box!(| ddd_parts | {
                 let drivers = ddd_parts.get_rep_term(n("driver"));
+
+                // Drivers that are macro arguments live one level out, as `Sequence`s.
+                // The body only needs to be typechecked once, with the element types:
+                if let Some(lq_env) = ddd_parts.less_quoted_env.last() {
+                    if let Some(lq_drivers) = find_drivers(&drivers, lq_env) {
+                        let mut lq_env = lq_env.clone();
+                        for driver in lq_drivers {
+                            let elt_ty = ::runtime::reify::un__sequence_type(
+                                lq_env.find_or_panic(&driver), &ddd_parts.this_ast)?;
+                            lq_env = lq_env.set(driver, elt_ty);
+                        }
+                        let mut lq_parts = ddd_parts.clone();
+                        lq_parts.less_quoted_env.pop();
+                        lq_parts.less_quoted_env.push(lq_env);
+                        return lq_parts.get_res(n("body"));
+                    }
+                }
+
                 let mut walked_env = Assoc::new();
 
                 let repeats = match ddd_parts.env.find(&::core_forms::vr_to_name(&drivers[0])) {
-                    Some(&Ty(::ast::Node(ref form, ref parts, _))) if form.name == n("tuple") => {
+                    Some(&Ty(::ast::Node(ref form, ref parts, _, _))) if form.name == n("tuple") => {
                         parts.get_rep_leaf_or_panic(n("component")).len()
                     }
                     Some(other_t) => {
                         ty_err!(UnableToDestructure(other_t.clone(), n("tuple"))
                                 at ddd_parts.this_ast);
                     }
-                    _ => ty_err!(UnboundName(::core_forms::vr_to_name(&drivers[0]))
+                    _ => ty_err!(UnboundName(::core_forms::vr_to_name(&drivers[0]), None)
                                  at ddd_parts.this_ast)
                 };
 
                 for i in 0..repeats {
                     for (name, ty) in ddd_parts.env.iter_pairs() {
-                        if drivers.contains(&::ast::VariableReference(*name)) {
+                        if drivers.contains(&::ast::VariableReference(*name, Span::nowhere())) {
                             walked_env = walked_env.set(
                                 *name,
                                 match ty {
-                                    Ty(::ast::Node(ref form, ref parts, _))
+                                    Ty(::ast::Node(ref form, ref parts, _, _))
                                             if form.name == n("tuple") => {
                                         Ty(parts.get_rep_leaf_or_panic(n("component"))[i].clone())
                                     }
@@ -347,28 +413,47 @@ pub fn dotdotdot_form(nt: Name) -> Rc<Form> {
         eval: Positive(NotWalked),
         quasiquote: Positive(
             cust_rc_box!(| ddd_parts | {
-                use ::runtime::eval::{Value,Sequence};
+                use ::runtime::eval::Value;
                 use walk_mode::WalkElt;
 
                 let drivers = ddd_parts.get_rep_term(n("driver"));
 
+                // Macro arguments, one level out (see above):
+                if let Some(lq_env) = ddd_parts.less_quoted_env.last() {
+                    if let Some(lq_drivers) = find_drivers(&drivers, lq_env) {
+                        let mut reps = vec![];
+                        let count = driver_elts(lq_drivers[0], lq_env, &ddd_parts.this_ast)?.len();
+                        for i in 0..count {
+                            let mut walked_lq_env = lq_env.clone();
+                            for driver in &lq_drivers {
+                                let walked_val =
+                                    driver_elt(*driver, lq_env, i, &ddd_parts.this_ast)?;
+                                walked_lq_env = walked_lq_env.set(*driver, walked_val);
+                            }
+                            let mut lq_parts = ddd_parts.clone();
+                            lq_parts.less_quoted_env.pop();
+                            lq_parts.less_quoted_env.push(walked_lq_env);
+
+                            lq_parts.clear_memo();
+                            reps.push(lq_parts.get_res(n("body"))?.to_ast());
+                        }
+                        return Ok(Value::from_ast(&::ast::Shape(reps)));
+                    }
+                }
+
                 // TODO: the typechecker should reject dotdotdotds with no drivers,
                 // or where a driver isn't in scope.
-                let count = match *ddd_parts.env.find_or_panic(
-                        &::core_forms::vr_to_name(&drivers[0])) {
-                    Sequence(ref contents) => { contents.len() }
-                    _ => { panic!("ICE: type error")}
-                };
+                let count = driver_elts(::core_forms::vr_to_name(&drivers[0]), &ddd_parts.env,
+                                        &ddd_parts.this_ast)?.len();
                 let mut reps = vec![];
 
                 for i in 0..count {
                     let mut walked_env = Assoc::new();
                     for (n, val) in ddd_parts.env.iter_pairs() {
-                        let walked_val = if drivers.contains(&::ast::VariableReference(*n)) {
-                            match *val {
-                                Sequence(ref contents) => (*contents[i]).clone(),
-                                _ => panic!("ICE: type error")
-                            }
+                        let is_driver =
+                            drivers.contains(&::ast::VariableReference(*n, Span::nowhere()));
+                        let walked_val = if is_driver {
+                            driver_elt(*n, &ddd_parts.env, i, &ddd_parts.this_ast)?
                         } else {
                             val.clone()
                         };
@@ -413,21 +498,33 @@ pub fn quote(pos: bool) -> Rc<Form> {
             }
         }
 
-        let pos_inside = nt_is_positive(starter_nt);
+        // Nonterminals declared by `extend_syntax` can be quoted (and unquoted), too:
+        let user_nts = ::core_type_forms::user_nts_in(&se);
+        let pos_inside = match with_user_nts(user_nts.clone(), || nt_direction(starter_nt)) {
+            Some(pos_inside) => pos_inside,
+            None => return se.set(n("starterer_nt"), Rc::new(Impossible)) // Not a nonterminal
+        };
 
-        se.keyed_map_borrow_f(&mut |nt: &Name, nt_def: &Rc<FormPat>| {
+        with_user_nts(user_nts.clone(), || se.keyed_map_borrow_f(&mut |nt, nt_def| {
             if already_has_unquote(nt_def)
                // HACK: this is to avoid hitting "starterer". TODO: find a better way
-               || (nt != &n("Expr") && nt != &n("Pat") && nt != &n("Type")) {
+               || (nt != &n("Expr") && nt != &n("Pat") && nt != &n("Type")
+                   && user_nts.find(nt).is_none()) {
                 nt_def.clone()
             } else {
                 // TODO: maybe we should only insert `dotdotdot` in repetition positions?
-                Rc::new(Biased(unquote(*nt, pos), Rc::new(Biased(dotdotdot(*nt), nt_def.clone()))))
-            }})
+                let mut res = Rc::new(Biased(dotdotdot(*nt), nt_def.clone()));
+                // Syntax from a user-defined nonterminal can be interpolated into its base's:
+                for (sub_nt, _) in user_nts.iter_pairs().filter(|&(_, base)| base == nt) {
+                    res = Rc::new(Biased(unquote(*sub_nt, pos), res));
+                }
+                Rc::new(Biased(unquote(*nt, pos), res))
+            }}))
             .set(n("starterer_nt"),
                 Rc::new(form_pat!(
                     // HACK: The `nt` from outside isn't in the same Scope, it seems:
-                    [(named "nt", (anyways (, ::ast::VariableReference(starter_nt)))),
+                    [(named "nt",
+                      (anyways (, ::ast::VariableReference(starter_nt, Span::nowhere())))),
                      (alt [], (delim "<[", "[", (named "ty_annot", (call "Type")))),
                      (lit "|"),
                      (named "body", (++ pos_inside (call_by_name starter_nt)))])))
@@ -440,6 +537,7 @@ pub fn quote(pos: bool) -> Rc<Form> {
         grammar: Rc::new(form_pat!((delim "'[", "[",
             [(extend (named "nt", varref), "starterer_nt", perform_quotation)]))),
         type_compare: ::form::Both(NotWalked, NotWalked), // Not a type
+        synth_kind: Positive(NotWalked),
         synth_type:
             if pos {
                 ::form::Positive(cust_rc_box!(|quote_parts| {
@@ -457,7 +555,7 @@ pub fn quote(pos: bool) -> Rc<Form> {
                     } else {
                         // TODO: if the user accidentally omits the annotation,
                         //  provide a good error message.
-                        let expected_type = &quote_parts.get_res(n("ty_annot"))?;
+                        let expected_type = &::ty::in_type(&quote_parts).get_res(n("ty_annot"))?;
 
                         // We're looking at things 1 level deeper:
                         let prot_expected_type = adjust_opacity(
@@ -524,7 +622,7 @@ pub fn quote(pos: bool) -> Rc<Form> {
 
 #[test]
 fn quote_unquote_eval_basic() {
-    use ::runtime::eval::Value;
+    use ::runtime::eval::{Value, RuntimeErr};
 
     let pos = true;
     let neg = false;
@@ -539,13 +637,13 @@ fn quote_unquote_eval_basic() {
     );
 
     fn eval_two_phased(expr: &Ast, env: Assoc<Name, Value>, qenv: Assoc<Name, Value>)
-            -> Result<Value, ()> {
+            -> Result<Value, RuntimeErr> {
         ::ast_walk::walk::<Eval>(expr, &::ast_walk::LazyWalkReses::new_mq_wrapper(
             env, vec![qenv]))
     }
 
     fn destr_two_phased(pat: &Ast, env: Assoc<Name, Value>, qenv: Assoc<Name, Value>, ctxt: Value)
-            -> Result<Assoc<Name, Value>, ()> {
+            -> Result<Assoc<Name, Value>, RuntimeErr> {
         ::ast_walk::walk::<Destructure>(pat, &::ast_walk::LazyWalkReses::new_mq_wrapper(
             env, vec![qenv]).with_context(ctxt))
     }
@@ -645,7 +743,7 @@ fn quote_type_basic() {
     assert_m!(synth_type_two_phased(
             &ast!({quote(pos) ; "nt" => (vr "Expr"), "body" => (++ true (vr "n"))}),
             env.clone(), qenv.clone()),
-        ty_err_p!(UnboundName(_)));
+        ty_err_p!(UnboundName(_, _)));
 
     // '[Expr | { x: qn  y: qn }]'
     assert_eq!(synth_type_two_phased(
@@ -901,7 +999,7 @@ fn unquote_type_basic() {
 
 #[test]
 fn use_dotdotdot() {
-    use ::runtime::eval::Value;
+    use ::runtime::eval::{Value, RuntimeErr};
 
     let pos = true;
     let expr_type = ast!({::core_type_forms::get__abstract_parametric_type() ; "name" => "Expr" });
@@ -945,7 +1043,7 @@ fn use_dotdotdot() {
 
 
     fn eval_two_phased(expr: &Ast, eval_env: Assoc<Name, Value>, eval_qenv: Assoc<Name, Value>)
-            -> Result<Value, ()> {
+            -> Result<Value, RuntimeErr> {
         ::ast_walk::walk::<Eval>(expr, &::ast_walk::LazyWalkReses::new_mq_wrapper(
             eval_env, vec![eval_qenv]))
     }
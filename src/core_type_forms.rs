@@ -60,12 +60,12 @@ use ty_compare::{Canonicalize, Subtype};
 use ast::*;
 use ::util::assoc::Assoc;
 
-//TODO #3: I think we need to extend `Form` with `synth_kind`...
 pub fn type_defn(form_name: &str, p: FormPat) -> Rc<Form> {
     Rc::new(Form {
         name: n(form_name),
         grammar: Rc::new(p),
         type_compare: Both(LiteralLike, LiteralLike),
+        synth_kind: Positive(::kind::proper_type_kind()),
         synth_type: Positive(LiteralLike),
         quasiquote: Both(LiteralLike, LiteralLike),
         eval: Positive(NotWalked)
@@ -73,11 +73,13 @@ pub fn type_defn(form_name: &str, p: FormPat) -> Rc<Form> {
 }
 
 fn type_defn_complex(form_name: &str, p: FormPat, sy: WalkRule<SynthTy>,
-                     tc: BiDiWR<Canonicalize, Subtype>) -> Rc<Form> {
+                     tc: BiDiWR<Canonicalize, Subtype>, ki: WalkRule<::kind::SynthKind>)
+        -> Rc<Form> {
     Rc::new(Form {
         name: n(form_name),
         grammar: Rc::new(p),
         type_compare: tc,
+        synth_kind: Positive(ki),
         synth_type: Positive(sy),
         quasiquote: Both(LiteralLike, LiteralLike),
         eval: Positive(NotWalked)
@@ -91,6 +93,7 @@ thread_local! {
         name: n("abstract_parametric_type"),
         grammar: Rc::new(form_pat!([(named "name", aat)])),
         type_compare: Both(LiteralLike, LiteralLike),
+        synth_kind: Positive(NotWalked),
         synth_type: Positive(LiteralLike),
         quasiquote: Both(LiteralLike, LiteralLike),
         eval: Positive(NotWalked)
@@ -107,10 +110,21 @@ pub fn make_core_syn_env_types() -> SynEnv {
         "For programming languages ... three levels have proved sufficient." */
 
     /* kinds */
-    let _type_kind = simple_form("Type", form_pat!((lit "*")));
-    let _higher_kind = simple_form("higher", form_pat!(
+    // Kinds are carried around (e.g. inside `forall` types) like any other type syntax,
+    //  but they don't have kinds themselves.
+    let kind_defn = |form_name: &str, p: FormPat| Rc::new(Form {
+        name: n(form_name),
+        grammar: Rc::new(p),
+        type_compare: Both(LiteralLike, LiteralLike),
+        synth_kind: Positive(NotWalked),
+        synth_type: Positive(LiteralLike),
+        quasiquote: Both(LiteralLike, LiteralLike),
+        eval: Positive(NotWalked)
+    });
+    let type_kind = kind_defn("type_kind", form_pat!((lit "*")));
+    let higher_kind = kind_defn("higher_kind", form_pat!(
         (delim "k[", "[",
-            [ (star (named "param", (call "kind"))), (lit "->"), (named "res", (call "kind"))])));
+            [ (star (named "param", (call "Kind"))), (lit "->"), (named "res", (call "Kind"))])));
 
 
     /* types */
@@ -134,15 +148,17 @@ pub fn make_core_syn_env_types() -> SynEnv {
                             expd_params.len()));
                     }
                     for (p_expected, p_got) in expd_params.iter().zip(actl_params.iter()) {
-                        // Parameters have reversed subtyping:
+                        // Parameters have reversed subtyping (so un-reverse any complaint):
                         let _ : ::util::assoc::Assoc<Name, Ty> = walk::<Subtype>(
-                            *p_got, &fn_parts.with_context(Ty::new(p_expected.clone())))?;
+                            *p_got, &fn_parts.with_context(Ty::new(p_expected.clone())))
+                            .map_err(TyErr::flip)?;
                     }
 
                     walk::<Subtype>(&fn_parts.get_term(n("ret")),
                         &fn_parts.with_context(
                             Ty::new(actual_parts.get_leaf_or_panic(&n("ret")).clone())))
-                 }))
+                 })),
+            ::kind::proper_type_kind()
         );
 
     let enum_type =
@@ -183,15 +199,45 @@ pub fn make_core_syn_env_types() -> SynEnv {
                     }
 
                     Ok(assoc_n!())
-                })));
+                })),
+            ::kind::proper_type_kind());
 
     let tuple_type =
-        type_defn("tuple",
-                  form_pat!((delim "**[", "[", (star (named "component", (call "Type"))))));
+        type_defn_complex("tuple",
+            form_pat!((delim "**[", "[", (star (named "component", (call "Type"))))),
+            LiteralLike, // synth is normal
+            Both(LiteralLike,
+                cust_rc_box!(move |tuple_parts| {
+                    let actual = tuple_parts.context_elt().concrete();
+                    let actual_parts = Subtype::context_match(
+                        &tuple_parts.this_ast, &actual, tuple_parts.env.clone())?;
+
+                    let expd_components = tuple_parts.get_rep_term(n("component"));
+                    let actl_components = actual_parts.get_rep_leaf_or_panic(n("component"));
+                    if expd_components.len() != actl_components.len() {
+                        return Err(TyErr::LengthMismatch(
+                            actl_components.iter().map(|&a| Ty(a.clone())).collect(),
+                            expd_components.len()));
+                    }
+                    // Components are covariant (unlike `fn` parameters):
+                    for (c_expected, c_got) in expd_components.iter().zip(actl_components) {
+                        let _ : ::util::assoc::Assoc<Name, Ty> = walk::<Subtype>(
+                            c_expected, &tuple_parts.with_context(Ty::new(c_got.clone())))?;
+                    }
+                    Ok(Assoc::new())
+                })),
+            ::kind::proper_type_kind());
 
+    // Parameters can have their kinds annotated (e.g. `forall F : k[* -> *]k . ⋯`);
+    //  by default, they're proper types (`*`).
+    // Either all the parameters are annotated, or none are (else it's ambiguous)
     let forall_type =
         type_defn_complex("forall_type",
-            form_pat!([(lit "forall"), (star (named "param", aat)), (lit "."),
+            form_pat!([(lit "forall"),
+                       (biased (star [(named "param", aat),
+                                      (lit ":"), (named "param_kind", (call "Kind"))]),
+                               (star (named "param", aat))),
+                       (lit "."),
                        (named "body", (import [* [forall "param"]], (call "Type")))]),
             LiteralLike, // synth is normal
             Both(
@@ -216,7 +262,8 @@ pub fn make_core_syn_env_types() -> SynEnv {
                             walk::<Subtype>(&forall_parts.get_term(n("body")), &forall_parts)
                         }
                     }
-                })));
+                })),
+            ::kind::forall_type_kind());
 
     /* This behaves slightly differently than the `mu` from Pierce's book,
      *  because we need to support mutual recursion.
@@ -225,7 +272,10 @@ pub fn make_core_syn_env_types() -> SynEnv {
      *  to prevent the attempted generation of an infinite type.
      */
     let mu_type = type_defn_complex("mu_type",
-        form_pat!([(lit "mu_type"), (star (named "param", (import [prot "param"], varref))),
+        form_pat!([(lit "mu_type"),
+             (biased (star [(named "param", (import [prot "param"], varref)),
+                            (lit ":"), (named "param_kind", (call "Kind"))]),
+                     (star (named "param", (import [prot "param"], varref)))),
              (lit "."), (named "body", (import [* [prot "param"]], (call "Type")))]),
 
         LiteralLike,
@@ -264,7 +314,8 @@ pub fn make_core_syn_env_types() -> SynEnv {
                 walk::<Subtype>(&mu_parts.get_term(n("body")),
                     &mu_parts.with_environment(amber_environment)
                         .with_context(Ty::new(rhs_body.clone())))
-            })));
+            })),
+        ::kind::mu_type_kind());
 
 
     // This only makes sense inside a concrete syntax type or during typechecking.
@@ -302,7 +353,7 @@ pub fn make_core_syn_env_types() -> SynEnv {
             let arg_res = tapp_parts.get_rep_res(n("arg"))?;
             let rator_res = tapp_parts.get_res(n("type_rator"))?;
             match rator_res.0 {
-                VariableReference(rator_vr) => {
+                VariableReference(rator_vr, rator_span) => {
                     // e.g. `X<[int, Y]<` underneath `mu X. ...`
 
                     // Rebuild a type_apply, but evaulate its arguments
@@ -310,7 +361,7 @@ pub fn make_core_syn_env_types() -> SynEnv {
                     //  we wish to avoid aliasing problems at the type level.
                     // In System F, this is avoided by performing capture-avoiding substitution.
                     let mut new__tapp_parts = ::util::mbe::EnvMBE::new_from_leaves(
-                        assoc_n!("type_rator" => VariableReference(rator_vr)));
+                        assoc_n!("type_rator" => VariableReference(rator_vr, rator_span)));
 
                     let mut args = vec![];
                     for individual__arg_res in arg_res {
@@ -319,18 +370,18 @@ pub fn make_core_syn_env_types() -> SynEnv {
                     }
                     new__tapp_parts.add_anon_repeat(args, None);
 
-                    if let Node(ref f, _, ref exp) = tapp_parts.this_ast {
-                        Ok(Ty::new(Node(/*forall*/ f.clone(), new__tapp_parts, exp.clone())))
+                    if let Node(ref f, _, ref exp, span) = tapp_parts.this_ast {
+                        Ok(Ty::new(Node(/*forall*/ f.clone(), new__tapp_parts, exp.clone(), span)))
                     } else {
                         panic!("ICE")
                     }
                 }
-                Node(ref got_f, ref lhs_parts, ref exports)
+                Node(ref got_f, ref lhs_parts, ref exports, span)
                         if got_f == &get__abstract_parametric_type() => {
                     // Like the above; don't descend into `Expr`
                     let mut new__tapp_parts = ::util::mbe::EnvMBE::new_from_leaves(
                         assoc_n!("type_rator" =>
-                            Node(got_f.clone(), lhs_parts.clone(), exports.clone())));
+                            Node(got_f.clone(), lhs_parts.clone(), exports.clone(), span)));
                     let mut args = vec![];
                     for individual__arg_res in arg_res {
                         args.push(::util::mbe::EnvMBE::new_from_leaves(
@@ -338,19 +389,20 @@ pub fn make_core_syn_env_types() -> SynEnv {
                     }
                     new__tapp_parts.add_anon_repeat(args, None);
 
-                    if let Node(ref f, _, ref exp) = tapp_parts.this_ast {
-                        Ok(Ty::new(Node(/*forall*/ f.clone(), new__tapp_parts, exp.clone())))
+                    if let Node(ref f, _, ref exp, span) = tapp_parts.this_ast {
+                        Ok(Ty::new(Node(/*forall*/ f.clone(), new__tapp_parts, exp.clone(), span)))
                     } else {
                         panic!("ICE")
                     }
 
                 }
-                Node(ref got_f, ref forall_type__parts, _)
+                Node(ref got_f, ref forall_type__parts, _, _)
                         if got_f == &forall_type_0 => {
                     // This might ought to be done by a specialized `beta`...
+                    // Kind checking usually catches these problems before we get here:
                     let params = forall_type__parts.get_rep_leaf_or_panic(n("param"));
                     if params.len() != arg_res.len() {
-                        panic!("Kind error: wrong number of arguments");
+                        ty_err!(LengthMismatch(arg_res, params.len()) at tapp_parts.this_ast);
                     }
                     let mut new__ty_env = tapp_parts.env.clone();
                     for (name, actual_type) in params.iter().zip(arg_res) {
@@ -364,11 +416,13 @@ pub fn make_core_syn_env_types() -> SynEnv {
                 }
 
                 _ => {
-                    panic!("Kind error: {} is not a forall.", rator_res);
+                    ty_err!(UnableToDestructure(rator_res, n("forall_type"))
+                            at tapp_parts.get_term(n("type_rator")));
                 }
             }
         }),
-        Both(LiteralLike, LiteralLike));
+        Both(LiteralLike, LiteralLike),
+        ::kind::type_apply_kind());
 
     assoc_n!("Type" => Rc::new(Biased(Rc::new(forms_to_form_pat![
         fn_type.clone(),
@@ -376,6 +430,8 @@ pub fn make_core_syn_env_types() -> SynEnv {
         type_defn("Int", form_pat!((lit "Int"))),
         type_defn("Nat", form_pat!((lit "Nat"))),
         type_defn("Float", form_pat!((lit "Float"))),
+        type_defn("String", form_pat!((lit "String"))),
+        type_defn("Char", form_pat!((lit "Char"))),
         enum_type.clone(),
         struct_type.clone(),
         tuple_type.clone(),
@@ -383,14 +439,51 @@ pub fn make_core_syn_env_types() -> SynEnv {
         dotdotdot_type.clone(),
         mu_type.clone(),
         type_apply.clone()
-        ]), Rc::new(VarRef))))
+        ]), Rc::new(VarRef))),
+        "Kind" => Rc::new(forms_to_form_pat![type_kind, higher_kind]))
 }
 
 
-// TODO #4: this should be extensible for when the syntax environment is extended...
-//  or just automatically have one type per NT. Probably the latter.
+thread_local! {
+    /// Nonterminals introduced (`nonterminal Nt of Base`) by the `extend_syntax`es
+    ///  currently being typechecked, and the built-in nonterminals that their forms expand into.
+    pub static user_nts : ::std::cell::RefCell<Assoc<Name, Name>>
+        = ::std::cell::RefCell::new(Assoc::new());
+}
+
+/// Run `f` with `nts` (temporarily) added to the user-defined nonterminals.
+pub fn with_user_nts<R, F: FnOnce() -> R>(nts: Assoc<Name, Name>, f: F) -> R {
+    let old = user_nts.with(|u| u.borrow().clone());
+    user_nts.with(|u| *u.borrow_mut() = old.set_assoc(&nts));
+    let res = f();
+    user_nts.with(|u| *u.borrow_mut() = old);
+    res
+}
+
+/// The user-defined nonterminals (and their bases) declared by enclosing `extend_syntax`es.
+pub fn user_nts_in(se: &SynEnv) -> Assoc<Name, Name> {
+    let mut res = Assoc::new();
+    if let Some(nts) = se.find(&n("user_nts")) {
+        if let Seq(ref nts) = **nts {
+            for nt in nts.iter().rev() {
+                if let Named(nt, ref base) = **nt {
+                    if let Call(base) = **base { res = res.set(nt, base); }
+                }
+            }
+        }
+    }
+    res
+}
+
+/// The built-in nonterminal that `nt`'s forms expand into (`nt` itself if it's built-in).
+pub fn nt_base(nt: Name) -> Name {
+    user_nts.with(|nts| nts.borrow().find(&nt).cloned()).unwrap_or(nt)
+}
+
+// Each NT gets its own type, even user-defined ones.
 pub fn nt_to_type(nt: Name) -> Ty {
-    if nt == n("Type") || nt == n("Pat") || nt == n("Expr") {
+    let base = nt_base(nt);
+    if base == n("Type") || base == n("Pat") || base == n("Expr") {
         let nt_sp = &nt.sp();
         ty!({get__abstract_parametric_type(); "name" => nt_sp})
     } else {
@@ -398,16 +491,21 @@ pub fn nt_to_type(nt: Name) -> Ty {
     }
 }
 
-// TODO #4: make this extensible, too! When the user creates a new NT,
-//  do they need to specify the direction?
+// User-defined NTs have the same direction as the NT that they expand into.
 pub fn nt_is_positive(nt: Name) -> bool {
-    if nt == n("Type") || nt == n("Expr") {
-        true
-    } else if nt == n("Pat") || nt == n("Ident") {
+    nt_direction(nt).unwrap_or_else(|| panic!("ICE: unknown NT {}", nt))
+}
+
+/// Like `nt_is_positive`, but `None` for a nonterminal we don't know about.
+pub fn nt_direction(nt: Name) -> Option<bool> {
+    let base = nt_base(nt);
+    if base == n("Type") || base == n("Expr") {
+        Some(true)
+    } else if base == n("Pat") || base == n("Ident") {
         // HACK: "Ident" is just not walked; this should probably be three-armed
-        false
+        Some(false)
     } else {
-        panic!("ICE: unknown NT {}", nt)
+        None
     }
 }
 
@@ -417,12 +515,15 @@ pub fn less_quoted_ty(t: &Ty, nt: Option<Name>, loc: &Ast) -> Result<Ty, ::ty::T
         tapp_parts;
         {
             if let Some(nt) = nt { // Check it if you got it
-                let nt_sp = &nt.sp();
-                ty_exp!(
-                    &Ty::new(tapp_parts.get_leaf_or_panic(&n("type_rator")).clone()),
-                    &ty!({get__abstract_parametric_type() ; "name" => nt_sp}),
-                    loc
-                );
+                let rator = Ty::new(tapp_parts.get_leaf_or_panic(&n("type_rator")).clone());
+                // A user-defined NT's syntax can go wherever its base NT's syntax can:
+                let rator_is_sub_nt = user_nts.with(|nts| nts.borrow().iter_pairs().any(
+                    |(sub_nt, base)| *base == nt && rator == nt_to_type(*sub_nt)));
+                if !rator_is_sub_nt {
+                    let nt_sp = &nt.sp();
+                    ty_exp!(&rator, &ty!({get__abstract_parametric_type() ; "name" => nt_sp}),
+                            loc);
+                }
             }
 
             let args = tapp_parts.get_rep_leaf_or_panic(n("arg"));
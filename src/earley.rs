@@ -22,6 +22,7 @@ use grammar::plug_hole;
 use read::{Token, TokenTree};
 use read::Token::*;
 use ast::Ast;
+use util::err::Span;
 use std::rc::Rc;
 use std::cell::RefCell;
 use name::*;
@@ -43,6 +44,24 @@ thread_local! {
 
     static best_token: RefCell<(usize, Name, Rc<FormPat>, usize)>
         = RefCell::new((0, n("[nothing parsed]"), Rc::new(Impossible), 0));
+
+    // The position of each token in the most recent chart
+    //  (including the `end_of_delim()`s, which are located at their closing delimiters).
+    static token_spans: RefCell<Vec<Span>> = RefCell::new(vec![]);
+}
+
+/// The span covered by the tokens from `start_idx` (inclusive) to `done_tok` (exclusive).
+fn span_between(start_idx: usize, done_tok: usize) -> Span {
+    token_spans.with(|spans| {
+        let spans = spans.borrow();
+        let at = |idx: usize| spans.get(idx).cloned().unwrap_or_else(Span::nowhere);
+        if done_tok > start_idx {
+            at(start_idx).to(&at(done_tok - 1))
+        } else { // Nothing consumed; point at the next token
+            let next = at(start_idx);
+            Span { end: next.begin, .. next }
+        }
+    })
 }
 
 fn get_next_id() -> UniqueId {
@@ -86,13 +105,14 @@ pub fn special_ddd_nt() -> Name { n("dotdotdot") }
 fn ddd_ast_marker() -> Name { n("⌜⋯⌟") } // TODO: gensym
 
 fn ddd_wrap(a: Ast) -> Ast {
-    Ast::Shape(vec![a, Ast::Atom(ddd_ast_marker())])
+    Ast::Shape(vec![a, Ast::Atom(ddd_ast_marker(), Span::nowhere())])
 }
 
 /// If `a` is a specially-marked DDD node, remove the special marker (and return true)
 fn ddd_unwrap(a: &Ast) -> Option<Ast> {
     match a {
-        Ast::Shape(ref subs) if subs.len() == 2 && subs[1] == Ast::Atom(ddd_ast_marker()) =>
+        Ast::Shape(ref subs)
+                if subs.len() == 2 && subs[1] == Ast::Atom(ddd_ast_marker(), Span::nowhere()) =>
                 Some(subs[0].clone()),
         _ => None
     }
@@ -105,6 +125,13 @@ fn reserved(nm: Name) -> bool {
     Simple(nm) == end_of_delim() || nm == n("forall") || nm == n("mu_type")
         || nm == n("Int") || nm == n("Ident") || nm == n("Float") || nm == n("match")
         || nm == n("enum") || nm == n("struct") || nm == n("fold") || nm == n("unfold")
+        || nm == n("String") || nm == n("Char") || nm == n(".") || nm == n("|")
+        || is_literal(&nm.orig_sp()) // numbers (etc.) aren't names
+}
+
+fn is_literal(s: &str) -> bool {
+    ::read::int_literal(s).is_some() || ::read::string_literal(s).is_some()
+        || ::read::char_literal(s).is_some()
 }
 
 // Hey, this doesn't need to be Reifiable!
@@ -217,6 +244,11 @@ fn create_chart(rule: Rc<FormPat>, grammar: SynEnv, tt: &TokenTree)
              wanted_by: Rc::new(RefCell::new(vec![start_but_startier.get_ref()]))};
 
     chart[0].push(start_item);
+    // Syntax extensions look at partial parses while we walk, so find the spans first:
+    let mut spans = vec![];
+    token_tree_spans(tt, &mut spans);
+    spans.push(tt.end); // There's no token here, but it's handy for zero-length spans at the end.
+    token_spans.with(|ts| *ts.borrow_mut() = spans);
     for t in &tt.t {
         walk_tt(&mut chart, t, &mut cur_tok);
     }
@@ -235,6 +267,17 @@ fn recognize(rule: &FormPat, grammar: &SynEnv, tt: &TokenTree) -> bool {
     )
 }
 
+/// The span of each token in `tt`, in the order that `walk_tt` visits them.
+fn token_tree_spans(tt: &TokenTree, spans: &mut Vec<Span>) {
+    for (idx, t) in tt.t.iter().enumerate() {
+        spans.push(tt.span_of(idx));
+        if let Group(_, _, ref tree) = *t {
+            token_tree_spans(tree, spans);
+            spans.push(tree.end);
+        }
+    }
+}
+
 fn walk_tt(chart: &mut Vec<Vec<Item>>, t: &Token, cur_tok: &mut usize) {
     chart.push(vec![]);
     examine_state_set(chart, Some(t), *cur_tok);
@@ -436,7 +479,7 @@ impl Item {
                     // Except for `Seq`. TODO: why?
                     let mut more = match *waiting_item.rule {
                         Anyways(_) | Impossible | Literal(_) | AnyToken | AnyAtomicToken
-                        | VarRef => {
+                        | VarRef | IntLiteral | StringLiteral | CharLiteral => {
                             panic!("{:#?} should not be waiting for anything!", waiting_item)
                         }
                         Seq(ref subs) => {
@@ -456,7 +499,7 @@ impl Item {
                         Plus(_) | Star(_) => { // It'll also keep going, though!
                             waiting_item.finish_with(me_justif, false)
                         }
-                        SynImport(_,_,_) if waiting_item.pos == 0 => {
+                        SynImport(_,_,_) | ComputeSyntax(_,_) if waiting_item.pos == 0 => {
                             vec![(Item { pos: 1,
                                          local_parse: RefCell::new(me_justif),
                                            .. waiting_item.clone() }, false)]
@@ -506,7 +549,7 @@ impl Item {
             (0, &Literal(xptd_n)) => {
                 match cur {
                     Some(&Simple(n)) if xptd_n == n =>  {
-                        self.finish_with(ParsedAtom(::ast::Atom(n)), true)
+                        self.finish_with(ParsedAtom(::ast::Atom(n, Span::nowhere())), true)
                     }
                     _ => vec![]
                 }
@@ -518,7 +561,7 @@ impl Item {
             (0, &AnyToken) => {
                 match cur {
                     Some(&Simple(n)) if !reserved(n) => {
-                      self.finish_with(ParsedAtom(::ast::Atom(n)), true)
+                      self.finish_with(ParsedAtom(::ast::Atom(n, Span::nowhere())), true)
                     }
                     Some(&Group(_,_,_)) => self.finish_with(ParsedAtom(::ast::Trivial), true), // TODO
                     _ => vec![]
@@ -527,7 +570,7 @@ impl Item {
             (0, &AnyAtomicToken) => {
                 match cur {
                     Some(&Simple(n)) if !reserved(n) => {
-                      self.finish_with(ParsedAtom(::ast::Atom(n)), true)
+                      self.finish_with(ParsedAtom(::ast::Atom(n, Span::nowhere())), true)
                     }
                     _ => vec![]
                 }
@@ -535,7 +578,32 @@ impl Item {
             (0, &VarRef) => {
                 match cur {
                     Some(&Simple(n)) if !reserved(n) => {
-                        self.finish_with(ParsedAtom(::ast::VariableReference(n)), true)
+                        self.finish_with(
+                            ParsedAtom(::ast::VariableReference(n, Span::nowhere())), true)
+                    },
+                    _ => vec![]
+                }
+            },
+            (0, &IntLiteral) => {
+                match cur {
+                    Some(&Simple(n)) if ::read::int_literal(&n.orig_sp()).is_some() => {
+                        self.finish_with(ParsedAtom(::ast::Atom(n, Span::nowhere())), true)
+                    },
+                    _ => vec![]
+                }
+            },
+            (0, &StringLiteral) => {
+                match cur {
+                    Some(&Simple(n)) if ::read::string_literal(&n.orig_sp()).is_some() => {
+                        self.finish_with(ParsedAtom(::ast::Atom(n, Span::nowhere())), true)
+                    },
+                    _ => vec![]
+                }
+            },
+            (0, &CharLiteral) => {
+                match cur {
+                    Some(&Simple(n)) if ::read::char_literal(&n.orig_sp()).is_some() => {
+                        self.finish_with(ParsedAtom(::ast::Atom(n, Span::nowhere())), true)
                     },
                     _ => vec![]
                 }
@@ -600,10 +668,10 @@ impl Item {
             (0, &Scope(ref f, _)) => { // form.grammar is a FormPat. Confusing!
                 self.start(&f.grammar, cur_idx, false)
             },
-            (0, &SynImport(ref lhs, _, _)) => {
+            (0, &SynImport(ref lhs, _, _)) | (0, &ComputeSyntax(_, ref lhs)) => {
                 self.start(&lhs, cur_idx, false)
             }
-            (1, &SynImport(_, ref name, ref f)) => {
+            (1, &SynImport(_, ref name, _)) | (1, &ComputeSyntax(ref name, _)) => {
                 // TODO: handle errors properly! Probably need to memoize, also!
                 let partial_parse = match *self.local_parse.borrow() {
                     NothingYet | Ambiguous(_, _) => return vec![],
@@ -616,11 +684,25 @@ impl Item {
                     }
                 };
 
-                let new_se = all_grammars.with(|grammars| {
-                    let mut mut_grammars = grammars.borrow_mut();
-                    mut_grammars.entry(self.id.get_ref()) // memoize
-                        .or_insert_with(|| f.0(self.grammar.clone(), partial_parse)).clone()
-                });
+                let memoized = all_grammars.with(
+                    |grammars| grammars.borrow().get(&self.id.get_ref()).cloned());
+                let new_se = match memoized {
+                    Some(new_se) => new_se,
+                    None => {
+                        let new_se = match *self.rule {
+                            SynImport(_, _, ref f) => f.0(self.grammar.clone(), partial_parse),
+                            // (If the computation fails, there's no parse)
+                            _ => match ::core_macro_forms::compute_syntax(
+                                    &self.grammar, &partial_parse) {
+                                Ok(new_se) => new_se,
+                                Err(_) => return vec![]
+                            }
+                        };
+                        all_grammars.with(|grammars| grammars.borrow_mut()
+                            .insert(self.id.get_ref(), new_se.clone()));
+                        new_se
+                    }
+                };
 
                 vec![(Item { start_idx: cur_idx, rule: new_se.find_or_panic(name).clone(), pos: 0,
                              done: RefCell::new(false),
@@ -630,7 +712,6 @@ impl Item {
                       },
                       false)]
             }
-            (0, &ComputeSyntax(_,_)) => { panic!("TODO") },
             (0, &Named(_, ref body)) | (0, &NameImport(ref body, _))
             | (0, &QuoteDeepen(ref body, _)) | (0, &QuoteEscape(ref body, _)) => {
                 self.start(&body, cur_idx, false)
@@ -686,16 +767,18 @@ impl Item {
         let res = match *self.rule {
             Anyways(ref a) => Ok(a.clone()),
             Impossible => panic!("Impossible!"),
-            Literal(_) | AnyToken | AnyAtomicToken | VarRef => {
+            Literal(_) | AnyToken | AnyAtomicToken | VarRef | IntLiteral | StringLiteral
+            | CharLiteral => {
                 match self.local_parse.borrow().clone() {
-                    ParsedAtom(a) => Ok(a), _ => { panic!("ICE: no simple parse saved")}
+                    ParsedAtom(a) => Ok(a.with_span(span_between(self.start_idx, done_tok))),
+                    _ => { panic!("ICE: no simple parse saved")}
                 }
             },
             Delimited(_, _, _) => {
                 // HACK: the wanted item is misaligned by a token because of the close delimiter
                 self.find_wanted(chart, done_tok-1).c_parse(chart, done_tok-1)
             }
-            Alt(_) | Biased(_, _) | Call(_) | SynImport(_,_, _) => {
+            Alt(_) | Biased(_, _) | Call(_) | SynImport(_,_, _) | ComputeSyntax(_, _) => {
                 self.find_wanted(chart, done_tok).c_parse(chart, done_tok)
             },
             Seq(_) | Star(_) | Plus(_) => {
@@ -722,9 +805,11 @@ impl Item {
                         for idx in 0..chart[pos].len() {
                             let i = &chart[pos][idx];
                             log!("Checking {:#?}\n", i);
+                            // (Without checking `start_idx`, we might find a nested use
+                            //  of the same rule that happens to end at the same place.)
                             if self.grammar.almost_ptr_eq(&i.grammar)
                                 && &*self.rule as *const FormPat == &*i.rule as *const FormPat
-                                && step.pos - 1 == i.pos {
+                                && step.pos - 1 == i.pos && self.start_idx == i.start_idx {
                                 step = i;
                                 found = true;
                                 break;
@@ -739,7 +824,9 @@ impl Item {
                 for (i, subtree) in subtrees.iter_mut().enumerate() {
                     if let Some(unwrapped) = ddd_unwrap(&subtree) {
                         if ddd_pos != None {
-                            return Err(ParseError{msg: format!("Found two DDDs at {}", unwrapped)});
+                            return Err(ParseError{
+                                msg: format!("Found two DDDs at {}", unwrapped),
+                                loc: span_between(self.start_idx, done_tok)});
                         }
                         *subtree = unwrapped;
                         ddd_pos = Some(i);
@@ -754,7 +841,6 @@ impl Item {
                     _ => { panic!("ICE: seriously, this can't happen") }
                 }
             },
-            ComputeSyntax(_, _) => { panic!("TODO") },
             Named(name, _) => {
                 let sub_parsed = self.find_wanted(chart, done_tok).c_parse(chart, done_tok)?;
                 Ok(Ast::IncompleteNode(::util::mbe::EnvMBE::new_from_leaves(
@@ -763,7 +849,8 @@ impl Item {
             Scope(ref form, ref export) => {
                 let sub_parsed = self.find_wanted(chart, done_tok).c_parse(chart, done_tok)?;
                 // TODO #14: We should add zero-length repeats of missing `Named`s,
-                Ok(Ast::Node(form.clone(), sub_parsed.flatten(), export.clone()))
+                Ok(Ast::Node(form.clone(), sub_parsed.flatten(), export.clone(),
+                             span_between(self.start_idx, done_tok)))
             },
             NameImport(_, ref beta) => {
                 let sub_parsed = self.find_wanted(chart, done_tok).c_parse(chart, done_tok)?;
@@ -792,7 +879,25 @@ type ParseResult = Result<Ast, ParseError>;
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct ParseError {
-    pub msg: String
+    pub msg: String,
+    pub loc: Span
+}
+
+impl ::std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{}: {}", self.loc, self.msg)
+    }
+}
+
+impl ::util::err::Diagnostic for ParseError {
+    fn kind(&self) -> String { "ParseError".to_string() }
+    fn describe(&self) -> String { self.msg.clone() }
+}
+
+impl ParseError {
+    pub fn render(&self, file: Name, source: &str, color: bool) -> String {
+        ::util::err::render_diagnostic(self, self.loc, file, source, color)
+    }
 }
 
 pub fn parse(rule: &FormPat, grammar: &SynEnv, tt: &TokenTree) -> ParseResult {
@@ -806,13 +911,55 @@ pub fn parse(rule: &FormPat, grammar: &SynEnv, tt: &TokenTree) -> ParseResult {
         Some(i) => i.c_parse(&chart, chart.len()-1),
         None => {
             best_token.with(|bt| {
-                let (idx, tok, ref grammar, pos) = *bt.borrow();
-                Err(ParseError{ msg: format!("Could not parse past token {} ({}) {:#?} {}", idx, tok, grammar, pos) })
+                let (mut idx, tok, _, _) = *bt.borrow();
+                let tok = if !chart[chart.len()-1].is_empty() { // We ran out of tokens
+                    idx = chart.len()-1;
+                    "end of input".to_string()
+                } else if Simple(tok) == end_of_delim() {
+                    "end of group".to_string()
+                } else {
+                    format!("`{}`", tok)
+                };
+                Err(ParseError{
+                    msg: format!("unexpected {}\nexpected {}",
+                                 tok, expected_at(&chart[idx])),
+                    loc: span_between(idx, idx + 1) })
             })
         }
     }
 }
 
+// How many expected tokens do we bother listing?
+const max_expected : usize = 8;
+
+/// Describe the tokens that the items in `column` are waiting for.
+fn expected_at(column: &[Item]) -> String {
+    let mut expected : Vec<String> = vec![];
+    for item in column {
+        if *item.done.borrow() { continue; }
+        let desc = match (item.pos, &*item.rule) {
+            (0, &Literal(n)) => format!("`{}`", n),
+            (0, &AnyToken) => "any token".to_string(),
+            (0, &AnyAtomicToken) => "an atom".to_string(),
+            (0, &VarRef) => "a name".to_string(),
+            (0, &IntLiteral) => "an integer literal".to_string(),
+            (0, &StringLiteral) => "a string literal".to_string(),
+            (0, &CharLiteral) => "a character literal".to_string(),
+            (0, &Delimited(n, _, _)) => format!("`{}`", n),
+            (2, &Delimited(_, _, _)) => "the end of the group".to_string(),
+            _ => continue
+        };
+        if !expected.contains(&desc) { expected.push(desc); }
+    }
+    match expected.len() {
+        0 => "nothing more here".to_string(),
+        1 => expected[0].clone(),
+        len if len <= max_expected => format!("one of {}", expected.join(", ")),
+        len => format!("one of {}, or {} others",
+                       expected[..max_expected].join(", "), len - max_expected)
+    }
+}
+
 fn parse_top(rule: &FormPat, tt: &TokenTree) -> ParseResult {
     parse(rule, &::util::assoc::Assoc::new(), tt)
 }
@@ -922,6 +1069,18 @@ fn earley_merging() {
 
 }
 
+#[test]
+fn parse_errors() {
+    let rule = Seq(vec![Rc::new(Literal(n("a"))),
+                        Rc::new(Alt(vec![Rc::new(Literal(n("b"))), Rc::new(IntLiteral)]))]);
+    assert_m!(parse_top(&rule, &tokens!("a" "x")), Err(ref e),
+              e.msg == "unexpected `x`\nexpected one of `b`, an integer literal");
+    assert_m!(parse_top(&rule, &tokens!("a")), Err(ref e),
+              e.msg == "unexpected end of input\nexpected one of `b`, an integer literal");
+    assert_m!(parse_top(&rule, &tokens!("a" "b" "b")), Err(ref e),
+              e.msg == "unexpected `b`\nexpected nothing more here");
+}
+
 #[test]
 fn earley_simple_recognition() {
     let main_grammar = ::util::assoc::Assoc::new();
@@ -1123,6 +1282,19 @@ fn basic_parsing_e() {
               Ok(_));
 }
 
+#[test]
+fn parse_nested_same_rule() {
+    // In `inc inc x`, both uses of `inc` reach the nullable `(anyways ⋯)` at the same place
+    let env = assoc_n!(
+        "inc" => Rc::new(form_pat!([[(lit "inc"), (named "e", (call "e"))], (anyways "!")])),
+        "e" => Rc::new(form_pat!((alt aat, (call "inc")))));
+
+    assert_eq!(parse(&Call(n("e")), &env, &tokens!("inc" "x")),
+        Ok(ast_shape!(("inc" {- "e" => "x"}) "!")));
+    assert_eq!(parse(&Call(n("e")), &env, &tokens!("inc" "inc" "x")),
+        Ok(ast_shape!(("inc" {- "e" => (("inc" {- "e" => "x"}) "!")}) "!")));
+}
+
 #[test]
 fn parse_ddd() {
 
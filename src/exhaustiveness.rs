@@ -0,0 +1,413 @@
+// Exhaustiveness and redundancy checking for `match`, using the "pattern matrix" approach
+//  from Maranget's "Warnings for pattern matching".
+//
+// Patterns are translated (guided by the scrutinee type) into constructors applied to
+//  subpatterns. A type whose values come from a known, finite set of constructors
+//  (`enum`s, `struct`s, and tuples) can be covered without a wildcard;
+//  anything else (`Int`, `String`, syntax, abstract types...) needs one.
+
+use ast::*;
+use name::*;
+use ty::Ty;
+use util::assoc::Assoc;
+use core_forms::{ast_to_name, ast_to_int};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Ctor {
+    Arm(Name), // an `enum` arm
+    Struct(Vec<Name>),
+    Tuple(usize),
+    Lit(String),
+    Opaque(usize) // a pattern we can't see into (e.g. a quoted one); it only equals itself
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Pat {
+    Wild,
+    Con(Ctor, Vec<Pat>),
+    Or(Vec<Pat>)
+}
+
+impl ::std::fmt::Display for Pat {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        use self::Pat::*;
+        match *self {
+            Wild => write!(f, "_"),
+            Or(ref alts) => {
+                let alts: Vec<String> = alts.iter().map(|a| format!("{}", a)).collect();
+                write!(f, "{}", alts.join(" | "))
+            }
+            Con(Ctor::Arm(name), ref args) => {
+                write!(f, "+[{}", name)?;
+                for a in args { write!(f, " {}", a)?; }
+                write!(f, "]+")
+            }
+            Con(Ctor::Struct(ref names), ref args) => {
+                write!(f, "*[")?;
+                for (name, a) in names.iter().zip(args) { write!(f, "{}: {} ", name, a)?; }
+                write!(f, "]*")
+            }
+            Con(Ctor::Tuple(_), ref args) => {
+                let args: Vec<String> = args.iter().map(|a| format!("{}", a)).collect();
+                write!(f, "**[{}]**", args.join(" "))
+            }
+            Con(Ctor::Lit(ref l), _) => write!(f, "{}", l),
+            Con(Ctor::Opaque(_), _) => write!(f, "_")
+        }
+    }
+}
+
+/// What values of a type look like, as far as matching is concerned.
+enum Shape {
+    Enum(Vec<(Name, Vec<Ty>)>),
+    Struct(Vec<(Name, Ty)>),
+    Tuple(Vec<Ty>),
+    Open // infinitely many constructors, or ones we can't see
+}
+
+fn shape(ty: &Ty, env: &Assoc<Name, Ty>) -> Shape {
+    let mut ty = ty.clone();
+    // Look through names and `mu`s (we only ever match on unfolded things, but
+    //  the component types of an unfolded thing can still be names for `mu` types).
+    for _ in 0..16 {
+        let next = match ty.0 {
+            VariableReference(name, _) => match env.find(&name) {
+                Some(t) if t != &ty => t.clone(),
+                _ => return Shape::Open // protected
+            },
+            Node(ref f, ref parts, _, _) if f.name.is("mu_type") => {
+                match *parts.get_leaf_or_panic(&n("body")) {
+                    ExtendEnv(ref body, _) => Ty::new((**body).clone()),
+                    ref body => Ty::new(body.clone())
+                }
+            }
+            Node(ref f, ref parts, _, _) if f.name.is("enum") => {
+                return Shape::Enum(parts.march_all(&[n("name")]).iter().map(|arm| {
+                    (ast_to_name(arm.get_leaf_or_panic(&n("name"))),
+                     arm.get_rep_leaf_or_panic(n("component")).into_iter()
+                        .map(|c| Ty::new(c.clone())).collect())
+                }).collect())
+            }
+            Node(ref f, ref parts, _, _) if f.name.is("struct") => {
+                let mut fields: Vec<(Name, Ty)> =
+                    parts.march_all(&[n("component")]).iter().map(|field| {
+                        (ast_to_name(field.get_leaf_or_panic(&n("component_name"))),
+                         Ty::new(field.get_leaf_or_panic(&n("component")).clone()))
+                    }).collect();
+                fields.sort_by(|a, b| a.0.orig_sp().cmp(&b.0.orig_sp()));
+                return Shape::Struct(fields)
+            }
+            Node(ref f, ref parts, _, _) if f.name.is("tuple") => {
+                return Shape::Tuple(parts.get_rep_leaf_or_panic(n("component")).into_iter()
+                    .map(|c| Ty::new(c.clone())).collect())
+            }
+            _ => return Shape::Open
+        };
+        ty = next;
+    }
+    Shape::Open
+}
+
+impl Shape {
+    /// All the constructors of the type, if there are finitely many
+    fn signature(&self) -> Option<Vec<Ctor>> {
+        match *self {
+            Shape::Enum(ref arms) => Some(arms.iter().map(|&(name, _)| Ctor::Arm(name)).collect()),
+            Shape::Struct(ref fields) => {
+                Some(vec![Ctor::Struct(fields.iter().map(|&(name, _)| name).collect())])
+            }
+            Shape::Tuple(ref comps) => Some(vec![Ctor::Tuple(comps.len())]),
+            Shape::Open => None
+        }
+    }
+
+    /// The types of the arguments to `c`
+    fn arg_types(&self, c: &Ctor) -> Vec<Ty> {
+        let res = match (self, c) {
+            (&Shape::Enum(ref arms), &Ctor::Arm(name)) => {
+                arms.iter().find(|arm| arm.0 == name).map(|arm| arm.1.clone())
+            }
+            (&Shape::Struct(ref fields), &Ctor::Struct(_)) => {
+                Some(fields.iter().map(|f| f.1.clone()).collect())
+            }
+            (&Shape::Tuple(ref comps), &Ctor::Tuple(_)) => Some(comps.clone()),
+            _ => None
+        };
+        res.unwrap_or_else(|| vec![Ty::new(Trivial); arity(c)])
+    }
+}
+
+// Only used for ill-typed things:
+fn arity(c: &Ctor) -> usize {
+    match *c { Ctor::Struct(ref names) => names.len(), Ctor::Tuple(len) => len, _ => 0 }
+}
+
+thread_local! {
+    static next_opaque: ::std::cell::Cell<usize> = ::std::cell::Cell::new(0);
+}
+
+fn opaque() -> Pat {
+    next_opaque.with(|id| { id.set(id.get() + 1); Pat::Con(Ctor::Opaque(id.get()), vec![]) })
+}
+
+/// Translate a (typechecked) pattern of type `ty`.
+pub fn translate(pat: &Ast, ty: &Ty, env: &Assoc<Name, Ty>) -> Pat {
+    let (f, parts) = match *pat {
+        Atom(_, _) => return Pat::Wild,
+        Node(ref f, ref parts, _, _) => (f, parts),
+        _ => return opaque()
+    };
+    let shape = shape(ty, env);
+    match &*f.name.orig_sp() {
+        "wildcard_pat" => Pat::Wild,
+        "enum_pat" => {
+            let c = Ctor::Arm(ast_to_name(parts.get_leaf_or_panic(&n("name"))));
+            let comps = parts.get_rep_leaf_or_panic(n("component"));
+            let comp_tys = shape.arg_types(&c);
+            if comps.len() != comp_tys.len() { return opaque() }
+            Pat::Con(c, comps.into_iter().zip(comp_tys.iter())
+                .map(|(p, t)| translate(p, t, env)).collect())
+        }
+        "struct_pat" => {
+            let fields = match shape { Shape::Struct(fields) => fields, _ => return opaque() };
+            let given = parts.march_all(&[n("component")]);
+            let args = fields.iter().map(|&(name, ref t)| {
+                // Unmentioned fields can be anything:
+                match given.iter().find(|g| {
+                    ast_to_name(g.get_leaf_or_panic(&n("component_name"))).orig_sp()
+                        == name.orig_sp()
+                }) {
+                    Some(g) => translate(g.get_leaf_or_panic(&n("component")), t, env),
+                    None => Pat::Wild
+                }
+            }).collect();
+            Pat::Con(Ctor::Struct(fields.iter().map(|f| f.0).collect()), args)
+        }
+        "tuple_pat" => {
+            let comps = parts.get_rep_leaf_or_panic(n("component"));
+            let c = Ctor::Tuple(comps.len());
+            let comp_tys = shape.arg_types(&c);
+            Pat::Con(c, comps.into_iter().zip(comp_tys.iter())
+                .map(|(p, t)| translate(p, t, env)).collect())
+        }
+        "int_pat" => {
+            Pat::Con(Ctor::Lit(format!("{}", ast_to_int(parts.get_leaf_or_panic(&n("n"))))),
+                     vec![])
+        }
+        "string_pat" | "char_pat" => {
+            let lit = parts.get_leaf(n("s")).or(parts.get_leaf(n("c"))).unwrap();
+            Pat::Con(Ctor::Lit(format!("{}", ast_to_name(lit).orig_sp())), vec![])
+        }
+        "or_pat" => {
+            let mut alts = vec![translate(parts.get_leaf_or_panic(&n("first")), ty, env)];
+            for alt in parts.get_rep_leaf_or_panic(n("alt")) {
+                alts.push(translate(alt, ty, env));
+            }
+            Pat::Or(alts)
+        }
+        _ => opaque() // quoted patterns, and anything else we don't understand
+    }
+}
+
+/// Rows whose first column matches `c`, with that column replaced by `c`'s arguments.
+fn specialize(rows: &[Vec<Pat>], c: &Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    let mut res = vec![];
+    for row in rows {
+        match row[0] {
+            Pat::Wild => {
+                let mut new_row = vec![Pat::Wild; arity];
+                new_row.extend_from_slice(&row[1..]);
+                res.push(new_row);
+            }
+            Pat::Con(ref row_c, ref args) if row_c == c => {
+                let mut new_row = args.clone();
+                new_row.extend_from_slice(&row[1..]);
+                res.push(new_row);
+            }
+            Pat::Con(_, _) => {}
+            Pat::Or(ref alts) => { res.append(&mut specialize(&expand_or(alts, row), c, arity)); }
+        }
+    }
+    res
+}
+
+/// Rows whose first column is a wildcard, with that column removed.
+fn default(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    let mut res = vec![];
+    for row in rows {
+        match row[0] {
+            Pat::Wild => res.push(row[1..].to_vec()),
+            Pat::Con(_, _) => {}
+            Pat::Or(ref alts) => { res.append(&mut default(&expand_or(alts, row))); }
+        }
+    }
+    res
+}
+
+fn expand_or(alts: &[Pat], row: &[Pat]) -> Vec<Vec<Pat>> {
+    alts.iter().map(|alt| {
+        let mut new_row = vec![alt.clone()];
+        new_row.extend_from_slice(&row[1..]);
+        new_row
+    }).collect()
+}
+
+fn head_ctors(rows: &[Vec<Pat>]) -> Vec<Ctor> {
+    fn add(p: &Pat, res: &mut Vec<Ctor>) {
+        match *p {
+            Pat::Wild => {}
+            Pat::Con(ref c, _) => if !res.contains(c) { res.push(c.clone()) },
+            Pat::Or(ref alts) => for alt in alts { add(alt, res) }
+        }
+    }
+    let mut res = vec![];
+    for row in rows { add(&row[0], &mut res) }
+    res
+}
+
+/// `Some(sig)` if the constructors in `heads` cover everything in the signature
+fn complete(shape: &Shape, heads: &[Ctor]) -> Option<Vec<Ctor>> {
+    shape.signature().and_then(|sig| {
+        if sig.iter().all(|c| heads.contains(c)) { Some(sig) } else { None }
+    })
+}
+
+fn prepend<T: Clone>(front: Vec<T>, back: &[T]) -> Vec<T> {
+    let mut res = front;
+    res.extend_from_slice(back);
+    res
+}
+
+/// Can `q` match anything that none of `rows` match?
+fn useful(rows: &[Vec<Pat>], q: &[Pat], tys: &[Ty], env: &Assoc<Name, Ty>) -> bool {
+    if q.is_empty() { return rows.is_empty() }
+    let shape = shape(&tys[0], env);
+    match q[0] {
+        Pat::Or(ref alts) => alts.iter().any(|alt| {
+            useful(rows, &prepend(vec![alt.clone()], &q[1..]), tys, env)
+        }),
+        Pat::Con(ref c, ref args) => {
+            useful(&specialize(rows, c, args.len()), &prepend(args.clone(), &q[1..]),
+                   &prepend(shape.arg_types(c), &tys[1..]), env)
+        }
+        Pat::Wild => match complete(&shape, &head_ctors(rows)) {
+            Some(sig) => sig.iter().any(|c| {
+                let arg_tys = shape.arg_types(c);
+                useful(&specialize(rows, c, arg_tys.len()),
+                       &prepend(vec![Pat::Wild; arg_tys.len()], &q[1..]),
+                       &prepend(arg_tys, &tys[1..]), env)
+            }),
+            None => useful(&default(rows), &q[1..], &tys[1..], env)
+        }
+    }
+}
+
+/// A list of patterns (one per column) that none of `rows` match, if there is one.
+fn missing(rows: &[Vec<Pat>], tys: &[Ty], env: &Assoc<Name, Ty>) -> Option<Vec<Pat>> {
+    if tys.is_empty() {
+        return if rows.is_empty() { Some(vec![]) } else { None }
+    }
+    let shape = shape(&tys[0], env);
+    let heads = head_ctors(rows);
+    match complete(&shape, &heads) {
+        Some(sig) => {
+            for c in sig {
+                let arg_tys = shape.arg_types(&c);
+                let specialized = specialize(rows, &c, arg_tys.len());
+                let sub_tys = prepend(arg_tys.clone(), &tys[1..]);
+                if let Some(mut w) = missing(&specialized, &sub_tys, env) {
+                    let rest = w.split_off(arg_tys.len());
+                    return Some(prepend(vec![Pat::Con(c, w)], &rest));
+                }
+            }
+            None
+        }
+        None => missing(&default(rows), &tys[1..], env).map(|w| {
+            // If some constructors are mentioned, name one that isn't; otherwise, say `_`:
+            let head = match shape.signature() {
+                Some(ref sig) if !heads.is_empty() => {
+                    let c = sig.iter().find(|c| !heads.contains(c)).unwrap().clone();
+                    Pat::Con(c.clone(), vec![Pat::Wild; shape.arg_types(&c).len()])
+                }
+                _ => Pat::Wild
+            };
+            prepend(vec![head], &w)
+        })
+    }
+}
+
+/// The result of checking the arms of a `match`
+#[derive(Clone, PartialEq, Debug)]
+pub struct Coverage {
+    /// An example of a value that no arm matches
+    pub missing: Option<Pat>,
+    /// The indices of arms that can never be reached
+    pub unreachable: Vec<usize>
+}
+
+/// We can't see what an opaque pattern covers, so give it the benefit of the doubt.
+/// (So syntax can be matched with quoted patterns alone, as macros tend to do.)
+fn blur(p: &Pat) -> Pat {
+    match *p {
+        Pat::Con(Ctor::Opaque(_), _) => Pat::Wild,
+        Pat::Con(ref c, ref args) => Pat::Con(c.clone(), args.iter().map(blur).collect()),
+        Pat::Or(ref alts) => Pat::Or(alts.iter().map(blur).collect()),
+        Pat::Wild => Pat::Wild
+    }
+}
+
+/// Check a `match` on a `scrutinee_ty`; each arm is a pattern and whether it has a guard.
+/// (Guarded arms can be unreachable, but don't count towards exhaustiveness.)
+pub fn check_match(arms: &[(Pat, bool)], scrutinee_ty: &Ty, env: &Assoc<Name, Ty>)
+        -> Coverage {
+    let tys = vec![scrutinee_ty.clone()];
+    let mut rows: Vec<Vec<Pat>> = vec![];
+    let mut unreachable = vec![];
+    for (i, &(ref p, guarded)) in arms.iter().enumerate() {
+        if !useful(&rows, &[p.clone()], &tys, env) {
+            unreachable.push(i);
+        }
+        if !guarded { rows.push(vec![p.clone()]); }
+    }
+    let blurred_rows: Vec<Vec<Pat>> = rows.iter().map(|row| vec![blur(&row[0])]).collect();
+    Coverage {
+        missing: missing(&blurred_rows, &tys, env).map(|mut w| w.remove(0)),
+        unreachable: unreachable
+    }
+}
+
+#[test]
+fn basic_coverage() {
+    let env = ::runtime::core_values::core_types();
+    let bool_ty = ::ty::synth_type(&ast!((vr "Bool")), env.clone()).unwrap();
+    let t = Pat::Con(Ctor::Arm(n("True")), vec![]);
+    let f = Pat::Con(Ctor::Arm(n("False")), vec![]);
+
+    assert_eq!(check_match(&[(t.clone(), false), (f.clone(), false)], &bool_ty, &env),
+               Coverage { missing: None, unreachable: vec![] });
+    assert_eq!(check_match(&[(t.clone(), false)], &bool_ty, &env),
+               Coverage { missing: Some(f.clone()), unreachable: vec![] });
+    // A guarded arm doesn't count:
+    assert_eq!(check_match(&[(t.clone(), true), (f.clone(), false)], &bool_ty, &env),
+               Coverage { missing: Some(t.clone()), unreachable: vec![] });
+    assert_eq!(check_match(&[(Pat::Wild, false), (t.clone(), false)], &bool_ty, &env),
+               Coverage { missing: None, unreachable: vec![1] });
+    assert_eq!(check_match(&[(Pat::Or(vec![f.clone(), t.clone()]), false), (Pat::Wild, false)],
+                           &bool_ty, &env),
+               Coverage { missing: None, unreachable: vec![1] });
+
+    // Tuples of `Bool`s:
+    let pair_ty = Ty::new(ast!({"Type" "tuple" :
+        "component" => [(, bool_ty.concrete()), (, bool_ty.concrete())]}));
+    let pair = |a: &Pat, b: &Pat| Pat::Con(Ctor::Tuple(2), vec![a.clone(), b.clone()]);
+    let cov = check_match(&[(pair(&t, &Pat::Wild), false), (pair(&Pat::Wild, &t), false)],
+                          &pair_ty, &env);
+    assert_eq!(cov, Coverage { missing: Some(pair(&f, &f)), unreachable: vec![] });
+    assert_eq!(format!("{}", cov.missing.unwrap()), "**[+[False]+ +[False]+]**");
+
+    // `Int`s need a catch-all:
+    let int_ty = ty!({"Type" "Int" :});
+    assert_eq!(check_match(&[(Pat::Con(Ctor::Lit("0".to_owned()), vec![]), false)],
+                           &int_ty, &env),
+               Coverage { missing: Some(Pat::Wild), unreachable: vec![] });
+}
@@ -26,6 +26,8 @@ custom_derive! {
         pub type_compare: BiDiWR<::ty_compare::Canonicalize, ::ty_compare::Subtype>,
         /// From a type environment, construct the type of this term.
         pub synth_type: BiDiWR<::ty::SynthTy, ::ty::UnpackTy>,
+        /// (type only) From a kind environment, construct the kind of this type.
+        pub synth_kind: BiDiWR<::kind::SynthKind, ::kind::UnpackKind>,
         /// (expr and pat only) From a value environment, evaluate this term.
         pub eval: BiDiWR<::runtime::eval::Eval, ::runtime::eval::Destructure>,
         /// At runtime, pick up code to use it as a value
@@ -84,6 +86,7 @@ pub fn simple_form(form_name: &str, p: FormPat) -> Rc<Form> {
             name: n(form_name),
             grammar: Rc::new(p),
             type_compare: ::form::Both(WalkRule::NotWalked, WalkRule::NotWalked),
+            synth_kind: ::form::Positive(WalkRule::NotWalked),
             synth_type: ::form::Positive(WalkRule::NotWalked),
             eval: ::form::Positive(WalkRule::NotWalked),
             quasiquote: ::form::Both(WalkRule::LiteralLike, WalkRule::LiteralLike)
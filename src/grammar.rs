@@ -14,7 +14,7 @@ use beta::{Beta, ExportBeta};
 impl Token {
     fn to_ast(&self) -> Ast {
         match *self {
-            Simple(ref s) => Atom(*s),
+            Simple(ref s) => Atom(*s, ::util::err::Span::nowhere()),
             Group(ref _s, ref _delim, ref body) => {
                 Shape(body.t.iter().map(|t| t.to_ast()).collect())
             }
@@ -41,6 +41,12 @@ custom_derive! {
         AnyToken,
         AnyAtomicToken,
         VarRef,
+        /// Matches an integer literal token (see `read::int_literal`), producing an `Atom`
+        IntLiteral,
+        /// Likewise, for `read::string_literal`
+        StringLiteral,
+        /// Likewise, for `read::char_literal`
+        CharLiteral,
         Delimited(Name, DelimChar, Rc<FormPat>),
         Seq(Vec<Rc<FormPat>>),
         Star(Rc<FormPat>),
@@ -52,9 +58,10 @@ custom_derive! {
         Call(Name),
         /**
          * This is where syntax gets reflective.
-         * Evaluates its body (one phase up)
+         * Evaluates what its body parses to (one phase up)
          *  as a function from the current `SynEnv` to a new one,
-         *  and names the result in the current scope.
+         *  and parses the named nonterminal in the result.
+         * (Usually, the body is an `Anyways`, so the function is fixed by the grammar.)
          */
         ComputeSyntax(Name, Rc<FormPat>),
 
@@ -106,7 +113,8 @@ impl FormPat {
             Biased(ref body_a, ref body_b) => {
                 body_a.binders().tap(|v| v.append(&mut body_b.binders()))
             }
-            Anyways(_) | Impossible | Literal(_) | AnyToken | AnyAtomicToken | VarRef | Call(_) => {
+            Anyways(_) | Impossible | Literal(_) | AnyToken | AnyAtomicToken | VarRef | IntLiteral
+            | StringLiteral | CharLiteral | Call(_) => {
                 vec![]
             }
         }
@@ -123,7 +131,7 @@ impl FormPat {
                     sub = new_sub;
                 }
                 match **sub {
-                    Call(nt) => Some(Some(nt)),
+                    Call(nt) | ComputeSyntax(nt, _) => Some(Some(nt)),
                     AnyAtomicToken => Some(None),
                     _ => None
                 }
@@ -131,7 +139,8 @@ impl FormPat {
             Named(_,_) => None, // Otherwise, skip
             Call(_) => None,
             Scope(_,_) => None, // Only look in the current scope
-            Anyways(_) | Impossible | Literal(_) | AnyToken | AnyAtomicToken | VarRef => None,
+            Anyways(_) | Impossible | Literal(_) | AnyToken | AnyAtomicToken | VarRef | IntLiteral
+            | StringLiteral | CharLiteral => None,
             Delimited(_,_,ref body) |Star(ref body) | Plus(ref body) | ComputeSyntax(_, ref body)
                     | SynImport(ref body, _, _) | NameImport(ref body, _) | QuoteDeepen(ref body, _)
                     | QuoteEscape(ref body, _) => {
@@ -185,11 +194,41 @@ impl ::std::fmt::Debug for SyntaxExtension {
 
 pub type SynEnv = Assoc<Name, Rc<FormPat>>;
 
+// Reifying a `SynEnv` structurally would copy its `Form`s, and `Form`s are compared by identity.
+// So, at runtime, a `SynEnv` is opaque: its `FormPat`s are smuggled inside a `Node`.
+pub fn reify_syn_env(se: &SynEnv) -> ::runtime::eval::Value {
+    let nts = se.iter_pairs().map(|(nt, pat)| Rc::new(Named(*nt, pat.clone()))).collect();
+    ::runtime::eval::Value::AbstractSyntax(Node(
+        simple_form("syn_env", Seq(nts)), ::util::mbe::EnvMBE::new(), ExportBeta::Nothing,
+        ::util::err::Span::nowhere()))
+}
+
+// `loc` is where `v` came from (for error messages).
+pub fn reflect_syn_env(v: &::runtime::eval::Value, loc: &Ast)
+        -> Result<SynEnv, ::runtime::eval::RuntimeErr> {
+    let nts = match *v {
+        ::runtime::eval::Value::AbstractSyntax(Node(ref f, _, _, _)) if f.name == n("syn_env") => {
+            match *f.grammar { Seq(ref nts) => nts.clone(), _ => panic!("ICE: malformed SynEnv") }
+        }
+        // Computations run before typechecking, so this can happen:
+        _ => return Err(::runtime::eval::rt_err(
+            ::runtime::eval::RuntimeError::NotASynEnv(v.clone()), loc))
+    };
+    // (`iter_pairs` goes from the newest binding to the oldest)
+    Ok(nts.iter().rev().fold(Assoc::new(), |se, nt| {
+        match **nt {
+            Named(nt, ref pat) => se.set(nt, pat.clone()),
+            _ => panic!("ICE: malformed SynEnv")
+        }
+    }))
+}
+
 /// Currently only used for DDDs
 pub fn plug_hole(outer: &Rc<FormPat>, hole: Name, inner: &Rc<FormPat>) -> Rc<FormPat> {
     match **outer {
         Call(n) => if n == hole { inner.clone() } else { outer.clone() },
-        Anyways(_) | Impossible | Literal(_) | AnyToken | AnyAtomicToken | VarRef => {
+        Anyways(_) | Impossible | Literal(_) | AnyToken | AnyAtomicToken | VarRef | IntLiteral
+        | StringLiteral | CharLiteral => {
             outer.clone()
         }
         Seq(ref subs) => {
@@ -371,6 +410,32 @@ fn extensible_parsing() {
             &mt_syn_env, &tokens!("X" "X" "X" "X" "X" "4")),
         Err(_));
 
+    // `ComputeSyntax` runs an Unseemly function on the `SynEnv`:
+    let copy_a_to_b = ast!({"Expr" "lambda" :
+        "param" => [@"p" "se"],
+        "p_t" => [@"p" (vr "SynEnv")],
+        "body" => (import [* ["param" : "p_t"]] {"Expr" "apply" :
+            "rator" => (vr "copy_nonterminal"),
+            "rand" => [(vr "se"),
+                       {"Expr" "string_literal" : "s" => "\"a\""},
+                       {"Expr" "string_literal" : "s" => "\"b\""}]})});
+    let a_and_b = Rc::new(assoc_n!(
+        "a" => Rc::new(form_pat!((lit "AA"))), "b" => Rc::new(form_pat!((lit "BB")))));
+
+    assert_eq!(
+        parse(&ComputeSyntax(n("b"), Rc::new(Anyways(copy_a_to_b.clone()))), &a_and_b,
+              &tokens!("AA")),
+        Ok(ast!("AA")));
+    assert_m!(
+        parse(&ComputeSyntax(n("b"), Rc::new(Anyways(copy_a_to_b.clone()))), &a_and_b,
+              &tokens!("BB")),
+        Err(_));
+    // ...and unparsing has to do the same:
+    assert_eq!(
+        ::unparse::unparse_mbe(&ComputeSyntax(n("b"), Rc::new(Anyways(copy_a_to_b))),
+                               &ast!("AA"), &::util::mbe::EnvMBE::new(), &a_and_b),
+        "AA");
+
 
 
 }
@@ -0,0 +1,261 @@
+/*
+Kinds are to types as types are to expressions.
+`*` is the kind of (proper) types, like `Int` or `[Int -> Bool]`,
+ and `k[* -> *]k` is the kind of type constructors, like `List` (which needs an argument).
+
+Kinds are just syntax (much like types), so we represent them with `Ty`.
+A `VariableReference` kind is one we don't know (e.g. for an abstract type like `Expr`);
+ it's compatible with anything.
+
+Kind synthesis is a positive walk over type syntax, in an environment of kinds.
+That environment comes from the type environment:
+ a name bound to a `forall` type is a type constructor, since that's what `type_apply` applies,
+ and anything else is a proper type.
+A `forall` written out in place, though, is a (polymorphic) proper type.
+*/
+
+use ast_walk::{walk, LazyWalkReses, WalkRule};
+use ast_walk::WalkRule::*;
+use walk_mode::WalkMode;
+use form::Form;
+use util::assoc::Assoc;
+use ast::*;
+use name::*;
+use util::err::Span;
+use ty::{Ty, TyErr, TypeError};
+use core_forms::{ast_to_name, vr_to_name};
+
+custom_derive!{
+    #[derive(Copy, Clone, Debug, Reifiable)]
+    pub struct SynthKind {}
+}
+custom_derive!{
+    #[derive(Copy, Clone, Debug, Reifiable)]
+    pub struct UnpackKind {}
+}
+
+impl WalkMode for SynthKind {
+    fn name() -> &'static str { "SynKd" }
+    type Elt = Ty;
+    type Negated = UnpackKind;
+    type Err = TypeError;
+    type D = ::walk_mode::Positive<SynthKind>;
+    type ExtraInfo = ();
+
+    fn get_walk_rule(f: &Form) -> WalkRule<SynthKind> { f.synth_kind.pos().clone() }
+    // The type forms that bind names (`forall_type`) say what kinds they have:
+    fn automatically_extend_env() -> bool { false }
+
+    fn walk_var(name: Name, parts: &LazyWalkReses<SynthKind>) -> Result<Ty, TypeError> {
+        match parts.env.find(&name) {
+            None => {
+                Err(::util::err::sp(::ty::unbound_name(name, &parts.env), parts.this_ast.clone()))
+            }
+            Some(kind) => Ok(kind.clone())
+        }
+    }
+
+    fn underspecified(name: Name) -> Ty { Ty(VariableReference(name, Span::nowhere())) }
+}
+
+// Nothing is kind-checked negatively; this just keeps `WalkMode` happy.
+impl WalkMode for UnpackKind {
+    fn name() -> &'static str { "UnpKd" }
+    type Elt = Ty;
+    type Negated = SynthKind;
+    type Err = TypeError;
+    type D = ::walk_mode::Negative<UnpackKind>;
+    type ExtraInfo = ();
+
+    fn get_walk_rule(f: &Form) -> WalkRule<UnpackKind> { f.synth_kind.neg().clone() }
+    fn automatically_extend_env() -> bool { false }
+
+    fn underspecified(name: Name) -> Ty { Ty(VariableReference(name, Span::nowhere())) }
+}
+
+impl ::walk_mode::NegativeWalkMode for UnpackKind {
+    fn needs_pre_match() -> bool { false }
+}
+
+/// `*`
+pub fn star() -> Ty { Ty(ast!({"Kind" "type_kind" :})) }
+
+/// `k[ params ⋯ -> res ]k`
+pub fn higher_kind(params: Vec<Ty>, res: Ty) -> Ty {
+    Ty(ast!({"Kind" "higher_kind" :
+        "param" => (,seq params.into_iter().map(|p| p.0).collect::<Vec<_>>()),
+        "res" => (, res.0)}))
+}
+
+fn unknown_kind() -> Ty { SynthKind::underspecified(n("unknown_kind")) }
+
+/// Can something of kind `got` go where something of kind `expected` is needed?
+pub fn kinds_match(got: &Ty, expected: &Ty) -> bool {
+    match (&got.0, &expected.0) {
+        (&VariableReference(_, _), _) | (_, &VariableReference(_, _)) => true,
+        (&Node(ref got_f, ref got_parts, _, _), &Node(ref exp_f, ref exp_parts, _, _)) => {
+            if got_f.name != exp_f.name { return false; }
+            if got_f.name == n("type_kind") { return true; }
+
+            let got_params = got_parts.get_rep_leaf_or_panic(n("param"));
+            let exp_params = exp_parts.get_rep_leaf_or_panic(n("param"));
+            got_params.len() == exp_params.len()
+                && got_params.iter().zip(exp_params.iter()).all(
+                       |(g, e)| kinds_match(&Ty((*g).clone()), &Ty((*e).clone())))
+                && kinds_match(&Ty(got_parts.get_leaf_or_panic(&n("res")).clone()),
+                               &Ty(exp_parts.get_leaf_or_panic(&n("res")).clone()))
+        }
+        _ => false
+    }
+}
+
+pub fn expect_kind(got: &Ty, expected: &Ty, loc: &Ast) -> Result<(), TypeError> {
+    if !kinds_match(got, expected) {
+        ty_err!(KindMismatch(got.clone(), expected.clone()) at loc);
+    }
+    Ok(())
+}
+
+/// The kind of a name bound to `t` in the type environment.
+fn binding_kind(t: &Ty) -> Ty {
+    match t.0 {
+        Node(ref f, ref parts, _, _) if f.name == n("forall_type") => {
+            higher_kind(parts.march_all(&[n("param")]).iter().map(|p| {
+                p.get_leaf(n("param_kind")).map(|k| Ty(k.clone())).unwrap_or_else(star)
+            }).collect(), star())
+        }
+        Node(ref f, _, _, _) if *f == ::core_type_forms::get__abstract_parametric_type() => {
+            unknown_kind()
+        }
+        VariableReference(_, _) => unknown_kind(), // e.g. a type parameter we're inside of
+        _ => star()
+    }
+}
+
+pub fn kind_env(ty_env: &Assoc<Name, Ty>) -> Assoc<Name, Ty> { ty_env.map(binding_kind) }
+
+pub fn synth_kind(t: &Ast, ty_env: &Assoc<Name, Ty>) -> Result<Ty, TypeError> {
+    walk::<SynthKind>(t, &LazyWalkReses::new_wrapper(kind_env(ty_env)))
+}
+
+/// Check that the type syntax `t` is a proper type (e.g. not `List` or `Int <[Bool]<`).
+pub fn expect_proper_type(t: &Ast, ty_env: &Assoc<Name, Ty>) -> Result<(), TypeError> {
+    expect_kind(&synth_kind(t, ty_env)?, &star(), t)
+}
+
+/// Most types (e.g. `Int`, `[Int -> Bool]`, `enum { ⋯ }`) are proper types,
+///  and so are all of the types inside them.
+pub fn proper_type_kind() -> WalkRule<SynthKind> {
+    cust_rc_box!(|parts| {
+        let subterms = parts.parts.map_reduce(
+            &|lwt| vec![lwt.term.clone()],
+            &|l, r| l.iter().chain(r.iter()).cloned().collect(), vec![]);
+        for sub in subterms {
+            if let Atom(_, _) = sub { continue; } // e.g. the names of `enum` arms
+            expect_kind(&walk::<SynthKind>(&sub, &parts)?, &star(), &sub)?;
+        }
+        Ok(star())
+    })
+}
+
+/// `forall T ⋯ . body`; parameters are proper types unless annotated (`forall F : k[* -> *]k .`)
+pub fn forall_type_kind() -> WalkRule<SynthKind> {
+    cust_rc_box!(|forall_parts| {
+        let mut body_env = forall_parts.env.clone();
+        for param_parts in forall_parts.march_parts(&[n("param")]) {
+            let kind = param_parts.maybe_get_term(n("param_kind")).map(Ty).unwrap_or_else(star);
+            body_env = body_env.set(ast_to_name(&param_parts.get_term(n("param"))), kind);
+        }
+        let body_kind = forall_parts.with_environment(body_env).get_res(n("body"))?;
+        expect_kind(&body_kind, &star(), &forall_parts.get_term(n("body")))?;
+        Ok(star())
+    })
+}
+
+/// `mu_type T ⋯ . body`; the parameters refer to names in the environment,
+///  which already have kinds, but they can be annotated, too (`mu_type F : k[* -> *]k .`).
+pub fn mu_type_kind() -> WalkRule<SynthKind> {
+    cust_rc_box!(|mu_parts| {
+        let mut body_env = mu_parts.env.clone();
+        for param_parts in mu_parts.march_parts(&[n("param")]) {
+            let kind_ast = match param_parts.maybe_get_term(n("param_kind")) {
+                Some(kind_ast) => kind_ast,
+                None => continue
+            };
+            let param = match param_parts.get_term(n("param")) {
+                ExtendEnv(ref param, _) => vr_to_name(param),
+                _ => panic!("ICE: ill-formed mu_type")
+            };
+            if let Some(env_kind) = mu_parts.env.find(&param) {
+                expect_kind(env_kind, &Ty(kind_ast.clone()), &kind_ast)?;
+            }
+            body_env = body_env.set(param, Ty(kind_ast));
+        }
+        mu_parts.with_environment(body_env).get_res(n("body"))
+    })
+}
+
+/// `rator <[ arg ⋯ ]<`; `rator` has to take arguments of the right kinds.
+pub fn type_apply_kind() -> WalkRule<SynthKind> {
+    cust_rc_box!(|tapp_parts| {
+        let rator_kind = tapp_parts.get_res(n("type_rator"))?;
+        let arg_kinds = tapp_parts.get_rep_res(n("arg"))?;
+        // Unknown type constructors (like `Expr`) are assumed to produce proper types:
+        if let VariableReference(_, _) = rator_kind.0 { return Ok(star()); }
+
+        let expected = higher_kind(arg_kinds.clone(), unknown_kind());
+        let higher_kind_form = ::core_forms::find("Kind", "higher_kind");
+        let rator_parts = match rator_kind.0.destructure(higher_kind_form) {
+            Some(ref rator_parts) if kinds_match(&rator_kind, &expected) => rator_parts.clone(),
+            _ => ty_err!(KindMismatch(rator_kind, higher_kind(arg_kinds, star()))
+                         at tapp_parts.get_term(n("type_rator")))
+        };
+        Ok(Ty(rator_parts.get_leaf_or_panic(&n("res")).clone()))
+    })
+}
+
+/// Type syntax whose kind we can't know until it's expanded (e.g. a type macro invocation).
+pub fn unknown_kind_rule() -> WalkRule<SynthKind> {
+    cust_rc_box!(|_parts| { Ok(unknown_kind()) })
+}
+
+#[test]
+fn basic_kind_synth() {
+    use runtime::core_values::core_types;
+    let env = core_types().set(n("List"), ty!({"Type" "forall_type" :
+        "param" => ["T"],
+        "body" => (import [* [forall "param"]] {"Type" "Int" :})}));
+
+    assert_eq!(synth_kind(&ast!({"Type" "Int" :}), &env), Ok(star()));
+    assert_eq!(synth_kind(&ast!({"Type" "fn" : "param" => [{"Type" "Int" :}],
+                                               "ret" => (vr "Bool")}), &env),
+               Ok(star()));
+    assert_eq!(synth_kind(&ast!((vr "List")), &env),
+               Ok(higher_kind(vec![star()], star())));
+    assert_eq!(synth_kind(&ast!({"Type" "type_apply" :
+                                    "type_rator" => (vr "List"), "arg" => [{"Type" "Int" :}]}),
+                          &env),
+               Ok(star()));
+
+    // `List` needs an argument:
+    assert_m!(expect_proper_type(&ast!({"Type" "fn" : "param" => [(vr "List")],
+                                                      "ret" => (vr "Bool")}), &env),
+              ty_err_p!(KindMismatch(_, _)));
+    // ...and `Int` can't take one:
+    assert_m!(synth_kind(&ast!({"Type" "type_apply" :
+                                   "type_rator" => {"Type" "Int" :}, "arg" => [(vr "Bool")]}),
+                         &env),
+              ty_err_p!(KindMismatch(_, _)));
+    // ...and `List` can't take two:
+    assert_m!(synth_kind(&ast!({"Type" "type_apply" :
+                                   "type_rator" => (vr "List"),
+                                   "arg" => [{"Type" "Int" :}, {"Type" "Int" :}]}),
+                         &env),
+              ty_err_p!(KindMismatch(_, _)));
+    // `Expr` (an abstract type) can take an argument:
+    assert_m!(expect_proper_type(&ast!({"Type" "type_apply" :
+                                           "type_rator" => (vr "Expr"),
+                                           "arg" => [{"Type" "Int" :}]}),
+                                 &env),
+              Ok(()));
+}
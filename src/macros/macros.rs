@@ -68,6 +68,10 @@ macro_rules! ebeta {
             ::beta::ExportBeta::ShadowAll(Box::new(sub), drivers)
         }
     };
+    ( [ [* $body:tt ] $( $rest:tt )*] ) => {
+        ::beta::ExportBeta::Shadow(Box::new(ebeta!([* $body])),
+               Box::new(ebeta!( [ $( $rest )* ] )))
+    };
     ( [ $name:tt $( $rest:tt )*] ) => {
         ::beta::ExportBeta::Shadow(Box::new(::beta::ExportBeta::Use(::name::n(expr_ify!($name)))),
                Box::new(ebeta!( [ $( $rest )* ] )))
@@ -78,7 +82,10 @@ macro_rules! ebeta {
 /* Read */
 
 macro_rules! tokens {
-    ($($contents:tt)*) => { TokenTree{t: vec![ $(  t_elt!($contents) ),* ] }}
+    ($($contents:tt)*) => {
+        TokenTree{t: vec![ $(  t_elt!($contents) ),* ],
+                  spans: vec![], end: ::util::err::Span::nowhere() }
+    }
 }
 
 macro_rules! t_elt {
@@ -125,7 +132,9 @@ macro_rules! ast {
             Shape(res)
         }
     };*/
-    ( (vr $var:expr) ) => { ::ast::VariableReference(::name::n($var)) };
+    ( (vr $var:expr) ) => {
+        ::ast::VariableReference(::name::n($var), ::util::err::Span::nowhere())
+    };
     ( (, $interpolate:expr)) => { $interpolate };
     // TODO: maybe we should use commas for consistency:
     ( ( $( $list:tt )* ) ) => { ast_shape!($($list)*)};
@@ -134,22 +143,23 @@ macro_rules! ast {
     };
     ( { $nt:tt $form:tt => $beta:tt : $($mbe_arg:tt)*} ) => {
         ::ast::Node(::core_forms::find_core_form($nt, $form), mbe!( $($mbe_arg)* ),
-                    ebeta!($beta))
+                    ebeta!($beta), ::util::err::Span::nowhere())
     };
     ( { $form:expr => $beta:tt ; $($mbe_arg:tt)*} ) => {
-        ::ast::Node($form, mbe!( $($mbe_arg)* ), ebeta!($beta))
+        ::ast::Node($form, mbe!( $($mbe_arg)* ), ebeta!($beta), ::util::err::Span::nowhere())
     };
     ( { $form:expr; [ $($mbe_arg:tt)* ] }) => {
         ast!( { $form ; $($mbe_arg)* } )
     };
     ( { $form:expr; $($mbe_arg:tt)* }) => {
-        ::ast::Node($form, mbe!( $($mbe_arg)* ), ::beta::ExportBeta::Nothing)
+        ::ast::Node($form, mbe!( $($mbe_arg)* ), ::beta::ExportBeta::Nothing,
+                    ::util::err::Span::nowhere())
     };
     ( { $nt:tt $form:tt : $($mbe_arg:tt)* }) => {
         ::ast::Node(::core_forms::find_core_form($nt, $form), mbe!( $($mbe_arg)* ),
-                    ::beta::ExportBeta::Nothing)
+                    ::beta::ExportBeta::Nothing, ::util::err::Span::nowhere())
     };
-    ($e:expr) => { ::ast::Atom(::name::n($e))}
+    ($e:expr) => { ::ast::Atom(::name::n($e), ::util::err::Span::nowhere())}
 }
 
 /* Ty */
@@ -302,6 +312,9 @@ macro_rules! form_pat {
     (at) => { ::grammar::FormPat::AnyToken };
     (aat) => { ::grammar::FormPat::AnyAtomicToken };
     (varref) => { ::grammar::FormPat::VarRef };
+    (int_lit) => { ::grammar::FormPat::IntLiteral };
+    (string_lit) => { ::grammar::FormPat::StringLiteral };
+    (char_lit) => { ::grammar::FormPat::CharLiteral };
     ((delim $n:expr, $d:expr, $body:tt)) => {
         ::grammar::FormPat::Delimited(::name::n($n), ::read::delim($d),
                           ::std::rc::Rc::new(form_pat!($body)))
@@ -355,6 +368,7 @@ macro_rules! basic_typed_form {
             name: ::name::n("unnamed form"),
             grammar: Rc::new(form_pat!($p)),
             type_compare: ::form::Positive(::ast_walk::WalkRule::NotWalked),
+            synth_kind: ::form::Positive(::ast_walk::WalkRule::NotWalked),
             synth_type: ::form::Positive($gen_type),
             quasiquote: ::form::Both(::ast_walk::WalkRule::LiteralLike,
                                      ::ast_walk::WalkRule::LiteralLike),
@@ -369,6 +383,7 @@ macro_rules! typed_form {
             name: ::name::n($name),
             grammar: Rc::new(form_pat!($p)),
             type_compare: ::form::Positive(::ast_walk::WalkRule::NotWalked),
+            synth_kind: ::form::Positive(::ast_walk::WalkRule::NotWalked),
             synth_type: ::form::Positive($gen_type),
             quasiquote: ::form::Both(::ast_walk::WalkRule::LiteralLike,
                                      ::ast_walk::WalkRule::LiteralLike),
@@ -383,6 +398,7 @@ macro_rules! negative_typed_form {
             name: ::name::n($name),
             grammar: Rc::new(form_pat!($p)),
             type_compare: ::form::Positive(::ast_walk::WalkRule::NotWalked),
+            synth_kind: ::form::Positive(::ast_walk::WalkRule::NotWalked),
             synth_type: ::form::Negative($gen_type),
             quasiquote: ::form::Both(::ast_walk::WalkRule::LiteralLike,
                                      ::ast_walk::WalkRule::LiteralLike),
@@ -418,7 +434,10 @@ macro_rules! val {
         ::runtime::eval::Value::Enum(::name::n($nm), vec![ $( val! $v ),* ])
     };
     (seq $($v:tt)*) => {
-        ::runtime::eval::Value::Sequence(vec![ $( Rc::new(val! $v) ),* ])
+        ::runtime::eval::Value::Sequence(vec![ $( ::std::rc::Rc::new(val! $v) ),* ])
+    };
+    (tuple $($v:tt)*) => {
+        ::runtime::eval::Value::Tuple(vec![ $( ::std::rc::Rc::new(val! $v) ),* ])
     };
     (, $interpolate:expr) => { $interpolate }
 }
@@ -462,14 +481,18 @@ macro_rules! tyf {
 }
 
 macro_rules! bind_patterns {
-    ( $iter:expr; () => $body:expr ) => { $body };
-    ( $iter:expr; ($p_car:pat, $($p_cdr:pat,)* ) => $body:expr ) => {
+    ( $iter:expr, $all:expr; () => $body:expr ) => { $body };
+    ( $iter:expr, $all:expr; ($p_car:pat, $($p_cdr:pat,)* ) => $body:expr ) => {
         match $iter.next() {
             Some($p_car) => {
-                bind_patterns!($iter; ($( $p_cdr, )*) => $body)
+                bind_patterns!($iter, $all; ($( $p_cdr, )*) => $body)
+            }
+            // Too few arguments, or the wrong kind (only possible without typechecking)
+            _ => {
+                return Err(::runtime::eval::rt_err(
+                    ::runtime::eval::RuntimeError::BuiltInMisuse($all.clone()),
+                    &::ast::Ast::Trivial));
             }
-            None => { panic!("ICE: too few arguments"); }
-            Some(ref other) => { panic!("Type ICE in argument: {:#?}", other); }
         }
     }
 }
@@ -477,9 +500,10 @@ macro_rules! bind_patterns {
 macro_rules! core_fn {
     ( $($p:pat),* => $body:expr ) => {
         BuiltInFunction(BIF(Rc::new(
-            move | args | {
-                let mut argi = args.into_iter();
-                bind_patterns!(argi; ($( $p, )*) => $body )
+            move | args: Vec<Value> | {
+                let mut argi = args.clone().into_iter();
+                let res: Value = bind_patterns!(argi, args; ($( $p, )*) => $body );
+                Ok(res)
             }
         )))
     }
@@ -508,7 +532,7 @@ macro_rules! without_freshening {
 macro_rules! expect_node {
     ( ($node:expr ; $form:expr) $env:ident ; $body:expr ) => (
         // This is tied to the signature of `Custom`
-        if let Node(ref f, ref $env, _) = $node {
+        if let Node(ref f, ref $env, _, _) = $node {
             if *f == $form {
                 $body
             } else {
@@ -526,7 +550,8 @@ macro_rules! expect_node {
 macro_rules! expect_ty_node {
     ( ($node:expr ; $form:expr ; $loc:expr) $env:ident ; $body:expr ) => ({
         // This is tied to the signature of `Custom`
-        let $env = $node.destructure($form, $loc)?;
+        // (If the type is being inferred, context should have determined it by now)
+        let $env = $node.known($loc)?.destructure($form, $loc)?;
         $body
     })
 }
@@ -98,7 +98,7 @@ macro_rules! Reifiable {
             fn ty() -> ::ast::Ast {
                 type_defn_wrapper!($(<$($ty_param_ty),*>)* => { "Type" "struct" :
                    "component_name" => [@"c" $(
-                       (, ::ast::Ast::Atom(::name::n(stringify!($field)))) ),* ],
+                       (, ast!(stringify!($field))) ),* ],
                    "component" =>
                    // TODO: unless we specify arguments with the same name as parameters,
                    //  we get bogus results
@@ -117,11 +117,12 @@ macro_rules! Reifiable {
                 let argument_list : Vec<&str> = vec![$( $( stringify!($ty_param_ty) ),* )*];
                 if argument_list.len() > 0 {
                     ast!({ "Type" "type_apply" :
-                        "type_rator" => (, ::ast::Ast::VariableReference( Self::ty_name() ) ),
+                        "type_rator" => (, ::ast::Ast::VariableReference(
+                            Self::ty_name(), ::util::err::Span::nowhere()) ),
                         "arg" => [ $( $( (, $ty_param_ty ::ty_invocation() ) ),* )* ]
                     })
                 } else {
-                    ::ast::Ast::VariableReference( Self::ty_name() )
+                    ::ast::Ast::VariableReference(Self::ty_name(), ::util::err::Span::nowhere())
                 }
             }
 
@@ -201,7 +202,7 @@ macro_rules! Reifiable {
             fn ty() -> ::ast::Ast {
                 type_defn_wrapper!($(<$($ty_param_ty),*>)* => { "Type" "enum" :
                     "name" => [@"c" $(
-                        (, ::ast::Ast::Atom(::name::n(stringify!($choice)))) ),* ],
+                        (, ast!(stringify!($choice))) ),* ],
                     "component" => [@"c" $( [ $($(
                         (, <$part as ::runtime::reify::Reifiable>::ty_invocation() )
                     ),*)*]),*]
@@ -215,11 +216,12 @@ macro_rules! Reifiable {
                 let argument_list : Vec<&str> = vec![$( $( stringify!($ty_param_ty) ),* )*];
                 if argument_list.len() > 0 {
                     ast!({ "Type" "type_apply" :
-                        "type_rator" => (, ::ast::Ast::VariableReference( Self::ty_name() ) ),
+                        "type_rator" => (, ::ast::Ast::VariableReference(
+                            Self::ty_name(), ::util::err::Span::nowhere()) ),
                         "arg" => [ $( $( (, $ty_param_ty ::ty_invocation() ) ),* )* ]
                     })
                 } else {
-                    ::ast::Ast::VariableReference( Self::ty_name() )
+                    ::ast::Ast::VariableReference(Self::ty_name(), ::util::err::Span::nowhere())
                 }
             }
 
@@ -317,11 +319,12 @@ macro_rules! type_defn_wrapper {
         // All types will be μ. I think this is the way things work in most languages.
         ast!({"Type" "forall_type" :
             "param" => [ $($(
-                (, ::ast::Ast::Atom(::name::n(stringify!($ty_param_ty))))
+                (, ast!(stringify!($ty_param_ty)))
             ),*)*],
             "body" => (import [* [forall "param"]] {"Type" "mu_type" :
                  "param" => [(import [prot "param"]
-                              (, ::ast::Ast::VariableReference(Self::ty_name())))],
+                              (, ::ast::Ast::VariableReference(
+                                  Self::ty_name(), ::util::err::Span::nowhere())))],
                  "body" => (import [* [prot "param"]] $body)
              })
         })
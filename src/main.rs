@@ -47,7 +47,9 @@ mod form;
 mod ast_walk;
 mod walk_mode;
 mod ty;
+mod kind;
 mod ty_compare;
+mod exhaustiveness;
 
 mod runtime;
 
@@ -68,6 +70,18 @@ use std::borrow::Cow;
 thread_local! {
     pub static ty_env : RefCell<Assoc<Name, Ty>> = RefCell::new(core_values::core_types());
     pub static val_env : RefCell<Assoc<Name, Value>> = RefCell::new(core_values::core_values());
+
+    /// Typechecked-and-expanded programs (and their rendered warnings),
+    ///  keyed by filename and source text.
+    /// (Only valid for the current `ty_env`, so changing that clears this.)
+    pub static expansion_cache
+        : RefCell<std::collections::HashMap<(String, String), (ast::Ast, String)>>
+        = RefCell::new(std::collections::HashMap::new());
+    /// Print every macro expansion (to stderr) when running a program?
+    pub static trace_expansions : RefCell<bool> = RefCell::new(false);
+    /// Typecheck the output of macro expansion, too?
+    /// Macros aren't supposed to be able to generate ill-typed code, so this is a sanity check.
+    pub static check_expansions : RefCell<bool> = RefCell::new(false);
 }
 
 struct LineHelper { highlighter: rustyline::highlight::MatchingBracketHighlighter }
@@ -139,6 +153,8 @@ fn main() {
         let just_type = regex::Regex::new("^:t (.*)$").unwrap();
         let just_eval = regex::Regex::new("^:e (.*)$").unwrap();
         let canon_type = regex::Regex::new("^:tt (.*)$").unwrap();
+        let trace_expansion = regex::Regex::new("^:x (.*)$").unwrap();
+        let step_expansion = regex::Regex::new("^:xs (.*)$").unwrap();
         let assign_value = regex::Regex::new("^(\\w+)\\s*:=(.*)$").unwrap();
         let save_value = regex::Regex::new("^:s +((\\w+)\\s*:=(.*))$").unwrap();
         let assign_type = regex::Regex::new("^(\\w+)\\s*t=(.*)$").unwrap();
@@ -156,6 +172,8 @@ fn main() {
         println!("    `:s <name> := <expr>` to save a binding to the prelude for the future.");
         println!("    `:s <name> t= <expr>` to save a type binding to the prelude.");
         println!("    `:p <expr>` to parse `<expr>` and print its debug AST output.");
+        println!("    `:x <expr>` to show all the macro expansions in `<expr>`.");
+        println!("    `:xs <expr>` to step through macro expansion of `<expr>`, layer by layer.");
         println!("    Command history is saved over sessions.");
         println!("    Tab-completion works on variables, and lots of Bash-isms work.");
         println!();
@@ -191,6 +209,24 @@ fn main() {
             } else if let Some(caps) = just_eval.captures(&line) {
                 eval_unseemly_program_without_typechecking(&caps[1])
                     .map(|x| format!("{}", x))
+            } else if let Some(caps) = trace_expansion.captures(&line) {
+                trace_unseemly_program(&caps[1])
+            } else if let Some(caps) = step_expansion.captures(&line) {
+                expansion_layers(&caps[1]).map(|layers| {
+                    let layer_count = layers.len();
+                    for (i, (steps, expanded)) in layers.into_iter().enumerate() {
+                        for step in steps { println!("{}", step); }
+                        println!("\x1b[1;34m{}/{}\x1b[0m {}", i+1, layer_count, expanded);
+                        if i+1 < layer_count {
+                            match rl.readline("    [enter to continue, `q` to stop] ") {
+                                Ok(ref l) if l.trim() == "q" => break,
+                                Ok(_) => {}
+                                Err(_) => break
+                            }
+                        }
+                    }
+                    format!("[{} layers of expansion]", layer_count)
+                })
             } else if let Some(caps) = canon_type.captures(&line) {
                 canonicalize_type(&caps[1]).map(|x| format!("{}", x))
             } else if let Some(caps) = assign_value.captures(&line) {
@@ -226,12 +262,32 @@ fn main() {
 
             match result_display {
                 Ok(v) => println!("\x1b[1;32m≉\x1b[0m {}", v),
-                Err(s) => println!("\x1b[1;31m✘\x1b[0m {}", s)
+                Err(s) => println!("\x1b[1;31m✘\x1b[0m {}", s.trim_end())
             }
         }
         rl.save_history(&history_filename).unwrap();
     } else {
-        let filename = &arguments[1];
+        // `--check-expansions` re-typechecks the program after macro expansion.
+        // `--trace-expansions` prints each macro expansion as it happens.
+        let usage = format!("Usage: {} [--check-expansions] [--trace-expansions] <file>",
+                            arguments[0]);
+        let (flags, files) : (Vec<&String>, Vec<&String>)
+            = arguments[1..].iter().partition(|a| a.starts_with("--"));
+        for flag in flags {
+            match flag.as_str() {
+                "--check-expansions" => check_expansions.with(|c| *c.borrow_mut() = true),
+                "--trace-expansions" => trace_expansions.with(|t| *t.borrow_mut() = true),
+                _ => {
+                    eprintln!("Unknown flag: {}", flag);
+                    eprintln!("{}", usage);
+                    std::process::exit(1);
+                }
+            }
+        }
+        let filename = match files.first() {
+            Some(filename) => filename,
+            None => { eprintln!("{}", usage); std::process::exit(1); }
+        };
 
         let mut raw_input = String::new();
         File::open(&Path::new(filename))
@@ -239,15 +295,21 @@ fn main() {
             .read_to_string(&mut raw_input)
             .expect("Error reading file");
 
-        let result = eval_unseemly_program(&raw_input);
+        let result = eval_unseemly_file(filename, &raw_input);
 
         match result {
             Ok(v) => println!("{}", v),
-            Err(e) => println!("\x1b[1;31m✘\x1b[0m {:#?}", e)
+            Err(e) => println!("{}", e.trim_end())
         }
     }
 }
 
+/// Only use ANSI colors in error messages if someone is looking at them in a terminal.
+fn color_errors() -> bool {
+    use std::io::IsTerminal;
+    std::io::stdout().is_terminal()
+}
+
 fn assign_variable(name: &str, expr: &str) -> Result<Value, String> {
     let res = eval_unseemly_program(expr);
 
@@ -260,7 +322,8 @@ fn assign_variable(name: &str, expr: &str) -> Result<Value, String> {
                 *tys.borrow_mut() = new_tys;
                 *vals.borrow_mut() = new_vals;
             })
-        })
+        });
+        expansion_cache.with(|cache| cache.borrow_mut().clear());
     }
     res
 }
@@ -269,17 +332,20 @@ fn assign_t_var(name: &str, t: &str) -> Result<ty::Ty, String> {
     let tokens = try!(read::read_tokens(t));
 
     let ast = try!(grammar::parse(&grammar::FormPat::Call(n("Type")),
-                                &core_forms::get_core_forms(), &tokens).map_err(|e| e.msg));
+                                &core_forms::get_core_forms(), &tokens)
+                      .map_err(|e| e.render(n("[input]"), t, color_errors())));
 
     let res = ty_env.with(|tys| {
-        ty::synth_type(&ast, tys.borrow().clone()).map_err(|e| format!("{:#?}", e))
+        ty::synth_type(&ast, tys.borrow().clone())
+            .map_err(|e| e.render(n("[input]"), t, color_errors()))
     });
 
     if let Ok(ref t) = res {
         ty_env.with(|tys| {
             let new_tys = tys.borrow().set(n(name), t.clone());
             *tys.borrow_mut() = new_tys;
-        })
+        });
+        expansion_cache.with(|cache| cache.borrow_mut().clear());
     }
 
     res
@@ -289,10 +355,12 @@ fn canonicalize_type(t: &str) -> Result<ty::Ty, String> {
     let tokens = try!(read::read_tokens(t));
 
     let ast = try!(grammar::parse(&grammar::FormPat::Call(n("Type")),
-                                &core_forms::get_core_forms(), &tokens).map_err(|e| e.msg));
+                                &core_forms::get_core_forms(), &tokens)
+                      .map_err(|e| e.render(n("[input]"), t, color_errors())));
 
     ty_env.with(|tys| {
-        ty::synth_type(&ast, tys.borrow().clone()).map_err(|e| format!("{:#?}", e))
+        ty::synth_type(&ast, tys.borrow().clone())
+            .map_err(|e| e.render(n("[input]"), t, color_errors()))
     })
 }
 
@@ -301,51 +369,185 @@ fn parse_unseemly_program(program: &str) -> Result<String, String> {
 
     let ast = try!(
         grammar::parse(&core_forms::outermost_form(), &core_forms::get_core_forms(), &tokens)
-            .map_err(|e| e.msg));
+            .map_err(|e| e.render(n("[input]"), program, color_errors())));
 
     Ok(format!("▵ {:#?}\n∴ {}\n", ast, ast))
 }
 
 fn type_unseemly_program(program: &str) -> Result<ty::Ty, String> {
-    let tokens = try!(read::read_tokens(program));
+    let tokens = read::read_tokens(program)?;
 
 
-    let ast = try!(
+    let ast =
         grammar::parse(&core_forms::outermost_form(), &core_forms::get_core_forms(), &tokens)
-            .map_err(|e| e.msg));
+            .map_err(|e| e.render(n("[input]"), program, color_errors()))?;
 
-    ty_env.with(|tys| {
-        ty::synth_type(&ast, tys.borrow().clone()).map_err(|e| format!("{:#?}", e))
+    let res = ty_env.with(|tys| {
+        let tys = tys.borrow();
+        ty::synth_expr_type_all(&ast, tys.clone(), type_names(&tys))
+            .map_err(|es| render_all(&es, n("[input]"), program))
+    });
+    report_warnings(program);
+    res
+}
+
+/// The names that `tys` binds to types, which have no values (e.g. `Bool`).
+fn type_names(tys: &Assoc<Name, ty::Ty>) -> Assoc<Name, ty::Ty> {
+    val_env.with(|vals| {
+        let vals = vals.borrow();
+        let mut res = Assoc::new();
+        for name in tys.iter_keys().filter(|name| vals.find(name).is_none()) {
+            res = res.set(name, tys.find_or_panic(&name).clone());
+        }
+        res
     })
 }
 
+/// Render every one of `errs` (which come from `program`, the text of `file`).
+fn render_all(errs: &[ty::TypeError], file: Name, program: &str) -> String {
+    errs.iter().map(|e| e.render(file, program, color_errors())).collect::<Vec<_>>().join("")
+}
+
+/// Print (to stderr) any warnings that typechecking `program` produced.
+fn report_warnings(program: &str) {
+    eprint!("{}", render_warnings(n("[input]"), program));
+}
+
+/// Render (and forget) any warnings that typechecking `program` (the text of `file`) produced.
+fn render_warnings(file: Name, program: &str) -> String {
+    ty::take_warnings().iter().map(|w| w.render(file, program, color_errors())).collect()
+}
+
 fn eval_unseemly_program_without_typechecking(program: &str) -> Result<Value, String> {
-    let tokens = try!(read::read_tokens(program));
+    let tokens = read::read_tokens(program)?;
 
-    let ast : ::ast::Ast = try!(
+    let ast : ::ast::Ast =
         grammar::parse(&core_forms::outermost_form(), &core_forms::get_core_forms(), &tokens)
-            .map_err(|e| e.msg));
+            .map_err(|e| e.render(n("[input]"), program, color_errors()))?;
+
+    // (Expansion doesn't need types, so we can still do it)
+    let ast = core_macro_forms::expand(&ast, Assoc::new())
+        .map_err(|e| e.render(n("[input]"), program, color_errors()))?;
 
     val_env.with(|vals| {
-        eval(&ast, vals.borrow().clone()).map_err(|_| "???".to_string())
+        eval(&ast, vals.borrow().clone())
+            .map_err(|e| e.render(n("[input]"), program, color_errors()))
     })
 }
 
+/// Read, parse, and typecheck `program` (reporting any warnings).
+fn parse_and_typecheck_file(filename: &str, program: &str) -> Result<ast::Ast, String> {
+    let (ast, warnings) = parse_and_typecheck_file_quietly(filename, program)?;
+    eprint!("{}", warnings);
+    Ok(ast)
+}
 
-fn eval_unseemly_program(program: &str) -> Result<Value, String> {
-    let tokens = try!(read::read_tokens(program));
+/// Read, parse, and typecheck `program`, returning the rendered warnings instead of printing them.
+fn parse_and_typecheck_file_quietly(filename: &str, program: &str)
+        -> Result<(ast::Ast, String), String> {
+    let tokens = read::read_tokens_from(n(filename), program)?;
 
-    let ast : ::ast::Ast = try!(
+    let ast : ::ast::Ast =
         grammar::parse(&core_forms::outermost_form(), &core_forms::get_core_forms(), &tokens)
-            .map_err(|e| e.msg));
+            .map_err(|e| e.render(n(filename), program, color_errors()))?;
+
+    let _type = ty_env.with(|tys| {
+        let tys = tys.borrow();
+        ty::synth_expr_type_all(&ast, tys.clone(), type_names(&tys))
+            .map_err(|es| render_all(&es, n(filename), program))
+    })?;
+    Ok((ast, render_warnings(n(filename), program)))
+}
+
+/// Typecheck `program`, then expand it one layer of macros at a time.
+/// Each layer is the macro expansions that happened, and the result of doing them.
+fn expansion_layers(program: &str)
+        -> Result<Vec<(Vec<core_macro_forms::ExpansionStep>, ast::Ast)>, String> {
+    let mut ast = parse_and_typecheck_file("[input]", program)?;
+    let mut layers: Vec<(Vec<core_macro_forms::ExpansionStep>, ast::Ast)> = vec![];
+    loop {
+        let (expanded, steps) = core_macro_forms::expand_one_layer(&ast)
+            .map_err(|e| e.render(n("[input]"), program, color_errors()))?;
+        if steps.is_empty() {
+            // Nothing is left to expand, so the helpers that survived stepping can go away:
+            if let Some(last) = layers.last_mut() {
+                last.1 = core_macro_forms::expand(&ast, Assoc::new())
+                    .map_err(|e| e.render(n("[input]"), program, color_errors()))?;
+            }
+            return Ok(layers);
+        }
+        layers.push((steps, expanded.clone()));
+        ast = expanded;
+    }
+}
+
+/// Typecheck and expand `program`, and show every macro expansion that happened.
+fn trace_unseemly_program(program: &str) -> Result<String, String> {
+    let ast = parse_and_typecheck_file("[input]", program)?;
+    let (expanded, trace) = core_macro_forms::expand_traced(&ast)
+        .map_err(|e| e.render(n("[input]"), program, color_errors()))?;
+    let mut res = String::new();
+    for step in trace { res.push_str(&format!("{}\n", step)); }
+    res.push_str(&format!("∴ {}", expanded));
+    Ok(res)
+}
+
+/// The front half of the pipeline: read → parse → typecheck → expand.
+/// The result is cached, so this is cheap to do repeatedly.
+fn expand_unseemly_file(filename: &str, program: &str) -> Result<ast::Ast, String> {
+    let trace = trace_expansions.with(|t| *t.borrow());
+    let check = check_expansions.with(|c| *c.borrow());
+    // Tracing and checking happen during expansion, so a cached result would skip them:
+    let use_cache = !trace && !check;
+
+    let key = (filename.to_string(), program.to_string());
+    if use_cache {
+        if let Some((expanded, warnings))
+                = expansion_cache.with(|cache| cache.borrow().get(&key).cloned()) {
+            eprint!("{}", warnings);
+            return Ok(expanded);
+        }
+    }
+
+    let (ast, warnings) = parse_and_typecheck_file_quietly(filename, program)?;
+    eprint!("{}", warnings);
 
-    let _type = try!(ty_env.with(|tys| {
-        ty::synth_type(&ast, tys.borrow().clone()).map_err(|e| format!("{:#?}", e))
-    }));
+    let expanded = if trace {
+        let (expanded, trace) = core_macro_forms::expand_traced(&ast)
+            .map_err(|e| e.render(n(filename), program, color_errors()))?;
+        for step in trace { eprintln!("{}", step); }
+        expanded
+    } else {
+        core_macro_forms::expand(&ast, Assoc::new())
+            .map_err(|e| e.render(n(filename), program, color_errors()))?
+    };
+
+    if check {
+        ty_env.with(|tys| {
+            ty::synth_type(&expanded, tys.borrow().clone()).map_err(|e| format!(
+                "ICE: macro expansion produced ill-typed code:\n{}\n{}",
+                expanded, e.render(n(filename), program, color_errors())))
+        })?;
+        ty::take_warnings(); // (we already reported these)
+    }
 
+    if use_cache {
+        expansion_cache.with(|cache| cache.borrow_mut().insert(key, (expanded.clone(), warnings)));
+    }
+    Ok(expanded)
+}
+
+fn eval_unseemly_program(program: &str) -> Result<Value, String> {
+    eval_unseemly_file("[input]", program)
+}
+
+/// Like `eval_unseemly_program`, but errors will mention `filename`
+fn eval_unseemly_file(filename: &str, program: &str) -> Result<Value, String> {
+    let ast = expand_unseemly_file(filename, program)?;
 
     val_env.with(|vals| {
-        eval(&ast, vals.borrow().clone()).map_err(|_| "???".to_string())
+        eval(&ast, vals.borrow().clone())
+            .map_err(|e| e.render(n(filename), program, color_errors()))
     })
 }
 
@@ -367,6 +569,238 @@ fn simple_end_to_end_eval() {
         Ok(val!(i 120)));
 }
 
+#[test]
+fn end_to_end_int_literals() {
+    assert_eq!(eval_unseemly_program("(plus 40 2)"), Ok(val!(i 42)));
+    assert_eq!(eval_unseemly_program("(times -0x10 -3)"), Ok(val!(i 48)));
+    assert_eq!(eval_unseemly_program("(plus 123456789123456789 1)"),
+               Ok(Value::Int("123456789123456790".parse().unwrap())));
+
+    assert_eq!(eval_unseemly_program(
+        "((fix .[ again : [ -> [ Int -> Int ]] .
+            .[ n : Int .
+                match n {
+                    0 => 1
+                    -1 => 0
+                    other => (times n ((again) (minus n 1))) } ]. ].) 6)"),
+        Ok(val!(i 720)));
+    assert_eq!(eval_unseemly_program("match (plus 2 2) { 4 => true  x => false }"),
+               Ok(val!(b true)));
+
+    assert_m!(type_unseemly_program("match true { 1 => one }"),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+}
+
+
+#[test]
+fn end_to_end_strings() {
+    assert_eq!(eval_unseemly_program(r#"(string_concat "Hello, " "world (and \"friends\")")"#),
+               Ok(Value::Text("Hello, world (and \"friends\")".to_string())));
+    assert_eq!(eval_unseemly_program(r#"(string_length "≉\n")"#), Ok(val!(i 2)));
+    assert_eq!(eval_unseemly_program(r#"(substring "unseemly" 2 6)"#),
+               Ok(Value::Text("seem".to_string())));
+    assert_eq!(eval_unseemly_program(r#"(string_to_int (int_to_string -0x10))"#),
+               Ok(val!(i -16)));
+    assert_eq!(eval_unseemly_program(r#"(string_less? "a" (char_to_string 'b'))"#),
+               Ok(val!(b true)));
+    assert_eq!(eval_unseemly_program(r#"(string_equal? "a b" "a  b")"#), Ok(val!(b false)));
+    assert_eq!(eval_unseemly_program(r#"(string_split "a,b,,c" ",")"#),
+               Ok(Value::Sequence(["a", "b", "", "c"].iter()
+                   .map(|s| ::std::rc::Rc::new(Value::Text(s.to_string()))).collect())));
+    assert_eq!(eval_unseemly_program("' '"), Ok(Value::Char(' ')));
+
+    assert_eq!(format!("{}", eval_unseemly_program(r#""tab\there""#).unwrap()),
+               r#""tab\there""#);
+    assert_m!(type_unseemly_program(r#"(plus "1" 2)"#),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+    assert_m!(eval_unseemly_program(r#"(substring "abc" 2 9)"#),
+              Err(ref e), e.starts_with("error[BuiltInMisuse]"));
+}
+
+#[test]
+fn end_to_end_let_and_letrec() {
+    assert_eq!(eval_unseemly_program("let x = 3  y = (plus 1 1) in (times x y)"),
+               Ok(val!(i 6)));
+    // Definitions are parallel, so the inner `x` is the outer one:
+    assert_eq!(eval_unseemly_program("let x = 3 in let x = (plus x 1)  y = x in (minus x y)"),
+               Ok(val!(i 1)));
+    // No annotation needed, even for functions:
+    assert_eq!(eval_unseemly_program("let f = .[n : Int . (plus n 1)]. in (f (f 1))"),
+               Ok(val!(i 3)));
+    assert_eq!(eval_unseemly_program(
+        "let +[Some x]+ = +[Some 5]+ : enum { Some (Int) None () } in x"),
+        Ok(val!(i 5)));
+    assert_m!(type_unseemly_program("let x = true in (plus x 1)"),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+
+    assert_eq!(eval_unseemly_program(
+        "letrec fact : [Int -> Int] =
+            .[n : Int . match n { 0 => 1  other => (times n (fact (minus n 1))) } ].
+         in (fact 5)"),
+        Ok(val!(i 120)));
+    assert_eq!(eval_unseemly_program(
+        "letrec
+            even? : [Int -> Bool] =
+                .[n : Int . match n { 0 => true  other => (odd? (minus n 1)) } ].
+            odd? : [Int -> Bool] =
+                .[n : Int . match n { 0 => false  other => (even? (minus n 1)) } ].
+         in (odd? 7)"),
+        Ok(val!(b true)));
+    // Closures that escape still know about each other:
+    assert_eq!(eval_unseemly_program(
+        "(letrec count : [Int -> Int] =
+                .[n : Int . match n { 0 => 0  other => (plus 1 (count (minus n 1))) } ].
+          in count
+          4)"),
+        Ok(val!(i 4)));
+    assert_m!(type_unseemly_program(
+        "letrec f : [Int -> Int] = .[n : Int . (f true)]. in (f 1)"),
+        Err(ref e), e.starts_with("error[Mismatch]"));
+    assert_m!(type_unseemly_program("letrec x : Int = 5 in x"),
+              Err(ref e), e.starts_with("error[UnableToDestructure]"));
+}
+
+#[test]
+fn end_to_end_struct_projection_and_update() {
+    assert_eq!(eval_unseemly_program("*[ x : 1  y : 2 ]* . y"), Ok(val!(i 2)));
+    assert_eq!(eval_unseemly_program(
+        "let p = *[ x : 1  y : 2 ]* in
+         let q = *[ p with x : 10 ]* in (plus (plus p . x q . x) q . y)"),
+        Ok(val!(i 13)));
+    assert_eq!(eval_unseemly_program(
+        "*[ *[ outer : *[ inner : 5 ]* ]* . outer with inner : 6  ]* . inner"),
+        Ok(val!(i 6)));
+    assert_m!(type_unseemly_program(".[p : struct { x : Int } . p . z]."),
+              Err(ref e), e.starts_with("error[NonexistentStructField]"));
+    assert_m!(type_unseemly_program("*[ *[ x : 1 ]* with x : true ]*"),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+    assert_m!(type_unseemly_program("*[ *[ x : 1 ]* with y : 1 ]*"),
+              Err(ref e), e.starts_with("error[NonexistentStructField]"));
+    assert_m!(eval_unseemly_program_without_typechecking("*[ x : 1 ]* . y"),
+              Err(ref e), e.starts_with("error[NoSuchField]"));
+    // Prefix forms extend as far to the right as possible:
+    assert_eq!(eval_unseemly_program("let p = *[ x : 1 ]* in p . x"), Ok(val!(i 1)));
+}
+
+#[test]
+fn end_to_end_tuples() {
+    assert_eq!(eval_unseemly_program(
+        "let divmod = .[a : Int  b : Int . **[ (zero? b) a (minus a b) ]** ].
+         in match (divmod 7 2) { **[ _z q r ]** => (plus q r) }"),
+        Ok(val!(i 12)));
+    assert_eq!(format!("{}", eval_unseemly_program("**[ 1 **[ \"a\" ]** **[]** ]**").unwrap()),
+               "**[1 **[\"a\"]** **[]**]**");
+    assert_eq!(type_unseemly_program("**[ 1 .[x : Int . x]. ]**").map(|t| format!("{}", t)),
+               Ok("**[Int [Int -> Int]]**".to_string()));
+    assert_m!(type_unseemly_program("match **[ 1 2 ]** { **[ a ]** => a }"),
+              Err(ref e), e.starts_with("error[LengthMismatch]"));
+    assert_m!(type_unseemly_program("(.[t : **[Int Int]** . t]. **[ 1 2 3 ]**)"),
+              Err(ref e), e.starts_with("error[LengthMismatch]"));
+    assert_m!(type_unseemly_program("match 1 { **[ a ]** => a }"),
+              Err(ref e), e.starts_with("error[UnableToDestructure]"));
+}
+
+#[test]
+fn end_to_end_fancy_patterns() {
+    assert_eq!(eval_unseemly_program("match **[1 2]** { **[_ x]** => x }"), Ok(val!(i 2)));
+    assert_eq!(eval_unseemly_program(
+        r#"match "b" { "a" => 'a'  "b" => 'b'  _ => '?' }"#), Ok(Value::Char('b')));
+    assert_eq!(eval_unseemly_program(
+        r#"match 'x' { 'y' => "why"  _ => "not y" }"#), Ok(Value::Text("not y".to_string())));
+    assert_m!(type_unseemly_program(r#"match 1 { "a" => 1  _ => 2 }"#),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+
+    // Or-patterns:
+    assert_eq!(eval_unseemly_program(
+        "match **[0 7]** { **[0 x]** | **[x 0]** => x  _ => -1 }"), Ok(val!(i 7)));
+    assert_eq!(eval_unseemly_program(
+        "match **[7 0]** { **[0 x]** | **[x 0]** => x  _ => -1 }"), Ok(val!(i 7)));
+    assert_eq!(eval_unseemly_program(
+        "match **[7 7]** { **[0 x]** | **[x 0]** => x  _ => -1 }"), Ok(val!(i -1)));
+    assert_eq!(eval_unseemly_program( // nested, and more than two alternatives:
+        "match +[Some 2]+ : enum { Some (Int) None () } {
+             +[Some 0 | 1 | 2]+ => true
+             _ => false }"),
+        Ok(val!(b true)));
+    assert_m!(type_unseemly_program("match **[1 2]** { **[0 x]** | **[y 0]** => 0 }"),
+              Err(ref e), e.starts_with("error[OrPatBindingMismatch]: `x` isn't bound"));
+    assert_m!(type_unseemly_program("match **[1 true]** { **[0 x]** | **[x _]** => 0 }"),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+
+    // Guards:
+    assert_eq!(eval_unseemly_program(
+        "match 5 { x if (zero? x) => 0  x if (equal? x 5) => 55  _ => 1 }"), Ok(val!(i 55)));
+    assert_eq!(eval_unseemly_program(
+        "match +[Some 3]+ : enum { Some (Int) None () } {
+             +[Some x]+ if (zero? x) => 0
+             +[Some x]+ => x
+             +[None]+ => -1 }"),
+        Ok(val!(i 3)));
+    assert_m!(type_unseemly_program("match 5 { x if (plus x 1) => 0  _ => 1 }"),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+
+    // Only the pattern failing means "try the next arm"; failures inside the arm are errors:
+    let none = "(+[None]+ : enum { Some (Int) None () })";
+    assert_m!(eval_unseemly_program(
+        &format!("match one {{ x => let +[Some y]+ = {} in y  _ => 99 }}", none)),
+        Err(ref e), e.starts_with("error[MatchFailure]"));
+    assert_m!(eval_unseemly_program(
+        &format!("match one {{ x if let +[Some y]+ = {} in true => 0  _ => 99 }}", none)),
+        Err(ref e), e.starts_with("error[MatchFailure]"));
+}
+
+#[test]
+fn end_to_end_exhaustiveness() {
+    // Just the warnings from typechecking `program`:
+    fn warnings_for(program: &str) -> Vec<String> {
+        use util::err::Diagnostic;
+        let tokens = read::read_tokens(program).unwrap();
+        let ast = grammar::parse(&core_forms::outermost_form(), &core_forms::get_core_forms(),
+                                 &tokens).unwrap();
+        ty::take_warnings();
+        let _ = ty_env.with(|tys| ty::synth_type(&ast, tys.borrow().clone()));
+        ty::take_warnings().iter()
+            .map(|w| format!("{} at {}", w.body.kind(), w.loc.span())).collect()
+    }
+
+    assert_m!(type_unseemly_program("match (zero? 1) { +[True]+ => 1 }"), Err(ref e),
+              e.starts_with("error[NonExhaustiveMatch]: non-exhaustive match of \
+                             `enum {True () False ()}`: missing `+[False]+`"));
+    // Guarded arms might not apply:
+    assert_m!(type_unseemly_program(
+                  "match (zero? 1) { +[True]+ => 1  +[False]+ if (zero? 2) => 0 }"),
+              Err(ref e), e.contains("missing `+[False]+`"));
+    assert_m!(type_unseemly_program("match 5 { 0 => 1  1 => 2 }"), Err(ref e),
+              e.contains("missing `_`"));
+    assert_m!(type_unseemly_program(
+                  "match **[(zero? 1) (zero? 2)]** {
+                       **[+[True]+ _]** => 1  **[_ +[True]+]** => 2 }"),
+              Err(ref e), e.contains("missing `**[+[False]+ +[False]+]**`"));
+    // Nested inside `unfold`ed `mu` types:
+    assert_m!(type_unseemly_program(
+                  "let_type IntList = mu_type IntList . enum { Nil () Cons (Int IntList) } in
+                   .[l : IntList . match unfold l { +[Cons a b]+ => a } ]."),
+              Err(ref e), e.contains("missing `+[Nil]+`"));
+    assert_m!(type_unseemly_program(
+                  "match *[a : (zero? 1) b : 5]* { *[a : +[True]+]* => 1  *[b : 0]* => 2 }"),
+              Err(ref e), e.contains("missing `*[a: +[False]+ b: _ ]*`"));
+
+    assert_m!(type_unseemly_program(
+                  "match (zero? 1) { +[True]+ => 1  +[False]+ | +[True]+ => 2 }"), Ok(_));
+    assert_m!(type_unseemly_program(
+                  "match **[(zero? 1) (zero? 2)]** {
+                       **[+[True]+ _]** => 1  **[_ +[True]+]** => 2  **[_ _]** => 3 }"),
+              Ok(_));
+
+    assert_eq!(warnings_for("match (zero? 1) { +[True]+ => 1  +[False]+ => 2 }"),
+               Vec::<String>::new());
+    assert_eq!(warnings_for("match (zero? 1) { x => 1  +[False]+ => 2 }"),
+               vec!["UnreachableArm at [input]:1:27".to_string()]);
+    assert_eq!(warnings_for(
+                   "match 5 { x if (zero? x) => 0  0 => 1  x => 2  0 => 3 }"),
+               vec!["UnreachableArm at [input]:1:48".to_string()]);
+}
+
 
 #[test]
 fn end_to_end_int_list_tools() {
@@ -579,3 +1013,672 @@ fn end_to_end_quotation_advanced() {
     }
 
 }
+
+#[test]
+fn end_to_end_extend_syntax() {
+    let cond_macro =
+        "forall T . '{ [{lit if}   cond = ,{Expr <[Bool]<},
+                       {lit then} then_e = ,{Expr <[T]<},
+                       {lit else} else_e = ,{Expr <[T]<},] }'
+         conditional -> .{ '[Expr | match ,[Expr | cond], {
+                                        +[True]+ => ,[Expr | then_e],
+                                        +[False]+ => ,[Expr | else_e], } ]' }.";
+
+    assert_eq!(eval_unseemly_program(
+                   &format!("extend_syntax {} in if (zero? 0) then 1 else 2", cond_macro)),
+               Ok(val!(i 1)));
+    assert_eq!(eval_unseemly_program(
+                   &format!("extend_syntax {} in if (zero? 5) then 1 else 2", cond_macro)),
+               Ok(val!(i 2)));
+    // The macro can be used more than once, and nested:
+    assert_eq!(eval_unseemly_program(
+                   &format!("extend_syntax {} in
+                                 if (zero? 5) then 1 else if true then 3 else 4", cond_macro)),
+               Ok(val!(i 3)));
+    // Invocations are typechecked against the macro's type:
+    assert_m!(type_unseemly_program(
+                  &format!("extend_syntax {} in if 0 then 1 else 2", cond_macro)),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:6:83\n"));
+    assert_m!(type_unseemly_program(
+                  &format!("extend_syntax {} in if true then 1 else false", cond_macro)),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:6:83\n"));
+    // Outside of the body, the syntax isn't there:
+    assert_m!(type_unseemly_program(
+                  &format!("(plus extend_syntax {} in 1  if true then 1 else 2)", cond_macro)),
+              Err(ref e), e.starts_with("error[LengthMismatch]"));
+
+    assert_m!(eval_unseemly_file("simple_macro.≉", include_str!("examples/simple_macro.≉")),
+              Ok(_));
+    // A literal isn't a binder, so it doesn't get freshened out from under its invocations:
+    assert_eq!(eval_unseemly_program(
+                   "extend_syntax forall . '{ [{lit zap}] }' zap -> .{ '[Expr | 5]' }. in zap"),
+               Ok(val!(i 5)));
+    // An implementation has to produce syntax...
+    let zap_five = "extend_syntax forall . '{ [{lit zap}] }' zap -> .{ 5 }. in zap";
+    assert_m!(type_unseemly_program(zap_five), Err(ref e),
+              e.starts_with("error[UnableToDestructure]") && e.contains(" --> [input]:1:52\n"));
+    // ...and if it wasn't typechecked, expansion complains about the invocation:
+    assert_m!(eval_unseemly_program_without_typechecking(zap_five), Err(ref e),
+              e.starts_with("error[NotSyntax]") && e.contains(" --> [input]:1:60\n"));
+}
+
+#[test]
+fn end_to_end_phase_1() {
+    let inc_macro =
+        "extend_syntax
+             forall . '{ [{lit inc} e = ,{Expr <[Int]<},] }'
+             inc -> .{ (wrap e) }.
+         in inc (times 2 3)";
+
+    // Macro implementations can use helpers (and the core values that the helpers use):
+    assert_eq!(eval_unseemly_program(&format!(
+                   "let_syntax_helper wrap = .[e : Expr <[Int]< .
+                        '[Expr | (plus one ,[Expr | e],)]' ].
+                    in {}", inc_macro)),
+               Ok(val!(i 7)));
+    // ...even inside a `letrec` definition:
+    assert_eq!(eval_unseemly_program(&format!(
+                   "let_syntax_helper wrap = .[e : Expr <[Int]< .
+                        '[Expr | (plus one ,[Expr | e],)]' ].
+                    in letrec f : [Int -> Int] = .[x : Int . {}]. in (f 0)", inc_macro)),
+               Ok(val!(i 7)));
+    // A quoted invocation of such a macro refers to the (freshened) macro argument:
+    assert_eq!(eval_unseemly_program(
+                   "let_syntax_helper wrap = .[e : Expr <[Int]< .
+                        '[Expr | (plus one ,[Expr | e],)]' ].
+                    in extend_syntax
+                        forall . '{ [{lit inc} e = ,{Expr <[Int]<},] }'
+                        inc -> .{ (wrap e) }.
+                    in extend_syntax
+                        forall . '{ [{lit inc_twice} e = ,{Expr <[Int]<},] }'
+                        inc_twice -> .{ '[Expr | inc inc ,[Expr | e], ]' }.
+                    in inc_twice 5"),
+               Ok(val!(i 7)));
+    // ...but not phase-0 definitions:
+    assert_m!(type_unseemly_program(&format!(
+                  "let wrap = .[e : Expr <[Int]< . e]. in {}", inc_macro)),
+              Err(ref e), e.starts_with("error[UnboundName]: `wrap` is not defined"));
+    // ...and phase-0 code can't see the helpers:
+    assert_m!(type_unseemly_program("let_syntax_helper h = 1 in (plus h 1)"),
+              Err(ref e), e.starts_with("error[UnboundName]: `h` is not defined"));
+    // Helpers go out of scope:
+    assert_m!(type_unseemly_program(&format!(
+                  "let x = let_syntax_helper wrap = .[e : Expr <[Int]< . e]. in 0 in {}",
+                  inc_macro)),
+              Err(ref e), e.starts_with("error[UnboundName]: `wrap"));
+}
+
+#[test]
+fn end_to_end_expansion_pipeline() {
+    let program = "extend_syntax
+                       forall T . '{ [{lit twice} e = ,{Expr <[T]<},] }'
+                       twice -> .{ '[Expr | **[,[Expr | e], ,[Expr | e],]** ]' }.
+                   in twice (plus 1 2)";
+
+    // No `macro_invocation`s survive expansion, so the evaluator can handle what's left:
+    let expanded = expand_unseemly_file("[input]", program).unwrap();
+    assert!(!format!("{}", expanded).contains("twice"));
+    assert_eq!(val_env.with(|vals| eval(&expanded, vals.borrow().clone())),
+               Ok(val!(tuple (i 3) (i 3))));
+
+    // ...and the result is remembered (until the type environment changes):
+    assert!(expansion_cache.with(|c| c.borrow().contains_key(
+        &("[input]".to_string(), program.to_string()))));
+    assert_eq!(eval_unseemly_program(program), Ok(val!(tuple (i 3) (i 3))));
+    assert_m!(assign_t_var("SomeType", "Int"), Ok(_));
+    assert!(expansion_cache.with(|c| c.borrow().is_empty()));
+
+    // Warnings are remembered, too, so that they can be reported again:
+    let unreachable = "match 1 { _ => 1  2 => 2 }";
+    assert_eq!(eval_unseemly_program(unreachable), Ok(val!(i 1)));
+    assert_m!(expansion_cache.with(|c| c.borrow().get(
+                  &("[input]".to_string(), unreachable.to_string())).cloned()),
+              Some((_, ref warnings)), warnings.contains("warning[UnreachableArm]"));
+
+    // The sanity check passes on well-behaved macros (and isn't skipped by the cache):
+    check_expansions.with(|c| *c.borrow_mut() = true);
+    assert_eq!(eval_unseemly_program(program), Ok(val!(tuple (i 3) (i 3))));
+    assert_m!(eval_unseemly_file("simple_macro.≉", include_str!("examples/simple_macro.≉")),
+              Ok(_));
+    assert!(!expansion_cache.with(|c| c.borrow().contains_key(
+        &("simple_macro.≉".to_string(), include_str!("examples/simple_macro.≉").to_string()))));
+    check_expansions.with(|c| *c.borrow_mut() = false);
+}
+
+#[test]
+fn end_to_end_expansion_trace() {
+    let program = "extend_syntax
+            forall T . '{ [{lit if}   cond = ,{Expr <[Bool]<},
+                           {lit then} then_e = ,{Expr <[T]<},
+                           {lit else} else_e = ,{Expr <[T]<},] }'
+            conditional -> .{ '[Expr | match ,[Expr | cond], {
+                                           +[True]+ => ,[Expr | then_e],
+                                           +[False]+ => ,[Expr | else_e], } ]' }.
+        in extend_syntax
+            forall T . '{ [{lit unless}   cond = ,{Expr <[Bool]<},
+                           {lit then} then_e = ,{Expr <[T]<},
+                           {lit else} else_e = ,{Expr <[T]<},] }'
+            unless -> .{ '[Expr | if ,[Expr | cond], then ,[Expr | else_e],
+                                  else ,[Expr | then_e], ]' }.
+        in unless (zero? 0) then 1 else 2";
+
+    // The `if` that `unless` expands to gets expanded inside it:
+    assert_eq!(trace_unseemly_program(program), Ok(
+"`unless`  cond = (zero? 0)  then_e = 1  else_e = 2
+  ⟿ if (zero? 0) then 2 else 1
+  `conditional`  cond = (zero? 0)  then_e = 2  else_e = 1
+    ⟿ match (zero? 0) {+[True]+ => 2 +[False]+ => 1}
+∴ match (zero? 0) {+[True]+ => 2 +[False]+ => 1}".to_string()));
+
+    // ...but stepping stops after each layer:
+    let layers = expansion_layers(program).unwrap();
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[0].0.iter().map(|step| step.macro_name).collect::<Vec<_>>(),
+               vec![n("unless")]);
+    assert_eq!(format!("{}", layers[0].1), "if (zero? 0) then 2 else 1");
+    assert_eq!(layers[1].0.iter().map(|step| step.macro_name).collect::<Vec<_>>(),
+               vec![n("conditional")]);
+    assert_eq!(format!("{}", layers[1].1), "match (zero? 0) {+[True]+ => 2 +[False]+ => 1}");
+
+    // Helpers survive stepping until the last layer:
+    let layers = expansion_layers(
+        "let_syntax_helper wrap = .[e : Expr <[Int]< . '[Expr | (plus one ,[Expr | e],)]' ].
+         in extend_syntax
+             forall . '{ [{lit inc} e = ,{Expr <[Int]<},] }'
+             inc -> .{ (wrap e) }.
+         in extend_syntax
+             forall . '{ [{lit inc_twice} e = ,{Expr <[Int]<},] }'
+             inc_twice -> .{ '[Expr | inc inc ,[Expr | e], ]' }.
+         in inc_twice 5").unwrap();
+    assert_eq!(layers.len(), 3);
+    assert_eq!(layers[1].0.iter().map(|step| step.macro_name).collect::<Vec<_>>(),
+               vec![n("inc")]);
+    assert_eq!(format!("{}", layers[2].1), "(plus one (plus one 5))");
+}
+
+#[test]
+fn end_to_end_pattern_macros() {
+    let unless_macro =
+        "forall T . '{ [{lit unless} cond = ,{Expr <[Bool]<},
+                       {lit then} then_e = ,{Expr <[T]<},
+                       {lit else} else_e = ,{Expr <[T]<},] }'
+         unless ==> '[Expr | match cond { +[True]+ => else_e  +[False]+ => then_e } ]'";
+    assert_eq!(eval_unseemly_program(
+                   &format!("extend_syntax {} in unless (zero? 5) then 1 else 2", unless_macro)),
+               Ok(val!(i 1)));
+    // The template is typechecked like any other implementation...
+    assert_m!(type_unseemly_program(
+                  "extend_syntax
+                       forall T . '{ [{lit unless} cond = ,{Expr <[Bool]<},
+                                      {lit then} then_e = ,{Expr <[T]<},] }'
+                       unless ==> '[Expr | match cond { +[True]+ => cond  +[False]+ => then_e } ]'
+                   in 0"),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:4:88\n"));
+    // ...and so are invocations:
+    assert_m!(type_unseemly_program(
+                  &format!("extend_syntax {} in unless 0 then 1 else 2", unless_macro)),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:4:91\n"));
+
+    // Repeated parts are marched through by `...[ ⋯ >> ⋯ ]...`:
+    let twice_all_macro =
+        "forall . '{ [{lit twice_all} [arg = ,{Expr <[Int]<},] *] }'
+         twice_all ==> '[Expr | **[ ...[arg >> (plus arg arg) ]... ]** ]'";
+    assert_eq!(eval_unseemly_program(
+                   &format!("extend_syntax {} in let t = twice_all 1 2 3 in t", twice_all_macro)),
+               Ok(val!(tuple (i 2) (i 4) (i 6))));
+    assert_m!(type_unseemly_program(
+                  &format!("extend_syntax {} in let t = twice_all 1 true in t", twice_all_macro)),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:2:86\n"));
+    // Drivers have to repeat the same number of times:
+    let zip_macro =
+        "forall . '{ [{lit zip} [a = ,{Expr <[Int]<},] * {lit with} [b = ,{Expr <[Int]<},] *] }'
+         zip ==> '[Expr | **[ ...[a b >> (plus a b) ]... ]** ]'";
+    assert_eq!(eval_unseemly_program(&format!("extend_syntax {} in zip 1 2 with 3 4", zip_macro)),
+               Ok(val!(tuple (i 4) (i 6))));
+    assert_m!(eval_unseemly_program(&format!("extend_syntax {} in zip 1 2 with 3", zip_macro)),
+              Err(ref e), e.contains("[BadDriver] `b") && e.contains("can't drive"));
+    // A repeated part can't be used without marching:
+    assert_m!(type_unseemly_program(
+                  "extend_syntax
+                       forall . '{ [{lit twice_all} [arg = ,{Expr <[Int]<},] *] }'
+                       twice_all ==> '[Expr | (plus arg arg) ]'
+                   in 0"),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:3:53\n")
+                          && e.contains("got:      `Sequence`"));
+
+    // Procedural macros can march through repetitions, too:
+    assert_eq!(eval_unseemly_program(
+                   "extend_syntax
+                        forall . '{ [{lit twice_all} [arg = ,{Expr <[Int]<},] *] }'
+                        twice_all -> .{ '[Expr | **[
+                            ...[arg >> (plus ,[Expr | arg], ,[Expr | arg],) ]... ]** ]' }.
+                    in let t = twice_all 5 in t"),
+               Ok(val!(tuple (i 10))));
+}
+
+#[test]
+fn end_to_end_macro_binding() {
+    let mylet_macro =
+        "forall T S . '{ [{lit mylet} p = ,{Pat <[T]<}, {lit :=} e = ,{Expr <[T]<},
+                         {lit in} body = ,{import [p = e] Expr <[S]<},] }'
+         mylet ==> '[Expr | match e { p => body } ]'";
+    assert_eq!(eval_unseemly_program(
+                   &format!("extend_syntax {} in mylet x := 5 in (plus x x)", mylet_macro)),
+               Ok(val!(i 10)));
+    assert_eq!(eval_unseemly_program(
+                   &format!("extend_syntax {} in mylet *[a : x b : y]* := *[a : one b : two]*
+                             in (minus y x)", mylet_macro)),
+               Ok(val!(i 1)));
+    // `x` gets the type of `e`:
+    assert_m!(type_unseemly_program(
+                  &format!("extend_syntax {} in mylet x := 5 in (plus x true)", mylet_macro)),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:3:73\n"));
+    // ...and isn't in scope anywhere else:
+    assert_m!(type_unseemly_program(
+                  &format!("extend_syntax {} in mylet x := x in x", mylet_macro)),
+              Err(ref e), e.starts_with("error[UnboundName]: `x` is not defined")
+                          && e.contains(" --> [input]:3:68\n"));
+}
+
+#[test]
+fn end_to_end_type_and_pat_macros() {
+    let macros =
+        "Type ::= forall T . '{ [{lit pair_of} t = ,{Type <[T]<},] }'
+             pair_of ==> '[Type | **[t t]** ]'
+         Pat ::= forall T . '{ [{lit both} a = ,{Pat <[T]<}, {lit and} b = ,{Pat <[T]<},] }'
+             both ==> '[Pat <[**[T T]**]< | **[a b]** ]' => [a b]";
+    assert_eq!(eval_unseemly_program(
+                   &format!("extend_syntax {} in
+                             (.[p : pair_of Int . match p {{ both x and y => (minus x y) }}].
+                                 **[7 4]**)", macros)),
+               Ok(val!(i 3)));
+    // `pair_of Int` means `**[Int Int]**` to the typechecker:
+    assert_m!(type_unseemly_program(
+                  &format!("extend_syntax {} in
+                            (.[p : pair_of Int . p]. **[7 true]**)", macros)),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:5:29\n"));
+    // ...and `both` binds both of its parts:
+    assert_m!(type_unseemly_program(
+                  &format!("extend_syntax {} in
+                            (.[p : pair_of Int . match p {{ both x and y => (plus x z) }}].
+                                **[7 4]**)", macros)),
+              Err(ref e), e.starts_with("error[UnboundName]: `z` is not defined")
+                          && e.contains(" --> [input]:5:84\n"));
+    // Only arguments can be exported:
+    assert_m!(type_unseemly_program(
+                  "extend_syntax
+                       Pat ::= forall T .
+                           '{ [{lit both} a = ,{Pat <[T]<}, {lit and} b = ,{Pat <[T]<},] }'
+                           both ==> '[Pat <[**[T T]**]< | **[a b]** ]' => [a and]
+                   in (.[p : **[Int Int]** . match p { both x and y => (minus x y) }]. **[7 4]**)"),
+              Err(ref e), e.starts_with("error[UnboundName]: `and` is not defined")
+                          && e.contains(" --> [input]:4:75\n"));
+}
+
+#[test]
+fn end_to_end_user_nonterminals() {
+    let arith =
+        "nonterminal Arith of Expr
+         Arith ::= forall . '{ [{lit one_more} a = ,{Arith <[Int]<},] }'
+             one_more ==> '[Expr | (plus one a)]'
+         Arith ::= forall . '{ [{lit just} e = ,{Expr <[Int]<},] }' just ==> '[Expr | e]'
+         forall . '{ [{lit arith} a = ,{Arith <[Int]<},] }' arith ==> '[Expr | (times a a)]'";
+    assert_eq!(eval_unseemly_program(
+                   &format!("extend_syntax {} in arith one_more one_more just five", arith)),
+               Ok(val!(i 49)));
+    // `Arith` only has the forms we gave it:
+    assert_m!(eval_unseemly_program(&format!("extend_syntax {} in arith five", arith)),
+              Err(ref e), e.starts_with("error[ParseError]") && e.contains(" --> [input]:5:103\n"));
+    // ...and `Arith <[Int]<` is checked like `Expr <[Int]<`:
+    assert_m!(type_unseemly_program(
+                  &format!("extend_syntax {} in arith one_more just true", arith)),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:5:112\n"));
+    // A new nonterminal has to expand into one we know about:
+    assert_m!(type_unseemly_program(
+                  "extend_syntax nonterminal Arith of Arithmetic in zero"),
+              Err(ref e), e.starts_with("error[UnboundName]: `Arithmetic` is not defined")
+                          && e.contains(" --> [input]:1:36\n"));
+    // ...and macros have to extend one that exists:
+    assert_m!(type_unseemly_program(
+                  "extend_syntax Nope ::= forall T . '{ [{lit nope} e = ,{Expr <[T]<},] }'
+                       nope ==> '[Expr | e]'
+                   in zero"),
+              Err(ref e), e.starts_with("error[UnboundName]: `Nope` is not defined")
+                          && e.contains(" --> [input]:1:15\n"));
+    // Built-in nonterminals can't be redeclared:
+    assert_m!(type_unseemly_program("extend_syntax nonterminal Expr of Pat in zero"),
+              Err(ref e), e.starts_with("error[NtRedefinition]: `Expr`")
+                          && e.contains(" --> [input]:1:27\n"));
+    // New nonterminals are visible inside their `extend_syntax` (even to nested ones)...
+    let just = "Arith ::= forall . '{ [{lit just} e = ,{Expr <[Int]<},] }' just ==> '[Expr | e]'";
+    assert_m!(type_unseemly_program(&format!("extend_syntax nonterminal Arith of Expr in
+                                              extend_syntax {} in zero", just)),
+              Ok(_));
+    // ...but nowhere else (not even in later programs):
+    assert_m!(type_unseemly_program(&format!("extend_syntax {} in zero", just)), Err(ref e),
+              e.starts_with("error[UnboundName]: `Arith` is not defined")
+              && e.contains(" --> [input]:1:15\n"));
+
+    // New nonterminals can be quoted...
+    let just_arith = format!("extend_syntax nonterminal Arith of Expr {} in", just);
+    assert_eq!(type_unseemly_program(&format!("{} '[Arith | just 1]'", just_arith))
+                   .map(|t| format!("{}", t)),
+               Ok("Arith <[Int]<".to_string()));
+    assert_eq!(eval_unseemly_program(&format!("{} '[Arith | just 1]'", just_arith))
+                   .map(|v| format!("{}", v)),
+               Ok("'[just 1]'".to_string()));
+    // ...and unquoted (into their own positions, or their base's):
+    assert_eq!(eval_unseemly_program(&format!(
+                   "{} (.[a : Arith <[Int]< . '[Arith | ,[Arith | a], ]' ]. '[Arith | just 1]')",
+                   just_arith)).map(|v| format!("{}", v)),
+               Ok("'[just 1]'".to_string()));
+    assert_eq!(eval_unseemly_program(&format!(
+                   "{} (.[a : Arith <[Int]< . '[Arith | just ,[Arith | a], ]' ].
+                        '[Arith | just 1]')",
+                   just_arith)).map(|v| format!("{}", v)),
+               Ok("'[just just 1]'".to_string()));
+    assert_m!(type_unseemly_program(&format!(
+                  "{} .[a : Arith <[Bool]< . '[Arith | just ,[Arith | a], ]' ].", just_arith)),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+    assert_m!(type_unseemly_program(&format!(
+                  "{} .[a : Expr <[Int]< . '[Arith | ,[Arith | a], ]' ].", just_arith)),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+    // Quoting something that isn't a nonterminal is just a parse error:
+    assert_m!(type_unseemly_program("'[Arith | 1]'"),
+              Err(ref e), e.starts_with("error[ParseError]"));
+}
+
+/// The kind of the type `t`.
+#[cfg(test)]
+fn kind_of_unseemly_type(t: &str) -> Result<String, String> {
+    let tokens = read::read_tokens(t)?;
+    let ast = grammar::parse(&grammar::FormPat::Call(n("Type")),
+                             &core_forms::get_core_forms(), &tokens)
+        .map_err(|e| e.render(n("[input]"), t, color_errors()))?;
+    ty_env.with(|tys| kind::synth_kind(&ast, &tys.borrow()))
+        .map(|k| format!("{}", k)).map_err(|e| e.render(n("[input]"), t, color_errors()))
+}
+
+#[test]
+fn end_to_end_kinds() {
+    let defs = "let_type List = forall T . mu_type List . enum { Nil () Cons (T List <[T]<) }
+                         Twice = forall F : k[* -> *]k T : * . **[F <[T]< F <[T]<]**";
+    assert_m!(type_unseemly_program(&format!("{} in .[l : List <[Int]< . l].", defs)), Ok(_));
+    assert_m!(type_unseemly_program(&format!("{} in .[l : Twice <[List Int]< . l].", defs)),
+              Ok(_));
+
+    // `List` needs exactly one argument:
+    assert_m!(type_unseemly_program(&format!("{} in .[l : List . l].", defs)), Err(ref e),
+              e.starts_with("error[KindMismatch]") && e.contains(" --> [input]:2:95\n")
+              && e.contains("got:      `k[* -> *]k`") && e.contains("expected: `*`"));
+    assert_m!(type_unseemly_program(&format!("{} in .[l : List <[Int Int]< . l].", defs)),
+              Err(ref e),
+              e.starts_with("error[KindMismatch]") && e.contains(" --> [input]:2:95\n")
+              && e.contains("expected: `k[* * -> *]k`"));
+    // ...and `Twice` needs a type constructor as its first one:
+    assert_m!(type_unseemly_program(&format!("{} in .[l : Twice <[Int Int]< . l].", defs)),
+              Err(ref e),
+              e.starts_with("error[KindMismatch]") && e.contains(" --> [input]:2:95\n")
+              && e.contains("got:      `k[k[* -> *]k * -> *]k`"));
+    // `Int` doesn't take arguments at all:
+    assert_m!(type_unseemly_program(".[x : Int <[Bool]< . x]."), Err(ref e),
+              e.starts_with("error[KindMismatch]") && e.contains(" --> [input]:1:7\n")
+              && e.contains("got:      `*`"));
+    // Definitions are kind-checked, too:
+    assert_m!(type_unseemly_program(
+                  "let_type Twice = forall F : k[* -> *]k T : * . **[F <[T]< F]** in zero"),
+              Err(ref e),
+              e.starts_with("error[KindMismatch]") && e.contains(" --> [input]:1:59\n"));
+    // `mu_type` parameters can be annotated, too, but have to agree with what they refer to:
+    let list = |kind: &str| format!(
+        "let_type List = forall T . mu_type List : {} . enum {{ Nil () Cons (T List <[T]<) }}
+         in .[l : List <[Int]< . l].", kind);
+    assert_m!(type_unseemly_program(&list("k[* -> *]k")), Ok(_));
+    assert_m!(type_unseemly_program(&list("k[* * -> *]k")), Err(ref e),
+              e.starts_with("error[KindMismatch]") && e.contains(" --> [input]:1:43\n"));
+
+    // Annotations (of a single parameter, and with `*`) determine the kind:
+    assert_m!(assign_t_var("Id", "forall T : * . T"), Ok(_));
+    assert_m!(kind_of_unseemly_type("Id"), Ok(ref k), k == "k[* -> *]k");
+    assert_m!(assign_t_var("Ap", "forall F : k[* -> *]k A : * . [F <[A]< -> A]"), Ok(_));
+    assert_m!(kind_of_unseemly_type("Ap"), Ok(ref k), k == "k[k[* -> *]k * -> *]k");
+    assert_m!(type_unseemly_program(".[x : forall T : * . T . x]."), Ok(_));
+    // Unannotated parameters are printed without annotations:
+    assert_m!(type_unseemly_program(".[x : forall T . T . x].").map(|t| format!("{}", t)),
+              Ok(ref t), t.starts_with("[forall T . T -> forall T") && !t.contains(":"));
+    let int_list = |kind: &str| format!(
+        "let_type IntList = mu_type IntList : {} . enum {{ Nil () Cons (Int IntList) }}
+         in .[l : IntList . l].", kind);
+    assert_m!(type_unseemly_program(&int_list("*")), Ok(_));
+    assert_m!(type_unseemly_program(&int_list("k[* -> *]k")), Err(ref e),
+              e.starts_with("error[KindMismatch]") && e.contains(" --> [input]:1:38\n"));
+}
+
+#[test]
+fn end_to_end_inference() {
+    // Parameter types can come from what the parameter is used for...
+    assert_eq!(eval_unseemly_program("(.[x . (plus x one)]. two)"), Ok(val!(i 3)));
+    assert_m!(type_unseemly_program(".[x y . (plus x y)]."), Ok(_));
+    // ...or from what the function is passed to:
+    assert_eq!(eval_unseemly_program(
+                   "(.[f : [Int -> Int] . (f three)]. .[x . (times x x)].)"),
+               Ok(val!(i 9)));
+    // (even when the parameter is itself a function, or a value to be destructured)
+    assert_eq!(eval_unseemly_program("(.[f . (f one)]. .[y : Int . y].)"), Ok(val!(i 1)));
+    assert_m!(type_unseemly_program("(.[f . (f one)]. .[y : Int . (zero? y)].)"),
+              Ok(ref t), format!("{}", t).contains("True"));
+
+    // Same for `enum` constructors:
+    let opt = "enum { Some (Int) None () }";
+    assert_eq!(eval_unseemly_program(&format!(
+                   "(.[o : {} . match o {{ +[Some x]+ => x +[None]+ => zero }}]. +[Some five]+)",
+                   opt)),
+               Ok(val!(i 5)));
+    // ...which still get checked:
+    assert_m!(type_unseemly_program(&format!("(.[o : {} . one]. +[Some true]+)", opt)),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+    assert_m!(type_unseemly_program(&format!("(.[o : {} . one]. +[Sone one]+)", opt)),
+              Err(ref e), e.starts_with("error[NonexistentEnumArm]"));
+    assert_eq!(eval_unseemly_program(&format!(
+                   "(.[x . match x {{ +[Some y]+ => y +[None]+ => zero }}]. (+[None]+ : {}))",
+                   opt)),
+               Ok(val!(i 0)));
+    assert_m!(type_unseemly_program(
+                  "(.[x . match x { +[Some y]+ => (zero? y) +[None]+ => false }].
+                       (+[None]+ : enum { Some (Bool) None () }))"),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+    assert_m!(type_unseemly_program(&format!(
+                  "(.[x . match x {{ +[Some y]+ => y }}]. (+[None]+ : {}))", opt)),
+              Err(ref e), e.starts_with("error[NonExhaustiveMatch]"));
+
+    // Nothing determines these:
+    assert_m!(type_unseemly_program(".[x . x]."),
+              Err(ref e), e.starts_with("error[AnnotationNeeded]"));
+    assert_m!(type_unseemly_program(".[f . (f one)]."),
+              Err(ref e), e.starts_with("error[AnnotationNeeded]"));
+    assert_m!(type_unseemly_program("match +[Some one]+ { +[Some x]+ => x }"),
+              Err(ref e), e.starts_with("error[AnnotationNeeded]"));
+    assert_m!(type_unseemly_program(".[x . match x { +[Some y]+ => y }]."),
+              Err(ref e), e.starts_with("error[AnnotationNeeded]"));
+}
+
+#[test]
+fn end_to_end_ascription() {
+    assert_eq!(eval_unseemly_program("(plus (one : Int) two)"), Ok(val!(i 3)));
+    assert_m!(type_unseemly_program("(one : Bool)"),
+              Err(ref e), e.starts_with("error[Mismatch]"));
+    // The ascribed type is what you get:
+    assert_eq!(eval_unseemly_program(
+                   "match (+[Some one]+ : enum { Some (Int) None () }) {
+                        +[Some x]+ => x +[None]+ => zero }"),
+               Ok(val!(i 1)));
+    // ...and it can be what determines an inferred type:
+    assert_eq!(eval_unseemly_program("((.[x . x]. : [Int -> Int]) five)"), Ok(val!(i 5)));
+    assert_m!(type_unseemly_program("(.[x . x]. : [Int -> Bool])"), Err(_));
+}
+
+#[test]
+fn end_to_end_compute_syntax() {
+    // The syntax environment for a part can be computed (at phase 1) while parsing:
+    assert_eq!(eval_unseemly_program(
+                   "extend_syntax
+                        forall . '{ [{lit twice} e = ,{compute .{
+                            .[se : SynEnv . (copy_nonterminal se \"Expr\" \"Expr\")]. }.
+                            Expr <[Int]<},] }'
+                        twice ==> '[Expr | (plus e e)]'
+                    in twice (plus 2 2)"),
+               Ok(val!(i 8)));
+    // `Arith` only has `just`, but the computation makes it parse like `Expr`:
+    let arith = |computation: &str| format!(
+        "extend_syntax
+             nonterminal Arith of Expr
+             Arith ::= forall . '{{ [{{lit just}} e = ,{{Expr <[Int]<}},] }}' just ==> '[Expr | e]'
+             forall . '{{ [{{lit arith}} a = ,{{compute .{{ {} }}. Arith <[Int]<}},] }}'
+                 arith ==> '[Expr | (times a a)]'
+         in arith (plus 2 2)", computation);
+    assert_eq!(eval_unseemly_program(
+                   &arith(".[se : SynEnv . (copy_nonterminal se \"Expr\" \"Arith\")].")),
+               Ok(val!(i 16)));
+    assert_m!(eval_unseemly_program(&arith(".[se : SynEnv . se].")), Err(ref e),
+              e.starts_with("error[ParseError]") && e.contains(" --> [input]:6:19\n"));
+    // A computation that doesn't produce a `SynEnv` just means there's no parse:
+    assert_m!(eval_unseemly_program(&arith(".[se : SynEnv . five].")), Err(ref e),
+              e.starts_with("error[ParseError]") && e.contains(" --> [input]:6:19\n"));
+    // The computation has to be a function from `SynEnv` to `SynEnv`:
+    assert_m!(type_unseemly_program(
+                  "extend_syntax
+                       forall . '{ [{lit twice} e = ,{compute .{ .[se : Int . se]. }.
+                           Expr <[Int]<},] }'
+                       twice ==> '[Expr | (plus e e)]'
+                   in twice 4"),
+              Err(ref e), e.starts_with("error[Mismatch]") && e.contains(" --> [input]:2:66\n")
+                          && e.contains("expected: `SynEnv`"));
+}
+
+#[test]
+fn errors_have_positions() {
+    assert_eq!(eval_unseemly_file("f.≉", "(plus one\n  (zero? nonesuch))"),
+        Err("error[Mismatch]: mismatched types
+ --> f.≉:1:1
+  |
+1 | (plus one
+  | ^^^^^^^^^
+2 |   (zero? nonesuch))
+  |   ^^^^^^^^^^^^^^^^^
+  = got:      `enum {True () False ()}`
+  = expected: `Int`
+error[UnboundName]: `nonesuch` is not defined
+ --> f.≉:2:10
+  |
+2 |   (zero? nonesuch))
+  |          ^^^^^^^^
+".to_string()));
+    assert_eq!(eval_unseemly_file("f.≉", "(plus one\n  (zero? one))"),
+        Err("error[Mismatch]: mismatched types
+ --> f.≉:1:1
+  |
+1 | (plus one
+  | ^^^^^^^^^
+2 |   (zero? one))
+  |   ^^^^^^^^^^^^
+  = got:      `enum {True () False ()}`
+  = expected: `Int`
+".to_string()));
+    assert_m!(eval_unseemly_file("f.≉", "(plus one\n  (zero? one )) )"), Err(ref e),
+              e.starts_with("f.≉:2:17: "));
+    // Uses of a bound variable point at the use, not at the binder:
+    assert_m!(type_unseemly_program(".[b : Bool . match b {+[True]+ => 1}]."), Err(ref e),
+              e.starts_with("error[NonExhaustiveMatch]") && e.contains(" --> [input]:1:20\n"));
+    assert_m!(type_unseemly_program("let_type Foo = Int in (.[n : Int . Foo]. 1)"), Err(ref e),
+              e.starts_with("error[TypeAsValue]") && e.contains(" --> [input]:1:36\n"));
+    // ...even inside syntax extensions:
+    assert_m!(type_unseemly_program(
+                  "extend_syntax forall . '{ [{lit zz}] }' zz -> .{ '[Expr | nonesuch]' }. in 0"),
+              Err(ref e),
+              e.starts_with("error[UnboundName]") && e.contains(" --> [input]:1:59\n"));
+}
+
+#[test]
+fn multiple_type_errors() {
+    // Independent errors all get reported, in order:
+    let errs = type_unseemly_program("**[ (zero? true) nonesuch (plus one) ]**").unwrap_err();
+    let kinds : Vec<&str> = errs.lines().filter(|l| l.starts_with("error[")).collect();
+    assert_eq!(kinds.len(), 3);
+    assert!(kinds[0].starts_with("error[Mismatch]"));
+    assert!(kinds[1].starts_with("error[UnboundName]: `nonesuch`"));
+    assert!(kinds[2].starts_with("error[LengthMismatch]"));
+
+    // ...but not the ones that they cause:
+    assert_m!(type_unseemly_program("(plus one (times nonesuch (nonesuch2 two)))"),
+              Err(ref e), e.matches("error[").count() == 2);
+    assert_m!(type_unseemly_program("match nonesuch { +[Some x]+ => x }"),
+              Err(ref e), e.matches("error[").count() == 1);
+
+    // There's a limit:
+    let many_errors = format!("**[ {} ]**",
+        (0..30).map(|i| format!("nonesuch{}", i)).collect::<Vec<_>>().join(" "));
+    assert_m!(type_unseemly_program(&many_errors), Err(ref e), e.matches("error[").count() == 20);
+}
+
+#[test]
+fn end_to_end_suggestions() {
+    assert_m!(type_unseemly_program("(pluss one two)"),
+              Err(ref e), e.contains("did you mean `plus`?"));
+    assert_m!(type_unseemly_program("(plsu one two)"),
+              Err(ref e), e.contains("did you mean `plus`?"));
+    // Nothing is close enough:
+    assert_m!(type_unseemly_program("(nonesuch one two)"),
+              Err(ref e), !e.contains("did you mean"));
+    assert_m!(type_unseemly_program("+[Sone one]+ : enum { Some (Int) None () }"),
+              Err(ref e), e.contains("did you mean `Some`?"));
+    assert_m!(type_unseemly_program("match +[Some one]+ : enum { Some (Int) None () }
+                                         { +[Sone x]+ => x  +[None]+ => zero }"),
+              Err(ref e), e.starts_with("error[NonexistentEnumArm]")
+                          && e.contains("did you mean `Some`?"));
+    assert_m!(type_unseemly_program(".[p : struct { width : Int  height : Int } . p . widht]."),
+              Err(ref e), e.contains("did you mean `width`?"));
+    assert_m!(type_unseemly_program("Bool"), Err(ref e), e.contains("type name"));
+    assert_m!(eval_unseemly_program("(plus one Bool)"), Err(ref e), e.contains("type name"));
+    // ...but only where a value is expected:
+    assert_eq!(eval_unseemly_program("(.[x : Bool . x]. true)"), Ok(val!(b true)));
+    assert_eq!(eval_unseemly_program("let_type B = Bool in (.[x : B . x]. true)"),
+               Ok(val!(b true)));
+    assert_m!(type_unseemly_program("let_type B = Bool in B"), Err(ref e),
+              e.contains("type name"));
+    assert_m!(type_unseemly_program(
+                  "letrec f : [Int -> Int] = .[x : Int . (plus x Bool)]. in (f one)"),
+              Err(ref e), e.starts_with("error[TypeAsValue]: `Bool` is a type name"));
+    assert_m!(eval_unseemly_program("let Bool = five in Bool"), Ok(_));
+}
+
+#[test]
+fn runtime_errors_have_positions_and_stacks() {
+    // (Quoted patterns are opaque to the exhaustiveness checker, so this typechecks.)
+    assert_eq!(eval_unseemly_file("f.≉",
+                                  "(.[e : Expr <[Int]< .\n  match e { '[Expr | one]' => one }].\n \
+                                   '[Expr | two]')"),
+        Err("error[NonExhaustiveMatch]: no arm of the `match` matched `'[two]'`
+ --> f.≉:2:3
+  |
+2 |   match e { '[Expr | one]' => one }].
+  |   ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+  = in the call to a function at f.≉:1:1
+".to_string()));
+    // Without typechecking, all sorts of things can go wrong:
+    assert_eq!(eval_unseemly_program_without_typechecking("(plus one\n  (zero? true))"),
+        Err("error[BuiltInMisuse]: a built-in function was called with unexpected arguments:
+ --> [input]:2:3
+  |
+2 |   (zero? true))
+  |   ^^^^^^^^^^^^
+  = `+[True]+`
+  = in the call to `zero?` at [input]:2:3
+".to_string()));
+    assert_m!(eval_unseemly_program_without_typechecking("(one two)"),
+        Err(ref e), e.starts_with("error[NotAFunction]: `1` is not a function"));
+    assert_m!(eval_unseemly_program_without_typechecking("nonesuch"),
+        Err(ref e), e.starts_with("error[UnboundVariable]: `nonesuch` is not defined"));
+    // Names point at themselves, not the expression around them:
+    assert_m!(eval_unseemly_program_without_typechecking("(plus one nonesuch)"),
+        Err(ref e), e.contains("1 | (plus one nonesuch)\n  |           ^^^^^^^^\n"));
+}
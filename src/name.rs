@@ -14,7 +14,9 @@ pub struct Spelling {
     // No two different variables have this the same. Tomatoes may have been added:
     unique: String,
     // The original spelling that the programmer chose.
-    orig: String
+    orig: String,
+    // The name that this one is a freshened version of, if any.
+    freshened_from: Option<Name>
 }
 
 thread_local! {
@@ -35,7 +37,8 @@ impl ::runtime::reify::Reifiable for Name {
     fn ty_name() -> Name { n("Name") }
 
     fn reify(&self) -> ::runtime::eval::Value {
-        ::runtime::eval::Value::AbstractSyntax(::ast::Ast::Atom(*self))
+        ::runtime::eval::Value::AbstractSyntax(
+            ::ast::Ast::Atom(*self, ::util::err::Span::nowhere()))
     }
 
     fn reflect(v: &::runtime::eval::Value) -> Name {
@@ -80,16 +83,27 @@ impl Name {
     }
 
     pub fn global(s: &str) -> Name {
-        Name::new(s, false)
+        Name::new(s, false, None)
     }
     pub fn gensym(s: &str) -> Name {
-        Name::new(s, true)
+        Name::new(s, true, None)
     }
     pub fn freshen(self) -> Name {
-        Name::new(&self.orig_sp(), true)
+        Name::new(&self.orig_sp(), true, Some(self))
     }
 
-    fn new(orig_spelling: &str, freshen: bool) -> Name {
+    /// Is this `ancestor`, or the result of (repeatedly) freshening it?
+    /// (Useful when a reference didn't get renamed along with its binder, e.g. under a quotation.)
+    pub fn is_freshening_of(self, ancestor: Name) -> bool {
+        let mut cur = Some(self);
+        while let Some(name) = cur {
+            if name == ancestor { return true; }
+            cur = spellings.with(|us| us.borrow()[name.id].freshened_from);
+        }
+        false
+    }
+
+    fn new(orig_spelling: &str, freshen: bool, freshened_from: Option<Name>) -> Name {
         use std::borrow::{BorrowMut, Borrow};
 
         let fake_freshness_ = fake_freshness.with(|ff| *ff.borrow());
@@ -112,7 +126,8 @@ impl Name {
                     let new_id = spellings_.borrow().len();
                     spellings_.borrow_mut().push(Spelling { 
                         unique: unique_spelling.clone(),
-                        orig: orig_spelling.to_owned()
+                        orig: orig_spelling.to_owned(),
+                        freshened_from: freshened_from
                     });
                     new_id
                 })
@@ -174,6 +189,12 @@ fn name_interning() {
 
     assert_ne!(n("a"), n("y"));
 
+    let a_fresh = a.freshen().freshen();
+    assert!(a_fresh.is_freshening_of(a));
+    assert!(a.is_freshening_of(a));
+    assert!(!a.is_freshening_of(a_fresh));
+    assert!(!n("a").freshen().is_freshening_of(n("y")));
+
     enable_fake_freshness(true);
 
     let x = n("x");
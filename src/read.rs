@@ -8,6 +8,8 @@ TODO #6: ...so that it's possible to switch tokenizers mid-file.
 extern crate regex;
 
 use name::*;
+use util::err::Span;
+use num::bigint::BigInt;
 
 custom_derive! {
     #[derive(Debug,PartialEq,Eq,Clone,Copy,Reifiable)]
@@ -29,11 +31,28 @@ impl DelimChar {
 
 use self::DelimChar::*;
 
-#[derive(Debug,PartialEq,Eq)]
+#[derive(Debug)]
 pub struct TokenTree {
-    pub t: Vec<Token>
+    pub t: Vec<Token>,
+    /// Where each token in `t` came from (for a `Group`, this is its opening delimiter).
+    /// Might be empty, if the tokens weren't read from anywhere.
+    pub spans: Vec<Span>,
+    /// The closing delimiter (or the end of the file)
+    pub end: Span
 }
 
+impl TokenTree {
+    pub fn span_of(&self, idx: usize) -> Span {
+        self.spans.get(idx).cloned().unwrap_or_else(Span::nowhere)
+    }
+}
+
+// Positions don't matter for equality
+impl PartialEq for TokenTree {
+    fn eq(&self, other: &TokenTree) -> bool { self.t == other.t }
+}
+impl Eq for TokenTree {}
+
 #[derive(Debug,PartialEq,Eq)]
 pub enum Token {
     Simple(Name),
@@ -54,9 +73,13 @@ pub use self::Token::*;
 // A token may start with an open delimiter, or end with a close delmiter,
 // but otherwise may not contain delimiters
 
-const nondelim : &str = r"[^\[\]\(\)\{\}\s]";
+// (`"` isn't allowed, so that string literals are always their own tokens)
+const nondelim : &str = r#"[^\[\]\(\)\{\}\s"]"#;
 const open : &str = r"[\[\(\{]";
 const close : &str = r"[\]\)\}]";
+// String and character literals may contain whitespace and delimiters:
+// (A character literal is one character, or one escape, which might be `\u{⋯}`.)
+const text_lit : &str = r#""(?:[^"\\]|\\(?s:.))*"|'(?:[^'\\]|\\u\{[0-9a-fA-F]+\}|\\(?s:.))'"#;
 
 pub fn delim(s: &str) -> DelimChar {
     match s {
@@ -65,44 +88,220 @@ pub fn delim(s: &str) -> DelimChar {
     }
 }
 
+/// Is this token an integer literal? Decimal or hex (`0x2A`), optionally negative.
+pub fn int_literal(s: &str) -> Option<BigInt> {
+    let (neg, s) = if s.starts_with('-') { (true, &s[1..]) } else { (false, s) };
+    let (radix, digits) = if s.starts_with("0x") || s.starts_with("0X") {
+        (16, &s[2..])
+    } else {
+        (10, s)
+    };
+    // `parse_bytes` would also accept a sign:
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) { return None }
+
+    BigInt::parse_bytes(digits.as_bytes(), radix).map(|i| if neg { -i } else { i })
+}
+
+/// Is this token a string literal (e.g. `"a\tb"`)? If so, produce its contents.
+pub fn string_literal(s: &str) -> Option<String> {
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') { return None }
+    unescape(&s[1..s.len()-1])
+}
+
+/// Is this token a character literal (e.g. `'a'` or `'\n'`)? If so, produce the character.
+pub fn char_literal(s: &str) -> Option<char> {
+    if s.len() < 3 || !s.starts_with('\'') || !s.ends_with('\'') || s == "'''" { return None }
+    let contents = unescape(&s[1..s.len()-1])?;
+    let mut chars = contents.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None
+    }
+}
+
+// Rust-style escapes
+fn unescape(s: &str) -> Option<String> {
+    let mut res = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' { res.push(c); continue }
+        res.push(match chars.next()? {
+            'n' => '\n', 't' => '\t', 'r' => '\r', '0' => '\0',
+            '\\' => '\\', '"' => '"', '\'' => '\'',
+            'u' => { // e.g. `\u{2249}`
+                if chars.next()? != '{' { return None }
+                let mut hex = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        h => hex.push(h)
+                    }
+                }
+                ::std::char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+            }
+            _ => return None
+        });
+    }
+    Some(res)
+}
+
+/// A comment, kept around for documentation tools. `text` doesn't include the comment markers.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span
+}
+
+fn is_open_delim(c: char) -> bool { c == '(' || c == '[' || c == '{' }
+
+/// Comments are `# like this` or `#| like this |#` (these nest, and can span lines).
+/// Either way, the opener has to be at the start of a token and followed by whitespace.
+/// (Otherwise, it might be part of a token or a delimiter name, like `#[ ⋯ ]#`.)
+/// Returns `s` with comments blanked out (preserving byte positions), and the comments.
+fn strip_comments(s: &str, span_of: &dyn Fn(usize, usize) -> Span)
+        -> Result<(String, Vec<Comment>), String> {
+    lazy_static! {
+        static ref text : regex::Regex =
+            regex::Regex::new(format!("^(?:{})", text_lit).as_str()).unwrap();
+    }
+    // Keep the newlines, so that line numbers are unaffected:
+    let blank = |from: usize, to: usize| -> String {
+        s[from..to].chars().map(|c| if c == '\n' { "\n".to_string() }
+                                   else { " ".repeat(c.len_utf8()) }).collect()
+    };
+
+    let mut res = String::with_capacity(s.len());
+    let mut comments = vec![];
+    let mut pos = 0;
+    while pos < s.len() {
+        let rest = &s[pos..];
+        // Don't look for comments inside of string literals:
+        if let Some(m) = text.find(rest) {
+            res.push_str(m.as_str());
+            pos += m.end();
+            continue;
+        }
+        let at_token_start = s[..pos].chars().last()
+            .map(|c| c.is_whitespace() || is_open_delim(c)).unwrap_or(true);
+
+        let followed_by_space = |opener: &str| rest.starts_with(opener)
+            && rest[opener.len()..].chars().next().map(char::is_whitespace).unwrap_or(true);
+
+        if at_token_start && followed_by_space("#|") {
+            let mut depth = 0;
+            let mut end = pos;
+            loop {
+                if s[end..].starts_with("#|") {
+                    depth += 1; end += 2;
+                } else if s[end..].starts_with("|#") {
+                    depth -= 1; end += 2;
+                    if depth == 0 { break }
+                } else if let Some(c) = s[end..].chars().next() {
+                    end += c.len_utf8();
+                } else {
+                    return Err(format!("{}: Unclosed comment at EOF", span_of(pos, pos + 2)));
+                }
+            }
+            comments.push(Comment { text: s[pos+2 .. end-2].trim().to_string(),
+                                    span: span_of(pos, end) });
+            res.push_str(&blank(pos, end));
+            pos = end;
+        } else if at_token_start && followed_by_space("#") {
+            let end = rest.find('\n').map(|i| pos + i).unwrap_or(s.len());
+            comments.push(Comment { text: s[pos+1 .. end].trim().to_string(),
+                                    span: span_of(pos, end) });
+            res.push_str(&blank(pos, end));
+            pos = end;
+        } else {
+            let c = rest.chars().next().unwrap();
+            res.push(c);
+            pos += c.len_utf8();
+        }
+    }
+    Ok((res, comments))
+}
+
 pub fn read_tokens(s: &str) -> Result<TokenTree, String> {
+    read_tokens_from(n("[input]"), s)
+}
+
+/// Like `read_tokens`, but `file` is used to report positions.
+pub fn read_tokens_from(file: Name, s: &str) -> Result<TokenTree, String> {
+    read_tokens_with_comments(file, s).map(|(tt, _)| tt)
+}
+
+/// Like `read_tokens_from`, but also produces the comments (in order).
+pub fn read_tokens_with_comments(file: Name, s: &str)
+        -> Result<(TokenTree, Vec<Comment>), String> {
     lazy_static! {
         static ref token : regex::Regex =
-            regex::Regex::new(format!(r"(?P<open_all>(?P<main_o>{nd}*)(?P<open>{o}))|((?P<close>{c})(?P<main_c>{nd}*))|(?P<normal>{nd}+)",
-                                      o = open, c = close, nd = nondelim)
+            regex::Regex::new(format!(r#"(?P<text>{t})|(?P<open_all>(?P<main_o>{nd}*)(?P<open>{o}))|((?P<close>{c})(?P<main_c>{nd}*))|(?P<normal>{nd}+)|(?P<stray>")"#,
+                                      t = text_lit, o = open, c = close, nd = nondelim)
                                       .as_str()).unwrap();
     }
-    let mut flat_tokens = token.captures_iter(s);
+    let line_starts : Vec<usize> = ::std::iter::once(0)
+        .chain(s.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    let span_of = |begin: usize, end: usize| -> Span {
+        let line_idx = match line_starts.binary_search(&begin) { Ok(i) => i, Err(i) => i - 1 };
+        Span { file: file, begin: begin, end: end, line: line_idx + 1,
+               col: s[line_starts[line_idx]..begin].chars().count() + 1 }
+    };
+
+    let (uncommented, comments) = strip_comments(s, &span_of)?;
+    let mut flat_tokens = token.captures_iter(&uncommented);
 
-    fn read_token_tree<'a>(flat_tokens: &mut regex::CaptureMatches<'a, 'a>)
+    fn read_token_tree<'a>(flat_tokens: &mut regex::CaptureMatches<'a, 'a>,
+                           span_of: &dyn Fn(usize, usize) -> Span, eof: usize)
             -> Result<(TokenTree, Option<(DelimChar, &'a str)>), String> {
         let mut this_level : Vec<Token> = vec![];
+        let mut spans : Vec<Span> = vec![];
         loop{
             match flat_tokens.next() {
-                None => { return Ok((TokenTree{ t: this_level }, None)) }
+                None => { return Ok((TokenTree{ t: this_level, spans: spans,
+                                                end: span_of(eof, eof) }, None)) }
                 Some(c) => {
-                    if let Some(normal) = c.name("normal") {
-                        this_level.push(Simple(n(normal.as_str())));
+                    let whole = c.get(0).unwrap();
+                    let span = span_of(whole.start(), whole.end());
+                    if let Some(text) = c.name("text") {
+                        if string_literal(text.as_str()).is_none()
+                                && char_literal(text.as_str()).is_none() {
+                            return Err(format!("{}: Invalid escape in {}", span, text.as_str()));
+                        }
+                        this_level.push(Simple(n(text.as_str())));
+                        spans.push(span);
+                    } else if c.name("stray").is_some() {
+                        return Err(format!("{}: Unterminated string literal", span));
+                    } else if let Some(normal) = c.name("normal") {
+                        let normal_s = normal.as_str();
+                        // (`text_lit` would have matched a well-formed one)
+                        if normal_s.len() >= 2 && normal_s.starts_with('\'')
+                                && normal_s.ends_with('\'') {
+                            return Err(format!("{}: Malformed character literal {}",
+                                               span, normal_s));
+                        }
+                        this_level.push(Simple(n(normal_s)));
+                        spans.push(span);
                     } else if let (Some(_main), Some(o_del), Some(all))
                         = (c.name("main_o"), c.name("open"), c.name("open_all")) {
-                        let (inside, last) = read_token_tree(flat_tokens)?;
+                        let (inside, last) = read_token_tree(flat_tokens, span_of, eof)?;
 
                         if let Some(last) = last {
                             if format!("{}{}",last.1, o_del.as_str()) == all.as_str() {
                                 this_level.push(Group(n(all.as_str()),
                                                       delim(o_del.as_str()), inside));
+                                spans.push(span);
                             } else {
                                 return Err(format!(
-                                    "Unmatched delimiter names: \"{}\" is closed by \"{}\". \
+                                    "{}: Unmatched delimiter names: \"{}\" is closed by \"{}\". \
                                      Remember(this tokenizer is weird)Remember",
-                                        all.as_str(), last.1));
+                                        inside.end, all.as_str(), last.1));
                             }
                         } else {
                             return Err(format!(
-                                "Unclosed delimiter at EOF: \"{}\"", o_del.as_str()));
+                                "{}: Unclosed delimiter at EOF: \"{}\"", span, o_del.as_str()));
                         }
                     } else if let (Some(main), Some(c_del)) = (c.name("main_c"), c.name("close")) {
-                        return Ok((TokenTree{ t: this_level },
+                        return Ok((TokenTree{ t: this_level, spans: spans, end: span },
                                    Some((delim(c_del.as_str()), main.as_str()))));
                     } else { panic!("ICE") }
 
@@ -111,11 +310,11 @@ pub fn read_tokens(s: &str) -> Result<TokenTree, String> {
         }
     }
 
-    let (tt, leftover) = read_token_tree(&mut flat_tokens)?;
+    let (tt, leftover) = read_token_tree(&mut flat_tokens, &span_of, s.len())?;
 
     match leftover {
-        None => Ok(tt),
-        Some(l) => { Err(format!("Read error: leftover {:#?}", l)) }
+        None => Ok((tt, comments)),
+        Some(l) => { Err(format!("{}: Read error: leftover {:#?}", tt.end, l)) }
     }
 }
 
@@ -140,3 +339,98 @@ fn nested_reading() {
     assert_eq!(read_tokens("(#(5 6)# -[yy foo()foo aa]-)"),
                Ok(tokens!((""; ("#"; "5" "6") ["-"; "yy" ("foo";) "aa"]))))
 }
+
+#[test]
+fn int_literals() {
+    assert_eq!(int_literal("0"), Some(BigInt::from(0)));
+    assert_eq!(int_literal("8888"), Some(BigInt::from(8888)));
+    assert_eq!(int_literal("-12"), Some(BigInt::from(-12)));
+    assert_eq!(int_literal("0x2A"), Some(BigInt::from(42)));
+    assert_eq!(int_literal("-0xff"), Some(BigInt::from(-255)));
+    assert_eq!(int_literal("99999999999999999999999").map(|i| format!("{}", i)),
+               Some("99999999999999999999999".to_string()));
+    assert_eq!(int_literal("-"), None);
+    assert_eq!(int_literal("0x"), None);
+    assert_eq!(int_literal("--1"), None);
+    assert_eq!(int_literal("+1"), None);
+    assert_eq!(int_literal("12a"), None);
+    assert_eq!(int_literal("one"), None);
+}
+
+#[test]
+fn text_literals() {
+    assert_eq!(read_tokens(r#"a "b c" d"#), Ok(tokens!("a" "\"b c\"" "d")));
+    assert_eq!(read_tokens(r#"f("(]" ' ')f"#), Ok(tokens!(("f"; "\"(]\"" "' '"))));
+    assert_eq!(read_tokens(r#"x"y\"z" '['"#), Ok(tokens!("x" r#""y\"z""# "'['")));
+    assert_eq!(read_tokens("'[Expr | x]'"), Ok(tokens!(["'"; "Expr" "|" "x"])));
+    match read_tokens("a \"bc") {
+        Err(e) => assert!(e.contains("1:3: Unterminated string literal"), "{}", e),
+        Ok(_) => panic!()
+    }
+    match read_tokens(r#""\q""#) {
+        Err(e) => assert!(e.contains("Invalid escape"), "{}", e),
+        Ok(_) => panic!()
+    }
+
+    assert_eq!(string_literal(r#""""#), Some("".to_string()));
+    assert_eq!(string_literal(r#""a\tb\\\"\u{2249}""#), Some("a\tb\\\"≉".to_string()));
+    assert_eq!(string_literal("abc"), None);
+    assert_eq!(char_literal("'a'"), Some('a'));
+    assert_eq!(char_literal(r"'\''"), Some('\''));
+    assert_eq!(char_literal("'ab'"), None);
+    assert_eq!(char_literal("''"), None);
+    match read_tokens("x 'ab'") {
+        Err(e) => assert!(e.contains("1:3: Malformed character literal 'ab'"), "{}", e),
+        Ok(_) => panic!()
+    }
+    match read_tokens("''") {
+        Err(e) => assert!(e.contains("Malformed character literal"), "{}", e),
+        Ok(_) => panic!()
+    }
+    assert_eq!(read_tokens(r"'\u{2249}' x"), Ok(tokens!(r"'\u{2249}'" "x")));
+    assert_eq!(char_literal(r"'\u{2249}'"), Some('≉'));
+}
+
+#[test]
+fn comments() {
+    assert_eq!(read_tokens("a # b c\nd #\ne"), Ok(tokens!("a" "d" "e")));
+    assert_eq!(read_tokens("a #| b #| c |# d |# e"), Ok(tokens!("a" "e")));
+    assert_eq!(read_tokens("(#| ) |# a)"), Ok(tokens!(("";"a"))));
+    // Not comments:
+    assert_eq!(read_tokens("a#b #c \"# d\" '#'"), Ok(tokens!("a#b" "#c" "\"# d\"" "'#'")));
+    assert_eq!(read_tokens("(#(5 6)# x)"), Ok(tokens!((""; ("#"; "5" "6") "x"))));
+    assert_eq!(read_tokens("#[ a ]# #|b|#"), Ok(tokens!(["#"; "a"] "#|b|#")));
+
+    let (tt, comments) = read_tokens_with_comments(
+        n("f.≉"), "#| Ünicode\n #| nested |# |# a # the end").unwrap();
+    assert_eq!(tt, tokens!("a"));
+    assert_eq!(format!("{}", tt.span_of(0)), "f.≉:2:18");
+    assert_eq!(comments.iter().map(|c| c.text.clone()).collect::<Vec<_>>(),
+               vec!["Ünicode\n #| nested |#".to_string(), "the end".to_string()]);
+    assert_eq!(format!("{}", comments[1].span), "f.≉:2:20");
+
+    match read_tokens("a #| b #| c |# d") {
+        Err(e) => assert!(e.contains("1:3: Unclosed comment"), "{}", e),
+        Ok(_) => panic!()
+    }
+}
+
+#[test]
+fn token_positions() {
+    let tt = read_tokens_from(n("f.unseemly"), "a bb\n  c(d\n)c").unwrap();
+    assert_eq!(tt.spans.iter().map(|sp| (sp.begin, sp.end, sp.line, sp.col)).collect::<Vec<_>>(),
+               vec![(0, 1, 1, 1), (2, 4, 1, 3), (7, 9, 2, 3)]);
+    assert_eq!(format!("{}", tt.span_of(2)), "f.unseemly:2:3");
+    match tt.t[2] {
+        Group(_, _, ref inside) => {
+            assert_eq!(format!("{}", inside.span_of(0)), "f.unseemly:2:5");
+            assert_eq!(format!("{}", inside.end), "f.unseemly:3:1");
+        }
+        _ => panic!()
+    }
+
+    match read_tokens_from(n("f.unseemly"), "a\n b(") {
+        Err(e) => assert!(e.starts_with("f.unseemly:2:2"), "{}", e),
+        Ok(_) => panic!()
+    }
+}
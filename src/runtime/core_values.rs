@@ -1,13 +1,13 @@
 use ast::Ast;
 use ty::Ty;
-use runtime::eval::{Value, BIF, eval};
+use runtime::eval::{Value, BIF, eval, RuntimeErr, RuntimeError, rt_err};
 use runtime::eval::Value::*;
 use util::assoc::Assoc;
 use name::*;
 use std::rc::Rc;
 
 
-use num::{BigInt};
+use num::{BigInt, ToPrimitive};
 
 #[derive(Debug,Clone,PartialEq)]
 pub struct TypedValue {
@@ -15,6 +15,11 @@ pub struct TypedValue {
     pub val: Value
 }
 
+// For when a built-in function gets arguments it can't handle
+fn misuse(args: Vec<Value>) -> RuntimeErr {
+    rt_err(RuntimeError::BuiltInMisuse(args), &Ast::Trivial)
+}
+
 pub fn erase_type(tv: &TypedValue) -> Value { tv.val.clone() }
 pub fn erase_value(tv: &TypedValue) -> Ty { Ty::new(tv.ty.clone()) }
 
@@ -29,7 +34,6 @@ pub fn core_typed_values() -> Assoc<Name, TypedValue> {
                     "param" => [{"Type" "fn" : "param" => [], "ret" => (vr "F") }],
                     "ret" => (vr "F")} ],
                 "ret" => (vr "F") })},
-            // TODO: built-in functions, even though none of them make sense here, shouldn't crash
             ( Function(cl) ) => {
                 let new_env = cl.env.set(cl.params[0],
                     // reconstruct the invocation that caused this:
@@ -41,7 +45,7 @@ pub fn core_typed_values() -> Assoc<Name, TypedValue> {
                         env: assoc_n!("orig_arg" => Function(cl.clone()),
                                       // TODO: `core_values` does the `map` every time...
                                       "fix" => core_values().find_or_panic(&n("fix")).clone())})));
-                eval(&cl.body, new_env).unwrap() // TODO: should be able to return `Result`
+                eval(&cl.body, new_env)?
             }
         ),
         "plus" =>
@@ -60,6 +64,68 @@ pub fn core_typed_values() -> Assoc<Name, TypedValue> {
         tyf!( {"Type" "fn" : "param" => [ {"Type" "Int" :}, {"Type" "Int" :} ],
                              "ret" => (vr "Bool")},
               ( Int(a), Int(b) ) => { val!(b a == b)} ),
+        "string_concat" =>
+        tf!([( "String", "String" ) -> "String"],
+             ( Text(a), Text(b) ) => Text(format!("{}{}", a, b))),
+        "string_length" =>
+        tf!([( "String" ) -> "Int"],
+             ( Text(s) ) => Int(BigInt::from(s.chars().count()))),
+        // Characters from `start` up to (but not including) `end`
+        "substring" =>
+        tf!([( "String", "Int", "Int" ) -> "String"],
+             ( Text(s), Int(start), Int(end) ) => {
+                 match (start.to_usize(), end.to_usize()) {
+                     (Some(st), Some(en)) if st <= en && en <= s.chars().count() => {
+                         Text(s.chars().skip(st).take(en - st).collect())
+                     }
+                     _ => return Err(misuse(vec![Text(s), Int(start), Int(end)]))
+                 }
+             }),
+        "string_split" =>
+        tyf!( { "Type" "fn" :
+                "param" => [ {"Type" "String" :}, {"Type" "String" :} ],
+                "ret" => { "Type" "type_apply" :
+                    "type_rator" => (vr "Sequence"), "arg" => [ {"Type" "String" :} ] } },
+              ( Text(s), Text(sep) ) => {
+                  if sep.is_empty() { return Err(misuse(vec![Text(s), Text(sep)])) }
+                  Sequence(s.split(sep.as_str()).map(|part| Rc::new(Text(part.to_string())))
+                      .collect())
+              }),
+        "int_to_string" =>
+        tf!([( "Int" ) -> "String"],
+             ( Int(i) ) => Text(format!("{}", i))),
+        // Uses the same syntax as integer literals
+        "string_to_int" =>
+        tf!([( "String" ) -> "Int"],
+             ( Text(s) ) => {
+                 match ::read::int_literal(&s) {
+                     Some(i) => Int(i),
+                     None => return Err(misuse(vec![Text(s)]))
+                 }
+             }),
+        "char_to_string" =>
+        tf!([( "Char" ) -> "String"],
+             ( Char(c) ) => Text(c.to_string())),
+        "string_equal?" =>
+        tyf!( {"Type" "fn" : "param" => [ {"Type" "String" :}, {"Type" "String" :} ],
+                             "ret" => (vr "Bool")},
+              ( Text(a), Text(b) ) => val!(b a == b)),
+        "string_less?" =>
+        tyf!( {"Type" "fn" : "param" => [ {"Type" "String" :}, {"Type" "String" :} ],
+                             "ret" => (vr "Bool")},
+              ( Text(a), Text(b) ) => val!(b a < b)),
+        // (For `ComputeSyntax`) Make the nonterminal `to` parse the same way as `from`
+        "copy_nonterminal" =>
+        tyf!( {"Type" "fn" :
+                "param" => [ (vr "SynEnv"), {"Type" "String" :}, {"Type" "String" :} ],
+                "ret" => (vr "SynEnv") },
+              ( se, Text(from), Text(to) ) => {
+                  let syn_env = ::grammar::reflect_syn_env(&se, &Ast::Trivial)?;
+                  match syn_env.find(&n(&from)).cloned() {
+                      Some(pat) => ::grammar::reify_syn_env(&syn_env.set(n(&to), pat)),
+                      None => return Err(misuse(vec![se, Text(from), Text(to)]))
+                  }
+              }),
         "zero" => tf!( "Int", val!(i 0) ),
         "one" => tf!( "Int", val!(i 1) ),
         "two" => tf!( "Int", val!(i 2) ),
@@ -93,6 +159,10 @@ pub fn core_types() -> Assoc<Name, Ty> {
         .set(n("Type"), ty!({get__abstract_parametric_type() ; "name" => "Type" }))
         .set(n("Expr"), ty!({get__abstract_parametric_type() ; "name" => "Expr" }))
         .set(n("Sequence"), ty!({get__abstract_parametric_type() ; "name" => "Sequence" }))
+        .set(n("SynEnv"), ty!({get__abstract_parametric_type() ; "name" => "SynEnv" }))
+        // (The syntax `String` always means the type form; this is for `Reifiable` types)
+        .set(n("String"), ty!({"Type" "String" :}))
+        .set(n("Char"), ty!({"Type" "Char" :}))
 }
 
 
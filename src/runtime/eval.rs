@@ -9,6 +9,8 @@ use ast_walk::{walk, WalkRule, LazyWalkReses};
 use walk_mode::{WalkMode, NegativeWalkMode};
 use form::Form;
 use std;
+use std::cell::RefCell;
+use util::err::Diagnostic;
 
 /**
  * Values in Unseemly.
@@ -17,7 +19,10 @@ use std;
 #[derive(Debug,Clone,PartialEq)]
 pub enum Value {
     Int(BigInt),
+    Text(String),
+    Char(char),
     Sequence(Vec<Rc<Value>>), // TODO: switch to a different core sequence type
+    Tuple(Vec<Rc<Value>>),
     Function(Rc<Closure>), // TODO: unsure if this Rc is needed
     BuiltInFunction(BIF),
     AbstractSyntax(Ast), // Unsure if this needs an Rc.
@@ -35,7 +40,7 @@ pub struct Closure {
 }
 
 // Built-in function
-pub struct BIF(pub Rc<(dyn Fn(Vec<Value>) -> Value)>);
+pub struct BIF(pub Rc<(dyn Fn(Vec<Value>) -> Result<Value, RuntimeErr>)>);
 
 impl PartialEq for BIF {
     fn eq(&self, other: &BIF) -> bool {
@@ -53,9 +58,19 @@ impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match *self {
             Int(ref bi) => { write!(f, "{}", bi) }
+            Text(ref s) => { write!(f, "{:?}", s) }
+            Char(c) => { write!(f, "{:?}", c) }
             Sequence(ref seq) => {
                 for elt in seq { try!(write!(f, "{}", &*elt)); }; Ok(())
             }
+            Tuple(ref components) => {
+                write!(f, "**[")?;
+                for (i, elt) in components.iter().enumerate() {
+                    if i > 0 { write!(f, " ")?; }
+                    write!(f, "{}", &*elt)?;
+                }
+                write!(f, "]**")
+            }
             Function(_) => { write!(f, "[closure]") }
             BuiltInFunction(_) => { write!(f, "[built-in function]") }
             AbstractSyntax(ref ast) => { write!(f, "'[{}]'", ast) }
@@ -81,6 +96,164 @@ impl std::fmt::Debug for BIF {
     }
 }
 
+custom_derive! {
+    #[derive(Reifiable, Clone, PartialEq)]
+    pub enum RuntimeError {
+        UnboundVariable(Name),
+        NotAFunction(Value),
+        // A pattern didn't match (`match` uses this to try the next arm)
+        MatchFailure(Value),
+        NonExhaustiveMatch(Value),
+        BuiltInMisuse(Vec<Value>),
+        NoSuchField(Name, Value),
+        // A `...[ ]...` driver wasn't a sequence (of the same length as the first driver)
+        BadDriver(Name, Value),
+        // A `ComputeSyntax` computation produced something other than a `SynEnv`
+        NotASynEnv(Value),
+        // A macro implementation produced something other than syntax
+        NotSyntax(Value),
+        MacroExpansionFailure(Name, Box<RuntimeErr>)
+    }
+}
+
+impl Diagnostic for RuntimeError {
+    fn kind(&self) -> String {
+        use self::RuntimeError::*;
+        match *self {
+            UnboundVariable(_) => "UnboundVariable",
+            NotAFunction(_) => "NotAFunction",
+            MatchFailure(_) => "MatchFailure",
+            NonExhaustiveMatch(_) => "NonExhaustiveMatch",
+            BuiltInMisuse(_) => "BuiltInMisuse",
+            NoSuchField(_, _) => "NoSuchField",
+            BadDriver(_, _) => "BadDriver",
+            NotASynEnv(_) => "NotASynEnv",
+            NotSyntax(_) => "NotSyntax",
+            MacroExpansionFailure(_, _) => "MacroExpansionFailure"
+        }.to_string()
+    }
+
+    fn describe(&self) -> String {
+        use self::RuntimeError::*;
+        match *self {
+            UnboundVariable(name) => format!("`{}` is not defined", name),
+            NotAFunction(ref v) => format!("`{}` is not a function, but it was called", v),
+            MatchFailure(ref v) => format!("`{}` doesn't match the pattern", v),
+            NonExhaustiveMatch(ref v) => format!("no arm of the `match` matched `{}`", v),
+            BuiltInMisuse(ref args) => {
+                format!("a built-in function was called with unexpected arguments:\n{}",
+                        args.iter().map(|a| format!("`{}`", a)).collect::<Vec<_>>().join(", "))
+            }
+            NoSuchField(name, ref v) => format!("`{}` has no component named `{}`", v, name),
+            BadDriver(name, ref v) => {
+                format!("`{}` can't drive this `...[ ]...`: its value, `{}`, isn't a sequence \
+                         as long as the first driver's", name, v)
+            }
+            NotASynEnv(ref v) => format!("`{}` is not a `SynEnv`", v),
+            NotSyntax(ref v) => format!("`{}` is not syntax, so a macro can't expand into it", v),
+            MacroExpansionFailure(name, ref inner) => {
+                format!("the macro `{}` failed to expand\n{}", name, inner)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.kind(), self.describe())
+    }
+}
+
+impl std::fmt::Debug for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+custom_derive! {
+    /// A `RuntimeError`, where it happened, and the Unseemly calls that were in progress.
+    #[derive(Reifiable, Clone, PartialEq)]
+    pub struct RuntimeErr {
+        pub body: RuntimeError,
+        pub loc: Ast,
+        pub stack: Vec<Ast> // `apply` nodes, outermost first
+    }
+}
+
+thread_local! {
+    // The `apply` nodes currently being evaluated
+    pub static call_stack: RefCell<Vec<Ast>> = RefCell::new(vec![]);
+}
+
+// Don't print every frame of a deep recursion:
+const max_frames_shown: usize = 10;
+
+/// Make a `RuntimeErr`, recording the current call stack.
+pub fn rt_err(body: RuntimeError, loc: &Ast) -> RuntimeErr {
+    RuntimeErr {
+        body: body, loc: loc.clone(), stack: call_stack.with(|cs| cs.borrow().clone())
+    }
+}
+
+/// Evaluate `f` with `call` on the call stack.
+pub fn with_frame<T, F: FnOnce() -> T>(call: &Ast, f: F) -> T {
+    call_stack.with(|cs| cs.borrow_mut().push(call.clone()));
+    let res = f();
+    call_stack.with(|cs| { cs.borrow_mut().pop(); });
+    res
+}
+
+impl RuntimeErr {
+    /// Built-in functions don't know where they were called from; fill that in.
+    pub fn or_at(self, loc: &Ast) -> RuntimeErr {
+        if self.loc.span().is_nowhere() { RuntimeErr { loc: loc.clone(), .. self } } else { self }
+    }
+
+    pub fn render(&self, file: Name, source: &str, color: bool) -> String {
+        ::util::err::render_diagnostic(self, self.loc.span(), file, source, color)
+    }
+}
+
+impl Diagnostic for RuntimeErr {
+    fn kind(&self) -> String { self.body.kind() }
+
+    fn describe(&self) -> String {
+        let mut res = self.body.describe();
+        for (i, frame) in self.stack.iter().rev().enumerate() {
+            if i == max_frames_shown {
+                res.push_str(&format!("\n... and {} more calls", self.stack.len() - i));
+                break;
+            }
+            let callee = match *frame {
+                Ast::Node(_, ref parts, _, _) => match parts.get_leaf(n("rator")) {
+                    Some(&Ast::VariableReference(name, _)) => format!("`{}`", name),
+                    _ => "a function".to_string()
+                },
+                _ => "a function".to_string()
+            };
+            res.push_str(&format!("\nin the call to {} at {}", callee, frame.span()));
+        }
+        res
+    }
+}
+
+impl std::fmt::Display for RuntimeErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let span = self.loc.span();
+        if span.is_nowhere() {
+            write!(f, "[{}] {}", self.kind(), self.describe())
+        } else {
+            write!(f, "{}: [{}] {}", span, self.kind(), self.describe())
+        }
+    }
+}
+
+impl std::fmt::Debug for RuntimeErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
 impl ::walk_mode::WalkElt for Value {
     fn from_ast(a: &Ast) -> Value {  AbstractSyntax(a.clone()) }
     fn to_ast(&self) -> Ast {
@@ -109,15 +282,18 @@ impl WalkMode for Eval {
 
     type Elt = Value;
     type Negated = Destructure;
-    type Err = ();
+    type Err = RuntimeErr;
     type D = ::walk_mode::Positive<Eval>;
     type ExtraInfo = ();
 
     fn get_walk_rule(f: &Form) -> WalkRule<Eval> { f.eval.pos().clone() }
     fn automatically_extend_env() -> bool { true }
 
-    fn walk_var(n: Name, cnc: &LazyWalkReses<Eval>) -> Result<Value, ()> {
-        Ok(cnc.env.find(&n).expect("Undefined var; did you use a type name as a value?").clone())
+    fn walk_var(n: Name, cnc: &LazyWalkReses<Eval>) -> Result<Value, RuntimeErr> {
+        match cnc.env.find(&n) {
+            Some(v) => Ok(v.clone()),
+            None => Err(rt_err(RuntimeError::UnboundVariable(n), &cnc.this_ast))
+        }
     }
 
     // TODO: maybe keep this from being called?
@@ -129,7 +305,7 @@ impl WalkMode for Destructure {
 
     type Elt = Value;
     type Negated = Eval;
-    type Err = ();
+    type Err = RuntimeErr;
     type D = ::walk_mode::Negative<Destructure>;
     type ExtraInfo = ();
 
@@ -141,6 +317,10 @@ impl WalkMode for Destructure {
 }
 
 impl NegativeWalkMode for Destructure {
+    fn qlit_mismatch_error(got: Value, _expd: Value) -> RuntimeErr {
+        rt_err(RuntimeError::MatchFailure(got), &Ast::Trivial)
+    }
+
     fn needs_pre_match() -> bool { false } // Values don't have binding (in this mode!)
 }
 
@@ -150,16 +330,16 @@ impl ::walk_mode::WalkElt for Ast {
 }
 
 
-pub fn eval_top(expr: &Ast) -> Result<Value, ()> {
+pub fn eval_top(expr: &Ast) -> Result<Value, RuntimeErr> {
     eval(expr, Assoc::new())
 }
 
-pub fn eval(expr: &Ast, env: Assoc<Name, Value>) -> Result<Value, ()> {
+pub fn eval(expr: &Ast, env: Assoc<Name, Value>) -> Result<Value, RuntimeErr> {
     walk::<Eval>(expr, &LazyWalkReses::new_wrapper(env))
 }
 
 pub fn neg_eval(pat: &Ast, env: Assoc<Name, Value>)
-        -> Result<Assoc<Name, Value>,()> {
+        -> Result<Assoc<Name, Value>, RuntimeErr> {
     walk::<Destructure>(pat, &LazyWalkReses::new_wrapper(env))
 }
 
@@ -178,17 +358,16 @@ impl WalkMode for QQuote {
     // Why not `Ast`? Because QQuote and Eval need to share environments.
     type Elt = Value;
     type Negated = QQuoteDestr;
-    type Err = ();
+    type Err = RuntimeErr;
     type D = ::walk_mode::Positive<QQuote>;
     type ExtraInfo = ();
 
-    fn walk_var(n: Name, _: &LazyWalkReses<Self>) -> Result<Value, ()> {
-        let n_sp = &n.sp();
-        Ok(val!(ast (vr n_sp)))
+    // Use the `Name` itself, not its spelling, so freshened names don't get conflated.
+    fn walk_var(n: Name, cnc: &LazyWalkReses<Self>) -> Result<Value, RuntimeErr> {
+        Ok(AbstractSyntax(::ast::VariableReference(n, cnc.this_ast.span())))
     }
-    fn walk_atom(n: Name, _: &LazyWalkReses<Self>) -> Result<Value, ()> {
-        let n_sp = &n.sp();
-        Ok(val!(ast n_sp))
+    fn walk_atom(n: Name, cnc: &LazyWalkReses<Self>) -> Result<Value, RuntimeErr> {
+        Ok(AbstractSyntax(::ast::Atom(n, cnc.this_ast.span())))
     }
     fn get_walk_rule(f: &Form) -> WalkRule<QQuote> { f.quasiquote.pos().clone() }
     fn automatically_extend_env() -> bool { false }
@@ -199,24 +378,24 @@ impl WalkMode for QQuoteDestr {
 
     type Elt = Value;
     type Negated = QQuote;
-    type Err = ();
+    type Err = RuntimeErr;
     type D = ::walk_mode::Negative<QQuoteDestr>;
     type ExtraInfo = ();
 
-    fn walk_var(n: Name, cnc: &LazyWalkReses<Self>) -> Result<Assoc<Name, Value>, ()> {
-        let n_sp = &n.sp();
-        if cnc.context_elt() == &val!(ast (vr n_sp)) {
+    fn walk_var(n: Name, cnc: &LazyWalkReses<Self>) -> Result<Assoc<Name, Value>, RuntimeErr> {
+        let expected = AbstractSyntax(::ast::VariableReference(n, ::util::err::Span::nowhere()));
+        if cnc.context_elt() == &expected {
             Ok(Assoc::<Name, Value>::new())
         } else {
-            Err(Self::qlit_mismatch_error(val!(ast (vr n_sp)), cnc.context_elt().clone()))
+            Err(Self::qlit_mismatch_error(cnc.context_elt().clone(), expected))
         }
     }
-    fn walk_atom(n: Name, cnc: &LazyWalkReses<Self>) -> Result<Assoc<Name, Value>, ()> {
-        let n_sp = &n.sp();
-        if cnc.context_elt() == &val!(ast n_sp) {
+    fn walk_atom(n: Name, cnc: &LazyWalkReses<Self>) -> Result<Assoc<Name, Value>, RuntimeErr> {
+        let expected = AbstractSyntax(::ast::Atom(n, ::util::err::Span::nowhere()));
+        if cnc.context_elt() == &expected {
             Ok(Assoc::<Name, Value>::new())
         } else {
-            Err(Self::qlit_mismatch_error(val!(ast (vr n_sp)), cnc.context_elt().clone()))
+            Err(Self::qlit_mismatch_error(cnc.context_elt().clone(), expected))
         }
     }
     fn get_walk_rule(f: &Form) -> WalkRule<QQuoteDestr> { f.quasiquote.neg().clone() }
@@ -224,6 +403,10 @@ impl WalkMode for QQuoteDestr {
 }
 
 impl NegativeWalkMode for QQuoteDestr {
+    fn qlit_mismatch_error(got: Value, _expd: Value) -> RuntimeErr {
+        rt_err(RuntimeError::MatchFailure(got), &Ast::Trivial)
+    }
+
     fn needs_pre_match() -> bool { true } // Quoted syntax does have binding!
 }
 
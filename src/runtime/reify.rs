@@ -33,13 +33,15 @@ pub trait Reifiable {
             name: Self::ty_name(),
             grammar: Rc::new(::grammar::FormPat::Impossible),
             type_compare: ::form::Positive(::ast_walk::WalkRule::NotWalked),
+            synth_kind: ::form::Positive(::ast_walk::WalkRule::NotWalked),
             synth_type: ::form::Positive(::ast_walk::WalkRule::LiteralLike),
             quasiquote: ::form::Both(::ast_walk::WalkRule::LiteralLike,
                                      ::ast_walk::WalkRule::LiteralLike),
             eval: ::form::Positive(::ast_walk::WalkRule::NotWalked),
         }),
         ::util::mbe::EnvMBE::new(),
-        ::beta::ExportBeta::Nothing)
+        ::beta::ExportBeta::Nothing,
+        ::util::err::Span::nowhere())
     }
 
     /// A name for that type, so that recursive types are okay.
@@ -52,7 +54,9 @@ pub trait Reifiable {
     /// e.g. `Annotated_with_int<[nat]<`
     /// (Types using this type will use this, rather than `ty`)
     /// This must be customized if `ty` is, I think...
-    fn ty_invocation() -> Ast { Ast::VariableReference(Self::ty_name()) }
+    fn ty_invocation() -> Ast {
+        Ast::VariableReference(Self::ty_name(), ::util::err::Span::nowhere())
+    }
 
     /// The Unseemly value that corresponds to a value.
     fn reify(&self) -> Value;
@@ -80,6 +84,11 @@ macro_rules! basic_reifiability {
 }
 
 basic_reifiability!(BigInt, "Int", Int);
+basic_reifiability!(String, "String", Text);
+basic_reifiability!(char, "Char", Char);
+// Syntax already has a runtime representation. Taking it apart structurally would copy
+//  its `Form`s, and `Form`s are compared by identity.
+basic_reifiability!(::ast::Ast, "Ast", AbstractSyntax);
 
 impl Reifiable for bool {
     fn ty_name() -> Name { n("Bool") }
@@ -146,14 +155,14 @@ impl Reifiable for Value {
 pub fn reify_1ary_function<A: Reifiable + 'static, R: Reifiable + 'static>(
         f: Rc<Box<(dyn Fn(A) -> R)>>) -> Value {
     Value::BuiltInFunction(::runtime::eval::BIF(Rc::new(
-        move |args: Vec<Value>| ((*f)(A::reflect(&args[0]))).reify())))
+        move |args: Vec<Value>| Ok(((*f)(A::reflect(&args[0]))).reify()))))
 }
 
 pub fn reflect_1ary_function<A: Reifiable + 'static, R: Reifiable + 'static>(
         f_v: Value) -> Rc<Box<(dyn Fn(A) -> R)>> {
     Rc::new(Box::new(move |a: A|
         extract!((&f_v)
-            Value::BuiltInFunction = (ref bif) => R::reflect(&(*bif.0)(vec![a.reify()]));
+            Value::BuiltInFunction = (ref bif) => R::reflect(&(*bif.0)(vec![a.reify()]).unwrap());
             Value::Function = (ref closure) => {
                 R::reflect(&::runtime::eval::eval(&closure.body,
                     closure.env.clone().set(closure.params[0], a.reify())).unwrap())
@@ -165,7 +174,7 @@ pub fn reify_2ary_function<A: Reifiable + 'static, B: Reifiable + 'static,
                            R: Reifiable + 'static>(
         f: Rc<Box<(dyn Fn(A, B) -> R)>>) -> Value {
     Value::BuiltInFunction(::runtime::eval::BIF(Rc::new(
-        move |args: Vec<Value>| ((*f)(A::reflect(&args[0]), B::reflect(&args[1]))).reify())))
+        move |args: Vec<Value>| Ok(((*f)(A::reflect(&args[0]), B::reflect(&args[1]))).reify()))))
 }
 
 pub fn reflect_2ary_function<A: Reifiable + 'static, B: Reifiable + 'static,
@@ -174,7 +183,7 @@ pub fn reflect_2ary_function<A: Reifiable + 'static, B: Reifiable + 'static,
     Rc::new(Box::new(move |a: A, b: B|
         extract!((&f_v)
             Value::BuiltInFunction = (ref bif) =>
-                R::reflect(&(*bif.0)(vec![a.reify(), b.reify()]));
+                R::reflect(&(*bif.0)(vec![a.reify(), b.reify()]).unwrap());
             Value::Function = (ref closure) => {
                 R::reflect(&::runtime::eval::eval(&closure.body,
                     closure.env.clone().set(closure.params[0], a.reify())
@@ -15,6 +15,7 @@ use form::Form;
 use util::assoc::Assoc;
 use ast::*;
 use name::*;
+use util::err::Span;
 use std::rc::Rc;
 
 #[derive(PartialEq, Clone)]
@@ -39,6 +40,21 @@ impl Ty {
         self.0.destructure(expd_form.clone()).ok_or(
             ty_err_val!(UnableToDestructure(self.clone(), expd_form.name) at loc /*TODO*/))
     }
+
+    /// Look through a type that's being inferred (e.g. an unannotated `lambda` parameter's).
+    /// It's an error if context hasn't determined it yet.
+    pub fn known(&self, loc: &Ast) -> TypeResult {
+        match ::ty_compare::underdetermined_source(self) {
+            None => Ok(self.clone()),
+            Some(_) => {
+                let res = synth_type(&self.0, Assoc::new())?;
+                match ::ty_compare::underdetermined_source(&res) {
+                    None => Ok(res),
+                    Some(source) => ty_err!(AnnotationNeeded(source) at loc)
+                }
+            }
+        }
+    }
 }
 
 // this kinda belongs in core_forms.rs
@@ -71,6 +87,17 @@ custom_derive!{
     #[derive(Copy, Clone, Debug, Reifiable)]
     pub struct SynthTy {}
 }
+custom_derive!{
+    /// What typechecking an expression needs to know, besides the types of the names in scope.
+    #[derive(Clone, Debug, Default, Reifiable)]
+    pub struct TyCtxt {
+        /// The names (and what they're bound to) that are types, so they can't be used as values.
+        /// (Only known when walking an expression; empty in a type.)
+        pub type_names: Assoc<Name, Ty>,
+        /// The types of what macro implementations (at phase 1) can see, besides the core values.
+        pub phase_1: Assoc<Name, Ty>
+    }
+}
 custom_derive!{
     #[derive(Copy, Clone, Debug, Reifiable)]
     pub struct UnpackTy {}
@@ -82,22 +109,27 @@ impl WalkMode for SynthTy {
     type Negated = UnpackTy;
     type Err = TypeError;
     type D = ::walk_mode::Positive<SynthTy>;
-    type ExtraInfo = ();
+    type ExtraInfo = TyCtxt;
 
     fn get_walk_rule(f: &Form) -> WalkRule<SynthTy> { f.synth_type.pos().clone() }
     fn automatically_extend_env() -> bool { true }
 
     fn walk_var(name: Name, parts: &::ast_walk::LazyWalkReses<SynthTy>) -> Result<Ty, TypeError> {
         match parts.env.find(&name) {
-            None => Err(::util::err::sp(TyErr::UnboundName(name), parts.this_ast.clone())),
+            None => Err(::util::err::sp(unbound_name(name, &parts.env), parts.this_ast.clone())),
             // If name is protected, stop:
-            Some(ty) if &Ty(VariableReference(name)) == ty => Ok(ty.clone()),
+            Some(ty) if &Ty(VariableReference(name, Span::nowhere())) == ty => Ok(ty.clone()),
+            Some(ty) if parts.extra_info.type_names.find(&name) == Some(ty) => {
+                Err(::util::err::sp(TyErr::TypeAsValue(name), parts.this_ast.clone()))
+            }
             Some(ty) => synth_type(&ty.concrete(), parts.env.clone())
         }
     }
 
     // Simply protect the name; don't try to unify it.
-    fn underspecified(name: Name) -> Ty { Ty(VariableReference(name)) }
+    fn underspecified(name: Name) -> Ty { Ty(VariableReference(name, Span::nowhere())) }
+
+    fn recover(err: TypeError) -> TypeResult { record_error(err) }
 }
 
 impl WalkMode for UnpackTy {
@@ -106,24 +138,212 @@ impl WalkMode for UnpackTy {
     type Negated = SynthTy;
     type Err = TypeError;
     type D = ::walk_mode::Negative<UnpackTy>;
-    type ExtraInfo = ();
+    type ExtraInfo = TyCtxt;
 
     fn get_walk_rule(f: &Form) -> WalkRule<UnpackTy> { f.synth_type.neg().clone() }
     fn automatically_extend_env() -> bool { true }
 
-    fn underspecified(name: Name) -> Ty { Ty(VariableReference(name)) }
+    fn underspecified(name: Name) -> Ty { Ty(VariableReference(name, Span::nowhere())) }
 }
 
 impl ::walk_mode::NegativeWalkMode for UnpackTy {
     fn needs_pre_match() -> bool { true }
 }
 
-pub fn synth_type_top(expr: &Ast) -> TypeResult {
-    walk::<SynthTy>(expr, &LazyWalkReses::new_wrapper(Assoc::new()))
-}
+pub fn synth_type_top(expr: &Ast) -> TypeResult { synth_type(expr, Assoc::new()) }
 
 pub fn synth_type(expr: &Ast, env: Assoc<Name, Ty>) -> TypeResult {
-    walk::<SynthTy>(expr, &LazyWalkReses::new_wrapper(env))
+    synth_type_all(expr, env).map_err(|mut errs| errs.remove(0))
+}
+
+/// The type that the annotation `part` (e.g. the `t` in `.[x : t . ⋯].`) stands for.
+/// Annotations have to be proper types (e.g. not `List` or `Int <[Bool]<`).
+pub fn synth_annotation(parts: &LazyWalkReses<SynthTy>, part: Name) -> TypeResult {
+    ::kind::expect_proper_type(&parts.get_term(part), &parts.env)?;
+    in_type(parts).get_res(part)
+}
+
+/// The types of what macro implementations (which run at phase 1) can refer to.
+pub fn phase_1_tys(parts: &LazyWalkReses<SynthTy>) -> Assoc<Name, Ty> {
+    ::runtime::core_values::core_types().set_assoc(&parts.extra_info.phase_1)
+}
+
+/// `parts`, for walking a part that's a type, where type names are fine.
+/// (Walk type parts this way before anything else does, since results are memoized.)
+pub fn in_type(parts: &LazyWalkReses<SynthTy>) -> LazyWalkReses<SynthTy> {
+    LazyWalkReses { extra_info: TyCtxt { type_names: Assoc::new(), .. parts.extra_info.clone() },
+                    .. parts.clone() }
+}
+
+/// Like `synth_type`, but if this is the outermost typechecking going on,
+///  report every independent error (in the order that they appear in the source).
+pub fn synth_type_all(expr: &Ast, env: Assoc<Name, Ty>) -> Result<Ty, Vec<TypeError>> {
+    synth_expr_type_all(expr, env, Assoc::new())
+}
+
+/// Like `synth_type_all`, but `expr` is an expression (e.g. a whole program),
+///  and the names in `type_names` are bound (in `env`) to types, so they aren't values.
+pub fn synth_expr_type_all(expr: &Ast, env: Assoc<Name, Ty>, type_names: Assoc<Name, Ty>)
+        -> Result<Ty, Vec<TypeError>> {
+    synth_depth.with(|d| *d.borrow_mut() += 1);
+    let res = walk::<SynthTy>(expr, &LazyWalkReses {
+        extra_info: TyCtxt { type_names: type_names, phase_1: Assoc::new() },
+        .. LazyWalkReses::new_wrapper(env.clone()) });
+    let outermost = synth_depth.with(|d| { *d.borrow_mut() -= 1; *d.borrow() == 0 });
+    if !outermost { return res.map_err(|e| vec![e]); }
+
+    let checks = deferred_checks.with(|cs| ::std::mem::replace(&mut *cs.borrow_mut(), vec![]));
+    let res = res.and_then(|res| {
+        if checks.is_empty() { return Ok(res); }
+        // Outer checks go first, since they might determine types that inner ones need.
+        // But it can go the other way, too, so retry the ones that were missing something
+        //  (until they stop making progress):
+        let mut pending = checks.iter().rev().collect::<Vec<_>>();
+        while !pending.is_empty() {
+            let mut stuck = vec![];
+            for check in &pending {
+                match check() {
+                    Err(ref e) if is_annotation_needed(e) => stuck.push((*check, e.clone())),
+                    other => other.or_else(note_error)?
+                }
+            }
+            if stuck.len() == pending.len() {
+                for (_, e) in stuck { note_error(e)?; }
+                break;
+            }
+            pending = stuck.into_iter().map(|(check, _)| check).collect();
+        }
+        // Something was inferred; fill in what we learned:
+        walk::<SynthTy>(&res.0, &LazyWalkReses::new_wrapper(env))
+    });
+
+    let mut errs = ty_errors.with(|es| ::std::mem::replace(&mut *es.borrow_mut(), vec![]));
+    match res {
+        Err(e) => {
+            let redundant = (e.body.is_cascade() && !errs.is_empty())
+                || errs.iter().any(|old| same_error(old, &e));
+            if !redundant { errs.push(e) }
+        }
+        Ok(ref t) if errs.is_empty() => { return Ok(t.clone()); }
+        Ok(_) => {}
+    }
+    // Errors with no location (e.g. from tests) keep their order, at the end:
+    errs.sort_by_key(|e| { let sp = e.loc.span(); (sp.is_nowhere(), sp.file.sp(), sp.begin) });
+    Err(errs)
+}
+
+thread_local! {
+    static synth_depth: ::std::cell::RefCell<u32> = ::std::cell::RefCell::new(0);
+    // Checks waiting on inferred types, which only get determined by their context.
+    static deferred_checks: ::std::cell::RefCell<Vec<Box<dyn Fn() -> Result<(), TypeError>>>>
+        = ::std::cell::RefCell::new(vec![]);
+}
+
+// After this many errors, stop trying to find more; they're likely to just be confusing.
+const max_ty_errors : usize = 20;
+
+thread_local! {
+    static ty_errors: ::std::cell::RefCell<Vec<TypeError>> = ::std::cell::RefCell::new(vec![]);
+
+    // The type of something that didn't typecheck. It goes with every other type,
+    //  so that typechecking can keep going without complaining about the same thing again.
+    static error_form: Rc<Form> = Rc::new(Form {
+        name: n("<error>"),
+        grammar: Rc::new(form_pat!((lit "<error>"))),
+        type_compare: ::form::Both(LiteralLike, LiteralLike),
+        synth_kind: ::form::Positive(NotWalked),
+        synth_type: ::form::Positive(cust_rc_box!(|parts| Ok(Ty(parts.this_ast.clone())))),
+        eval: ::form::Positive(NotWalked),
+        quasiquote: ::form::Both(LiteralLike, LiteralLike)
+    })
+}
+
+pub fn error_ty() -> Ty {
+    error_form.with(|f| Ty(Node(f.clone(), ::util::mbe::EnvMBE::new(), ::beta::ExportBeta::Nothing,
+                               Span::nowhere())))
+}
+
+pub fn is_error_ty(t: &Ty) -> bool {
+    error_form.with(|f| match t.0 { Node(ref t_f, _, _, _) => t_f == f, _ => false })
+}
+
+// Does `a` have an error type somewhere in it?
+fn mentions_error_ty(a: &Ast) -> bool {
+    match *a {
+        Node(_, ref parts, _, _) => {
+            is_error_ty(&Ty(a.clone()))
+                || parts.map_reduce(&|sub| mentions_error_ty(sub), &|l, r| *l || *r, false)
+        }
+        QuoteMore(ref body, _) | QuoteLess(ref body, _) | ExtendEnv(ref body, _) => {
+            mentions_error_ty(body)
+        }
+        _ => false
+    }
+}
+
+impl TyErr {
+    /// The same error, seen from the other side of a contravariant position (e.g. `fn` params),
+    ///  so that "got" and "expected" still refer to what the user wrote.
+    pub fn flip(self) -> TyErr {
+        match self {
+            TyErr::Mismatch(got, exp) => TyErr::Mismatch(exp, got),
+            other => other
+        }
+    }
+
+    /// Is this error just a consequence of an earlier one?
+    fn is_cascade(&self) -> bool {
+        use self::TyErr::*;
+        match *self {
+            Mismatch(ref got, ref exp) => mentions_error_ty(&got.0) || mentions_error_ty(&exp.0),
+            NonexistentEnumArm(_, ref t) | NonexistentStructField(_, ref t)
+                | NonExhaustiveMatch(ref t, _) | UnableToDestructure(ref t, _) => {
+                mentions_error_ty(&t.0)
+            }
+            // (The number of types doesn't depend on whether they went wrong)
+            LengthMismatch(_, _) | KindMismatch(_, _) | NtInterpMismatch(_, _)
+                | UnboundName(_, _) | TypeAsValue(_) | AnnotationNeeded(_)
+                | OrPatBindingMismatch(_) | NtRedefinition(_) => false
+        }
+    }
+}
+
+// Typechecking can revisit the same syntax, so the same error can come up more than once.
+fn same_place(loc: &Ast, other_loc: &Ast) -> bool {
+    let (span, other_span) = (loc.span(), other_loc.span());
+    if span.is_nowhere() { loc == other_loc } else {
+        (span.file, span.begin, span.end) == (other_span.file, other_span.begin, other_span.end)
+    }
+}
+
+fn same_error(e: &TypeError, other: &TypeError) -> bool {
+    e.body == other.body && same_place(&e.loc, &other.loc)
+}
+
+/// Note `err` and carry on, pretending that the thing that caused it has the error type.
+/// (Unless there are too many errors, in which case we just fail.)
+pub fn record_error(err: TypeError) -> TypeResult {
+    if synth_depth.with(|d| *d.borrow() == 0) { return Err(err); } // nowhere to report it!
+    note_error(err).map(|()| error_ty())
+}
+
+fn note_error(err: TypeError) -> Result<(), TypeError> {
+    ty_errors.with(|es| {
+        let mut es = es.borrow_mut();
+        if err.body.is_cascade() || es.iter().any(|old| same_error(old, &err)) { return Ok(()); }
+        if es.len() + 1 >= max_ty_errors { return Err(err); }
+        es.push(err);
+        Ok(())
+    })
+}
+
+fn is_annotation_needed(err: &TypeError) -> bool {
+    match err.body { TyErr::AnnotationNeeded(_) => true, _ => false }
+}
+
+/// Run `check` once the outermost `synth_type` is done, when inferred types are determined.
+pub fn defer_check(check: Box<dyn Fn() -> Result<(), TypeError>>) {
+    deferred_checks.with(|cs| cs.borrow_mut().push(check))
 }
 
 pub fn neg_synth_type(pat: &Ast, env: Assoc<Name, Ty>)
@@ -135,56 +355,120 @@ custom_derive! {
     #[derive(Reifiable, Clone, PartialEq)]
     pub enum TyErr {
         Mismatch(Ty, Ty), // got, expected
+        KindMismatch(Ty, Ty), // got, expected (both kinds)
         LengthMismatch(Vec<Ty>, usize),
         NtInterpMismatch(Name, Name),
         NonexistentEnumArm(Name, Ty),
         NonexistentStructField(Name, Ty),
-        NonExhaustiveMatch(Ty),
+        NonExhaustiveMatch(Ty, String), // the type, and an example of what's missing
         UnableToDestructure(Ty, Name),
-        UnboundName(Name)
+        UnboundName(Name, Option<Name>), // the name, and what it might be a typo of
+        TypeAsValue(Name), // a type name, referred to as if it were a value
+        AnnotationNeeded(Name), // what we couldn't infer the type of
+        OrPatBindingMismatch(Name), // bound by some alternatives of an or-pattern, but not all
+        NtRedefinition(Name) // a `nonterminal` declaration of a built-in nonterminal
     }
 }
 
-impl ::std::fmt::Display for TyErr {
-    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+impl ::util::err::Diagnostic for TyErr {
+    fn kind(&self) -> String {
+        use self::TyErr::*;
+        match *self {
+            Mismatch(_, _) => "Mismatch",
+            KindMismatch(_, _) => "KindMismatch",
+            LengthMismatch(_, _) => "LengthMismatch",
+            NtInterpMismatch(_, _) => "NtInterpMismatch",
+            NonexistentEnumArm(_, _) => "NonexistentEnumArm",
+            NonexistentStructField(_, _) => "NonexistentStructField",
+            NonExhaustiveMatch(_, _) => "NonExhaustiveMatch",
+            UnableToDestructure(_, _) => "UnableToDestructure",
+            UnboundName(_, _) => "UnboundName",
+            TypeAsValue(_) => "TypeAsValue",
+            AnnotationNeeded(_) => "AnnotationNeeded",
+            OrPatBindingMismatch(_) => "OrPatBindingMismatch",
+            NtRedefinition(_) => "NtRedefinition"
+        }.to_string()
+    }
+
+    fn describe(&self) -> String {
         use self::TyErr::*;
         match *self {
             Mismatch(ref got, ref exp) => {
-                write!(f, "[Mismatch] got:\n  `{:#?}`\n   expected:\n  `{:#?}`\n", got, exp)
+                format!("mismatched types\ngot:      `{}`\nexpected: `{}`", got, exp)
+            }
+            KindMismatch(ref got, ref exp) => {
+                format!("mismatched kinds\ngot:      `{}`\nexpected: `{}`", got, exp)
             }
             LengthMismatch(ref got, exp_len) => {
-                write!(f, "[LengthMismatch] got:\n  ")?;
-                for g in got {
-                    write!(f, "{}, ", g)?;
-                }
-                write!(f, "\n  expected {} arguments.\n", exp_len)
+                format!("expected {} arguments, got {}:\n{}", exp_len, got.len(),
+                        got.iter().map(|g| format!("`{}`", g)).collect::<Vec<_>>().join(", "))
             }
             NtInterpMismatch(got, exp) => {
-                write!(f, "[NtInterpMismatch] expected the nonterminal `{:#?}`, \
-                           but `{:#?}` was interpolated",
-                       exp, got)
+                format!("expected the nonterminal `{}`, but `{}` was interpolated", exp, got)
             }
             NonexistentEnumArm(got_name, ref ty) => {
-                write!(f, "[NonexistentEnumArm] the enum `{}` doesn't have an arm named `{:#?}`",
-                       ty, got_name)
+                format!("the enum `{}` doesn't have an arm named `{}`{}", ty, got_name,
+                        did_you_mean(got_name, &names_in(ty, "enum", "name")))
             }
             NonexistentStructField(got_name, ref ty) => {
-                write!(f, "[NonexistentStructField] the struct `{}` doesn't have a \
-                           field named `{:#?}`",
-                       ty, got_name)
+                format!("the struct `{}` doesn't have a field named `{}`{}", ty, got_name,
+                        did_you_mean(got_name, &names_in(ty, "struct", "component_name")))
+            }
+            NonExhaustiveMatch(ref ty, ref missing) => {
+                format!("non-exhaustive match of `{}`: missing `{}`", ty, missing)
             }
-            NonExhaustiveMatch(ref ty) =>
-                write!(f, "[NonExhaustiveMatch] non-exhaustive match of `{}`", ty),
             UnableToDestructure(ref ty, expected_name) => {
-                write!(f, "[UnableToDestructure] expected a `{}` type, got `{}`", expected_name, ty)
+                format!("expected a `{}` type, got `{}`", expected_name, ty)
+            }
+            UnboundName(name, suggestion) => {
+                format!("`{}` is not defined{}", name,
+                        suggestion.map(|s| format!("\ndid you mean `{}`?", s)).unwrap_or_default())
             }
-            UnboundName(name) => {
-                write!(f, "[UnboundName] `{}` is not defined", name)
+            TypeAsValue(name) => {
+                format!("`{}` is a type name, not a value\n(did you use a type as a value?)", name)
             }
+            AnnotationNeeded(name) => {
+                format!("type annotation needed\ncan't infer the type of `{}` from its context",
+                        name)
+            }
+            OrPatBindingMismatch(name) => {
+                format!("`{}` isn't bound by every alternative of this pattern\n\
+                         (each alternative has to bind the same names)", name)
+            }
+            NtRedefinition(name) => format!("`{}` is already a built-in nonterminal", name),
         }
     }
 }
 
+/// `name` isn't bound in `env`; maybe it's a typo of something that is?
+pub fn unbound_name(name: Name, env: &Assoc<Name, Ty>) -> TyErr {
+    let spellings : Vec<String> = env.iter_keys().map(|k| k.orig_sp()).collect();
+    let suggestion = ::util::err::closest_spelling(
+        &name.orig_sp(), spellings.iter().map(|s| s.as_str())).map(n);
+    TyErr::UnboundName(name, suggestion)
+}
+
+// The names of the arms/fields of an `enum`/`struct` type (if that's what `ty` is)
+fn names_in(ty: &Ty, form_name: &str, part_name: &str) -> Vec<String> {
+    match ty.0.destructure(::core_forms::find("Type", form_name)) {
+        Some(parts) => parts.get_rep_leaf_or_panic(n(part_name)).iter()
+            .map(|a| ::core_forms::ast_to_name(a).orig_sp()).collect(),
+        None => vec![]
+    }
+}
+
+fn did_you_mean(name: Name, candidates: &[String]) -> String {
+    ::util::err::closest_spelling(&name.orig_sp(), candidates.iter().map(|s| s.as_str()))
+        .map(|s| format!("\ndid you mean `{}`?", s)).unwrap_or_default()
+}
+
+impl ::std::fmt::Display for TyErr {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        use util::err::Diagnostic;
+        write!(f, "[{}] {}", self.kind(), self.describe())
+    }
+}
+
 // temporary, until we get rid of `Debug` as the way of outputting errors
 impl ::std::fmt::Debug for TyErr {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
@@ -203,6 +487,49 @@ impl From<()> for TyErr {
 
 pub type TypeError = ::util::err::Spanned<TyErr>;
 
+/// Something suspicious that doesn't stop a program from typechecking.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TyWarning {
+    UnreachableArm
+}
+
+impl ::util::err::Diagnostic for TyWarning {
+    fn kind(&self) -> String {
+        match *self { TyWarning::UnreachableArm => "UnreachableArm".to_string() }
+    }
+
+    fn describe(&self) -> String {
+        match *self {
+            TyWarning::UnreachableArm => {
+                "unreachable `match` arm\nthe arms before it already match everything it does"
+                    .to_string()
+            }
+        }
+    }
+
+    fn severity(&self) -> &'static str { "warning" }
+}
+
+thread_local! {
+    static ty_warnings: ::std::cell::RefCell<Vec<::util::err::Spanned<TyWarning>>>
+        = ::std::cell::RefCell::new(vec![]);
+}
+
+/// Report a warning. (Typechecking can revisit the same syntax, so duplicates are dropped.)
+pub fn warn(w: TyWarning, loc: Ast) {
+    ty_warnings.with(|ws| {
+        let mut ws = ws.borrow_mut();
+        if !ws.iter().any(|old| old.body == w && same_place(&loc, &old.loc)) {
+            ws.push(::util::err::sp(w, loc));
+        }
+    })
+}
+
+/// Get (and forget) all the warnings reported so far.
+pub fn take_warnings() -> Vec<::util::err::Spanned<TyWarning>> {
+    ty_warnings.with(|ws| ::std::mem::replace(&mut *ws.borrow_mut(), vec![]))
+}
+
 pub type TypeResult = Result<Ty, TypeError>;
 
 
@@ -266,7 +593,7 @@ fn type_specialization() {
     let nat_ty = ty!( { "Type" "Nat" : });
 
     fn tbn(nm: &'static str) -> Ty {
-        Ty(::ast::Ast::VariableReference(n(nm)))
+        Ty(::ast::Ast::VariableReference(n(nm), Span::nowhere()))
     }
 
     let _para_ty_env = assoc_n!(
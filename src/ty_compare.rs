@@ -6,6 +6,7 @@ use util::assoc::Assoc;
 use ast::*;
 use ty::{Ty, TyErr};
 use name::*;
+use util::err::Span;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use core_forms::{find_core_form, ast_to_name};
@@ -110,15 +111,15 @@ pub fn resolve(Clo { it: t, env }: Clo<Ty>, unif: &HashMap<Name, Clo<Ty>>) -> Cl
     let u_f = underdetermined_form.with(|u_f| { u_f.clone() });
 
     let resolved = match t {
-        Ty(VariableReference(vr)) => {
+        Ty(VariableReference(vr, _)) => {
             match env.find(&vr).cloned() {
                 // HACK: leave mu-protected variables alone, instead of recurring forever
-                Some(Ty(VariableReference(new_vr))) if vr == new_vr => None,
+                Some(Ty(VariableReference(new_vr, _))) if vr == new_vr => None,
                 Some(different) => Some(Clo{it: different, env: env.clone()}),
                 None => None
             }
         }
-        Ty(Node(ref form, ref parts, _)) if form == &find_core_form("Type", "type_apply") => {
+        Ty(Node(ref form, ref parts, _, _)) if form == &find_core_form("Type", "type_apply") => {
             // Expand defined type applications.
             // This is sorta similar to the type synthesis for "type_apply",
             //  but it does not recursively process the arguments (which may be underdetermined!).
@@ -129,7 +130,7 @@ pub fn resolve(Clo { it: t, env }: Clo<Ty>, unif: &HashMap<Name, Clo<Ty>>) -> Cl
                 unif);
 
             match resolved {
-                Clo{it: Ty(VariableReference(rator_vr)), env} => {
+                Clo{it: Ty(VariableReference(rator_vr, rator_span)), env} => {
                     // e.g. `X<[int, Y]<` underneath `mu X. ...`
 
                     // Rebuild a type_apply, but evaulate its arguments
@@ -137,7 +138,7 @@ pub fn resolve(Clo { it: t, env }: Clo<Ty>, unif: &HashMap<Name, Clo<Ty>>) -> Cl
                     //  we wish to avoid aliasing problems at the type level.
                     // In System F, this is avoided by performing capture-avoiding substitution.
                     let mut new__tapp_parts = ::util::mbe::EnvMBE::new_from_leaves(
-                        assoc_n!("type_rator" => VariableReference(rator_vr)));
+                        assoc_n!("type_rator" => VariableReference(rator_vr, rator_span)));
 
                     let mut args = vec![];
                     for individual__arg_res in arg_terms {
@@ -147,7 +148,7 @@ pub fn resolve(Clo { it: t, env }: Clo<Ty>, unif: &HashMap<Name, Clo<Ty>>) -> Cl
                     new__tapp_parts.add_anon_repeat(args, None);
 
                     let res = Ty::new(Node(find_core_form("Type", "type_apply"),
-                        new__tapp_parts, ::beta::ExportBeta::Nothing));
+                        new__tapp_parts, ::beta::ExportBeta::Nothing, t.0.span()));
 
                     if res != t { Some(Clo{it: res, env: env.clone()}) } else { None }
                 }
@@ -178,7 +179,7 @@ pub fn resolve(Clo { it: t, env }: Clo<Ty>, unif: &HashMap<Name, Clo<Ty>>) -> Cl
                 }
             }
         }
-        Ty(Node(ref form, ref parts, _)) if form == &u_f => { // underdetermined
+        Ty(Node(ref form, ref parts, _, _)) if form == &u_f => { // underdetermined
             unif.get(&ast_to_name(parts.get_leaf_or_panic(&n("id")))).cloned()
         }
         _ => None
@@ -203,17 +204,37 @@ thread_local! {
                 unification.with(|unif| {
                     let unif = unif.borrow();
                     // TODO: don't use the id in an error message; it's user-hostile:
-                    let clo = unif.get(&id).ok_or(TyErr::UnboundName(id))?;
+                    let clo = unif.get(&id).ok_or(TyErr::UnboundName(id, None))?;
                     canonicalize(&clo.it, clo.env.clone())
                 })
             }),
             WalkRule::NotWalked),
-        synth_type:   ::form::Both(WalkRule::NotWalked,WalkRule::NotWalked),
+        synth_kind:   ::form::Both(WalkRule::NotWalked,WalkRule::NotWalked),
+        // Whatever it's been determined to be so far (which might still be nothing):
+        synth_type:   ::form::Both(
+            cust_rc_box!(|udet_parts| {
+                let resolved = unification.with(|unif| {
+                    resolve(Clo{it: Ty(udet_parts.this_ast.clone()), env: udet_parts.env.clone()},
+                            &unif.borrow())
+                });
+                if resolved.it.0 == udet_parts.this_ast { return Ok(resolved.it); }
+                ::ty::synth_type(&resolved.it.0, resolved.env)
+            }),
+            WalkRule::NotWalked),
         eval:         ::form::Both(WalkRule::NotWalked,WalkRule::NotWalked),
         quasiquote:   ::form::Both(WalkRule::NotWalked,WalkRule::NotWalked)
     })
 }
 
+/// If `t` is a not-yet-determined type, what it's the type of
+///  (e.g. the name of the `lambda` parameter that needs it).
+pub fn underdetermined_source(t: &Ty) -> Option<Name> {
+    let u_f = underdetermined_form.with(|u_f| { u_f.clone() });
+    let parts = t.0.destructure(u_f)?;
+    let id = ast_to_name(parts.get_leaf_or_panic(&n("id"))).orig_sp();
+    Some(n(id.split('⚁').next().unwrap()))
+}
+
 
 custom_derive!{
     #[derive(Copy, Clone, Debug, Reifiable)]
@@ -240,9 +261,9 @@ impl WalkMode for Canonicalize {
     fn walk_var(n: Name, cnc: &LazyWalkReses<Canonicalize>) -> Result<Ty, TyErr> {
         match cnc.env.find(&n) {
             // If it's protected, stop:
-            Some(t) if &Ty(VariableReference(n)) == t => Ok(t.clone()),
+            Some(t) if &Ty(VariableReference(n, Span::nowhere())) == t => Ok(t.clone()),
             Some(t) => canonicalize(t, cnc.env.clone()),
-            None => Ok(Ty(VariableReference(n))) //TODO why can this happen?
+            None => Ok(Ty(VariableReference(n, Span::nowhere()))) //TODO why can this happen?
         }
     }
 }
@@ -266,7 +287,7 @@ impl WalkMode for Subtype {
                 // TODO: we need `gensym`!
                 let new_name = n(format!("{}⚁{}", name, *id.borrow()).as_str());
 
-                ty!({ u_f.clone() ; "id" => (, ::ast::Atom(new_name))})
+                ty!({ u_f.clone() ; "id" => (, ::ast::Atom(new_name, Span::nowhere()))})
             })
         })
     }
@@ -274,10 +295,10 @@ impl WalkMode for Subtype {
     /// Look up the reference and keep going.
     fn walk_var(n: Name, cnc: &LazyWalkReses<Subtype>) -> Result<Assoc<Name, Ty>, TyErr> {
         let lhs: &Ty = cnc.env.find_or_panic(&n);
-        if lhs == &Ty(VariableReference(n)) { // mu-protected!
+        if lhs == &Ty(VariableReference(n, Span::nowhere())) { // mu-protected!
             return match cnc.context_elt() {
                 // mu-protected type variables have to exactly match by name:
-                &Ty(VariableReference(other_n)) if other_n == n => Ok(Assoc::new()),
+                &Ty(VariableReference(other_n, _)) if other_n == n => Ok(Assoc::new()),
                 different => Err(TyErr::Mismatch(different.clone(), lhs.clone()))
             }
         }
@@ -395,6 +416,8 @@ impl ::walk_mode::NegativeWalkMode for Subtype {
                 // Make a determination (possibly just merging two underdetermined types):
                 (Ok(l), _) => { unif.borrow_mut().insert(l, rhs.clone()); return None; }
                 (_, Ok(r)) => { unif.borrow_mut().insert(r, lhs.clone()); return None; }
+                // Something already failed to typecheck; don't complain about it again:
+                _ if ::ty::is_error_ty(&lhs.it) || ::ty::is_error_ty(&rhs.it) => { return None; }
                 // They are (potentially) different.
                 _ => { Some((lhs, rhs)) }
             }
@@ -402,7 +425,7 @@ impl ::walk_mode::NegativeWalkMode for Subtype {
 
         // Now, resolve `:::[]:::` subtyping
         match (&res_lhs.it, &mut res_rhs.it) {
-            (&Ty(Node(_, ref lhs_body, _)), &mut Ty(Node(_, ref mut rhs_body, _))) => {
+            (&Ty(Node(_, ref lhs_body, _, _)), &mut Ty(Node(_, ref mut rhs_body, _, _))) => {
                     // the LHS should be the subtype (i.e. already specific),
                     // and the RHS should be made to match
                     let _ = rhs_body.heal_splices__with(lhs_body, &match_dotdotdot(env));
@@ -778,6 +801,32 @@ fn subtype_dotdotdot() {
         Err(_));
 }
 
+#[test]
+fn subtype_tuples() {
+    let int_bool = ty!({"Type" "tuple" :
+        "component" => [{"Type" "Int" :}, (vr "Bool")]});
+    let int_t = ty!({"Type" "tuple" :
+        "component" => [{"Type" "Int" :}, (vr "T")]});
+    let env = assoc_n!("Bool" => ty!({"Type" "enum" : "name" => [@"c" "True", "False"],
+                                      "component" => [@"c" [], []]}),
+                       "T" => Subtype::underspecified(n("-")));
+
+    assert_m!(must_subtype(&int_bool, &int_bool, env.clone()), Ok(_));
+    // Components are compared covariantly:
+    assert_m!(must_subtype(&int_t, &int_bool, env.clone()), Ok(_));
+    assert_m!(must_subtype(&ty!({"Type" "tuple" :
+                                    "component" => [(vr "Bool"), {"Type" "Int" :}]}),
+                           &int_bool, env.clone()),
+        Err(TyErr::Mismatch(_, _)));
+
+    assert_m!(must_subtype(&ty!({"Type" "tuple" : "component" => [{"Type" "Int" :}]}),
+                           &int_bool, env.clone()),
+        Err(TyErr::LengthMismatch(_, 2)));
+    assert_m!(must_subtype(&ty!({"Type" "tuple" : "component" => []}),
+                           &ty!({"Type" "tuple" : "component" => []}), env.clone()),
+        Ok(_));
+}
+
 #[test]
 fn basic_resolve() {
     let u_f = underdetermined_form.with(|u_f| { u_f.clone() });
@@ -1,4 +1,5 @@
 use name::*;
+use util::err::Span;
 use grammar::{FormPat, SynEnv};
 use grammar::FormPat::*;
 use ast::Ast;
@@ -27,8 +28,8 @@ fn node_names_mentioned(pat: &FormPat) -> Vec<Name> {
             res.append(&mut node_names_mentioned(&*rhs));
             res
         }
-        Anyways(_) | Impossible | Literal(_) | AnyToken | AnyAtomicToken | VarRef | Call(_)
-        | SynImport(_,_,_) => { vec![] }
+        Anyways(_) | Impossible | Literal(_) | AnyToken | AnyAtomicToken | VarRef | IntLiteral
+        | StringLiteral | CharLiteral | Call(_) | SynImport(_,_,_) => { vec![] }
     }
 }
 
@@ -37,7 +38,7 @@ pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv)
     //HACK: handle underdetermined forms
     let undet = ::ty_compare::underdetermined_form.with(|u| u.clone());
     match *actl {
-        Node(ref form, ref body, _) if form == &undet => {
+        Node(ref form, ref body, _, _) if form == &undet => {
             return ::ty_compare::unification.with(|unif| {
                 let var = ::core_forms::ast_to_name(body.get_leaf_or_panic(&n("id")));
                 let looked_up = unif.borrow().get(&var).cloned();
@@ -54,18 +55,26 @@ pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv)
     // TODO: this really ought to notice when `actl` is ill-formed for `pat`.
     match (pat, actl) {
         (&Named(name, ref body), _) => {
-            unparse_mbe(&*body, context.get_leaf(name).unwrap_or(&Atom(n("<->"))), context, s)
+            let missing = Atom(n("<->"), Span::nowhere());
+            unparse_mbe(&*body, context.get_leaf(name).unwrap_or(&missing), context, s)
         }
             //=> unparse_mbe(&*body, context.get_leaf(name).unwrap_or(&Atom(n("<MISSING>"))), context, s),
         (&Call(sub_form), _) => unparse_mbe(s.find_or_panic(&sub_form), actl, context, s),
         (&Anyways(_), _) | (&Impossible, _) => "".to_string(),
         (&Literal(n), _) => n.print(),
-        (&AnyToken, &Atom(n)) => n.print(),
+        (&AnyToken, &Atom(n, _)) => n.print(),
         (&AnyToken, _) => panic!("TODO: pretty print arbitrary token trees"),
-        (&AnyAtomicToken, &Atom(n)) => n.print(),
+        (&AnyAtomicToken, &Atom(n, _)) => n.print(),
         (&AnyAtomicToken, _) => "".to_string(), // HACK for `Alt`
-        (&VarRef, &VariableReference(n)) => n.print(),
+        (&VarRef, &VariableReference(n, _)) => n.print(),
         (&VarRef, _) => "".to_string(), // HACK for `Alt`
+        (&IntLiteral, &Atom(n, _)) | (&StringLiteral, &Atom(n, _))
+        | (&CharLiteral, &Atom(n, _)) => {
+            n.orig_sp() // (not `print`; we want to keep the quotes and escapes)
+        }
+        (&IntLiteral, _) | (&StringLiteral, _) | (&CharLiteral, _) => {
+            "".to_string() // HACK for `Alt`
+        }
         (&Delimited(opener, delim, ref body), _) => {
             let mut closer = opener.print();
             closer.pop();
@@ -73,12 +82,10 @@ pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv)
                 opener.print(), unparse_mbe(&*body, actl, context, s), delim.close(), closer)
         }
         (&Seq(ref sub_pats), _) => {
-            let mut prev_empty = true;
             let mut res = String::new();
             for sub_pat in sub_pats {
                 let sub_res = unparse_mbe(&*sub_pat, actl, context, s);
-                if !prev_empty && sub_res != "" { res.push(' '); }
-                prev_empty = sub_res == "";
+                if res != "" && sub_res != "" { res.push(' '); }
                 res.push_str(&sub_res);
             }
             res
@@ -87,6 +94,13 @@ pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv)
             let mut any_scopes = false;
             for sub_pat in sub_pats {
                 if let Scope(_, _) = &**sub_pat { any_scopes = true; continue; }
+                // If none of its parts are here, this isn't the alternative that was parsed:
+                let mentioned = node_names_mentioned(&*sub_pat);
+                if !mentioned.is_empty() && mentioned.iter().all(
+                        |n| context.get_leaf(*n).is_none()
+                            && context.get_rep_leaf(*n).map_or(true, |l| l.is_empty())) {
+                    continue;
+                }
 
                 let sub_res = unparse_mbe(&*sub_pat, actl, context, s);
                 if sub_res != "" { return sub_res } // HACK: should use `Option`
@@ -95,7 +109,7 @@ pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv)
             //  but "belong" under an `Alt`, so just assume forms know their grammar:
             if any_scopes {
                 match actl {
-                    &Node(ref form_actual, ref body, _) => {
+                    &Node(ref form_actual, ref body, _, _) => {
                         return unparse_mbe(&*form_actual.grammar, actl, body, s);
                     }
                     _ => { }
@@ -105,7 +119,13 @@ pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv)
             return "".to_string(); // Not sure if it's an error, or really just empty
         }
         (&Biased(ref lhs, ref rhs), _) => {
-            format!("{}{}", unparse_mbe(lhs, actl, context, s), unparse_mbe(rhs, actl, context, s))
+            // If any of its parts are missing, the left side wasn't what got parsed:
+            let lhs_missing_parts = node_names_mentioned(&*lhs).iter().any(
+                |n| context.get_leaf(*n).is_none()
+                    && context.get_rep_leaf(*n).map_or(true, |l| l.is_empty()));
+            let lhs_res = if lhs_missing_parts { "".to_string() }
+                          else { unparse_mbe(lhs, actl, context, s) };
+            if lhs_res != "" { lhs_res } else { unparse_mbe(rhs, actl, context, s) }
         }
         (&Star(ref sub_pat), _) | (&Plus(ref sub_pat), _) => {
             let mut first = true;
@@ -117,8 +137,18 @@ pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv)
             }
             res
         }
-        (&ComputeSyntax(_, _), _) => format!("?compute syntax? {:#?} ?cs?", actl),
-        (&Scope(ref form, _), &Node(ref form_actual, ref body, _)) => {
+        (&ComputeSyntax(nt, ref body), _) => {
+            // We can only redo the computation if it's fixed by the grammar.
+            // (Otherwise, we don't have what the body parsed.)
+            let new_s = match **body {
+                Anyways(ref f) => {
+                    ::core_macro_forms::compute_syntax(s, f).unwrap_or_else(|_| s.clone())
+                }
+                _ => s.clone()
+            };
+            unparse_mbe(new_s.find_or_panic(&nt), actl, context, &new_s)
+        }
+        (&Scope(ref form, _), &Node(ref form_actual, ref body, _, _)) => {
             if form == form_actual {
                 unparse_mbe(&*form.grammar, actl, body, s)
             } else {
@@ -138,7 +168,7 @@ pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv)
             unparse_mbe(&*body, &*actl_body, context, s)
         }
         (&QuoteEscape(_, _), _) => { format!("[Missing ql]{:#?}", actl)}
-        (&SynImport(ref _fp, ref _n, ref _se), &Node(_, ref _body, _)) => {
+        (&SynImport(ref _fp, ref _n, ref _se), &Node(_, ref _body, _, _)) => {
             // TODO: I think we need to store the LHS in the AST somehow for this to work.
 /*            (*se.0)(se, )
             format!("{} {}",
@@ -151,3 +181,21 @@ pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv)
 }
 
 //pub fn unparse_mbe(pat: &FormPat, actl: &Ast, context: &EnvMBE<Ast>, s: &SynEnv) -> String {
+
+#[test]
+fn unparse_seq_alt_biased() {
+    let s : SynEnv = ::util::assoc::Assoc::new();
+    let ctxt = mbe!("a" => "A", "x" => [], "b" => "B");
+    let unparse = |pat: FormPat| unparse_mbe(&pat, &Trivial, &ctxt, &s);
+
+    // An empty part doesn't swallow the space between its neighbors:
+    assert_eq!(unparse(form_pat!([(named "a", aat), (star (named "x", aat)), (named "b", aat)])),
+               "A B");
+    // Alternatives that mention only missing parts weren't what got parsed:
+    assert_eq!(unparse(form_pat!((alt [(lit "L"), (named "z", aat)], (named "b", aat)))), "B");
+    // If the left side of a `Biased` got parsed, the right side didn't:
+    assert_eq!(unparse(form_pat!((biased (named "a", aat), (named "z", aat)))), "A");
+    // ...and if the left side is missing parts, it wasn't what got parsed:
+    assert_eq!(unparse(form_pat!((biased [(named "a", aat), (named "z", aat)], (named "b", aat)))),
+               "B");
+}
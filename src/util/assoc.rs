@@ -10,7 +10,7 @@ custom_derive! {
     /// A functional key-value map. Seaching is linear (boo!), but the map is persistant (yay!).
     /// (It's just a linked list of pairs.)
     #[must_use] // this is a functional data structure; dropping it on the floor is usually bad
-    #[derive(Reifiable, Default)]
+    #[derive(Reifiable)]
     pub struct Assoc<K, V> {
         n: Option<Rc<AssocNode<K, V>>> // This could be a newtype, except for `custom_derive`
     }
@@ -24,6 +24,11 @@ impl<K : PartialEq + Clone, V: Clone> Clone for Assoc<K, V> {
     }
 }
 
+// (`derive` would needlessly require `K` and `V` to be `Default`)
+impl<K, V> Default for Assoc<K, V> {
+    fn default() -> Assoc<K, V> { Assoc { n: None } }
+}
+
 impl <K : PartialEq + Clone, V: PartialEq> PartialEq for Assoc<K, V> {
     fn eq(&self, other: &Assoc<K, V>) -> bool {
         for (k, v) in self.iter_pairs() {
@@ -1,9 +1,57 @@
 use std::fmt::{Display, Debug, Result, Formatter};
+use name::*;
+
+custom_derive! {
+    /// A region of source text: byte offsets, plus the (1-based) line and column where it starts.
+    /// Spans are always equal to each other; syntax is compared structurally.
+    /// (Otherwise, every test that compares a parse to an `ast!` would need to know positions.)
+    #[derive(Reifiable, Clone, Copy)]
+    pub struct Span {
+        pub file: Name,
+        pub begin: usize,
+        pub end: usize,
+        pub line: usize,
+        pub col: usize
+    }
+}
+
+impl Span {
+    /// For syntax that doesn't come from a file (e.g. it was constructed by `ast!`)
+    pub fn nowhere() -> Span { Span { file: n("[nowhere]"), begin: 0, end: 0, line: 0, col: 0 } }
+
+    pub fn is_nowhere(&self) -> bool { self.line == 0 }
+
+    /// The span from the start of `self` to the end of `other`
+    pub fn to(&self, other: &Span) -> Span {
+        if self.is_nowhere() { return *other }
+        if other.is_nowhere() { return *self }
+        Span { end: ::std::cmp::max(self.end, other.end), .. *self }
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _other: &Span) -> bool { true }
+}
+impl Eq for Span {}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        if self.is_nowhere() {
+            write!(f, "[unknown location]")
+        } else {
+            write!(f, "{}:{}:{}", self.file.orig_sp(), self.line, self.col)
+        }
+    }
+}
+
+impl Debug for Span {
+    fn fmt(&self, f: &mut Formatter) -> Result { write!(f, "{}", self) }
+}
 
 custom_derive! {
     #[derive(Reifiable, Clone, PartialEq)]
     pub struct Spanned<T> {
-        pub loc: ::ast::Ast, // TODO: implement spans!
+        pub loc: ::ast::Ast,
         pub body: T
     }
 }
@@ -14,16 +62,143 @@ pub fn sp<T>(t: T, a: ::ast::Ast) -> Spanned<T> {
 
 impl<T: Display> Display for Spanned<T> {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{} at {:#?}", self.body, self.loc)
+        let span = self.loc.span();
+        if span.is_nowhere() {
+            write!(f, "{} at {:#?}", self.body, self.loc)
+        } else {
+            write!(f, "{}: {}", span, self.body)
+        }
     }
 }
 
 impl<T: Debug> Debug for Spanned<T> {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "{:#?} at {:#?}", self.body, self.loc)
+        let span = self.loc.span();
+        if span.is_nowhere() {
+            write!(f, "{:#?} at {:#?}", self.body, self.loc)
+        } else {
+            write!(f, "{}: {:#?}", span, self.body)
+        }
     }
 }
 
+impl<T: Diagnostic> Spanned<T> {
+    /// Render this error, pointing into `source` (the text of `file`).
+    pub fn render(&self, file: Name, source: &str, color: bool) -> String {
+        render_diagnostic(&self.body, self.loc.span(), file, source, color)
+    }
+}
+
+/// An error that knows how to explain itself to a human.
+pub trait Diagnostic {
+    /// Which kind of error this is (e.g. "Mismatch").
+    fn kind(&self) -> String;
+    /// The first line is the headline; any others are printed as notes below the snippet.
+    fn describe(&self) -> String;
+    /// "error" or "warning"
+    fn severity(&self) -> &'static str { "error" }
+}
+
+// How many lines of a multi-line span do we bother printing?
+const max_snippet_lines : usize = 4;
+
+/// Produce a rustc-style diagnostic: a headline, the location, and the offending source lines
+///  (`source` is the text of `file`), with the span underlined.
+/// ANSI colors are used iff `color` is set.
+pub fn render_diagnostic<D: Diagnostic + ?Sized>(err: &D, span: Span, file: Name, source: &str,
+                                                 color: bool) -> String {
+    let paint = |code: &str, text: &str| -> String {
+        if color { format!("\x1b[{}m{}\x1b[0m", code, text) } else { text.to_string() }
+    };
+    let highlight = if err.severity() == "warning" { "1;33" } else { "1;31" };
+    let description = err.describe();
+    let mut lines = description.lines();
+    let mut res = format!("{}{} {}\n",
+        paint(highlight, &format!("{}[{}]", err.severity(), err.kind())), paint("1", ":"),
+        paint("1", lines.next().unwrap_or("")));
+
+    // Spans can come from somewhere other than `source` (e.g. another file, or a previous REPL
+    //  line); in that case, only report the location.
+    let usable = !span.is_nowhere() && span.file == file
+        && span.begin <= span.end && span.end <= source.len()
+        && source.is_char_boundary(span.begin) && source.is_char_boundary(span.end);
+    if !usable {
+        if !span.is_nowhere() { res.push_str(&format!("  {} {}\n", paint("1;34", "-->"), span)); }
+    } else {
+        let first_line = span.line;
+        let last_line = first_line + source[span.begin..span.end].matches('\n').count();
+        let gutter = last_line.to_string().len();
+        let bar = paint("1;34", &format!("{} |", " ".repeat(gutter)));
+
+        res.push_str(&format!("{}{} {}\n", " ".repeat(gutter), paint("1;34", "-->"), span));
+        res.push_str(&format!("{}\n", bar));
+
+        let mut line_start = source[..span.begin].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        for line_num in first_line..(last_line + 1) {
+            if line_num - first_line == max_snippet_lines && line_num != last_line {
+                res.push_str(&format!("{}\n",
+                    paint("1;34", &format!("{} ...", " ".repeat(gutter)))));
+            }
+            let text = source[line_start..].split('\n').next().unwrap_or("");
+            let line_end = line_start + text.len();
+            if line_num - first_line < max_snippet_lines || line_num == last_line {
+                // Underline the part of this line that's inside the span:
+                let mut from = ::std::cmp::max(span.begin, line_start) - line_start;
+                if line_num != first_line { // don't underline indentation
+                    from = text.len() - text.trim_start().len();
+                }
+                let to = ::std::cmp::min(span.end, line_end) - line_start;
+                let pad = text[..from].chars().count();
+                let width = ::std::cmp::max(
+                    text[::std::cmp::min(from, to)..to].chars().count(), 1);
+                res.push_str(&format!("{} {}\n",
+                    paint("1;34", &format!("{:>w$} |", line_num, w = gutter)), text));
+                res.push_str(&format!("{} {}{}\n",
+                    bar, " ".repeat(pad), paint(highlight, &"^".repeat(width))));
+            }
+            line_start = line_end + 1;
+        }
+    }
+
+    for note in lines {
+        res.push_str(&format!("{} {}\n", paint("1;34", "  ="), note));
+    }
+    res
+}
+
+/// How many single-character insertions, deletions, substitutions,
+///  and swaps of adjacent characters turn `a` into `b`?
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) : (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // dist[i][j] is the distance between the first `i` of `a` and the first `j` of `b`
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..(a.len() + 1) { dist[i][0] = i; }
+    for j in 0..(b.len() + 1) { dist[0][j] = j; }
+    for i in 1..(a.len() + 1) {
+        for j in 1..(b.len() + 1) {
+            let substitute = dist[i-1][j-1] + if a[i-1] == b[j-1] { 0 } else { 1 };
+            dist[i][j] = ::std::cmp::min(substitute,
+                                         ::std::cmp::min(dist[i-1][j], dist[i][j-1]) + 1);
+            if i > 1 && j > 1 && a[i-1] == b[j-2] && a[i-2] == b[j-1] {
+                dist[i][j] = ::std::cmp::min(dist[i][j], dist[i-2][j-2] + 1);
+            }
+        }
+    }
+    dist[a.len()][b.len()]
+}
+
+/// The candidate that `name` is most likely a typo of (for "did you mean...?" hints).
+pub fn closest_spelling<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+        where I: IntoIterator<Item=&'a str> {
+    // Anything farther than this is probably just a different name:
+    let max_distance = ::std::cmp::max(1, name.chars().count() / 3);
+    candidates.into_iter()
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|&(dist, _)| dist > 0 && dist <= max_distance)
+        .min_by_key(|&(dist, _)| dist)
+        .map(|(_, c)| c)
+}
+
 /*
 impl<T: From<()>> From<()> for Spanned<T> {
     fn from(_: ()) -> Spanned<T> {
@@ -31,3 +206,30 @@ impl<T: From<()>> From<()> for Spanned<T> {
     }
 }
 */
+
+#[test]
+fn spelling_suggestions() {
+    assert_eq!(edit_distance("plus", "plus"), 0);
+    assert_eq!(edit_distance("puls", "plus"), 1);
+    assert_eq!(edit_distance("pluss", "plus"), 1);
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+
+    assert_eq!(closest_spelling("plsu", vec!["times", "plus", "minus"]), Some("plus"));
+    assert_eq!(closest_spelling("pslu", vec!["times", "plus", "minus"]), None); // too far
+    assert_eq!(closest_spelling("Cons", vec!["Nil", "Cons"]), None); // that's not a typo!
+    assert_eq!(closest_spelling("Conz", vec!["Nil", "Cons"]), Some("Cons"));
+}
+
+#[test]
+fn render_other_file() {
+    let err = ::earley::ParseError {
+        msg: "unexpected `x`".to_string(),
+        loc: Span { file: n("a.≉"), begin: 2, end: 3, line: 1, col: 3 }
+    };
+    assert_eq!(err.render(n("a.≉"), "a x", false),
+               "error[ParseError]: unexpected `x`\n --> a.≉:1:3\n  |\n1 | a x\n  |   ^\n");
+    // The same offsets in some other file would point at the wrong text:
+    assert_eq!(err.render(n("b.≉"), "a x", false),
+               "error[ParseError]: unexpected `x`\n  --> a.≉:1:3\n");
+}
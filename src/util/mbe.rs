@@ -750,6 +750,10 @@ impl<T: Clone> EnvMBE<T> {
 
                 if !splices.is_empty() {
                     let mut template = cur_repeat.remove(i);
+                    // (Don't leave the splice itself lurking in the `Assoc`.)
+                    for splice in &splices {
+                        template.leaves = template.leaves.unset(&splice.0);
+                    }
 
                     // TODO: each of the splices better be the same length.
                     // I don't know what has to go wrong to violate that rule.
@@ -1107,7 +1111,8 @@ fn mbe_ddd_map_with() {
 
     fn concat(l: &Ast, r: &Ast) -> Ast {
         match (l, r) {
-            (&Atom(ln), &Atom(rn)) => Atom(n( format!("{}{}", ln, rn).as_str() )),
+            (&Atom(ln, _), &Atom(rn, _)) => Atom(n( format!("{}{}", ln, rn).as_str() ),
+                ::util::err::Span::nowhere()),
             _ => panic!()
         }
     }
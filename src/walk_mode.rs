@@ -109,6 +109,10 @@ pub trait WalkMode : Debug + Copy + Reifiable {
      */
     fn underspecified(Name) -> Self::Elt { panic!("ICE: no underspecified_elt") }
 
+    /// Walking a subterm failed. Can we note the error somewhere and keep going?
+    /// (Typechecking does this, so that it can report more than one error at a time.)
+    fn recover(err: Self::Err) -> Res<Self> { Err(err) }
+
     fn name() -> &'static str;
 
 }
@@ -164,12 +168,12 @@ impl<Mode: WalkMode<D=Self>> Dir for Positive<Mode> {
 
     fn walk_quasi_literally(a: Ast, cnc: &LazyWalkReses<Self::Mode>) -> Res<Self::Mode> {
         match a {
-            Node(f, parts, exports) => {
+            Node(f, parts, exports, span) => {
                 let mut walked : EnvMBE<Ast> =
                     parts.map_marched_against(
                         &mut |p: &Ast, cnc_m: &LazyWalkReses<Self::Mode>| match *p {
                             // Yes, `walk`, not `w_q_l`; the mode is in charge of figuring things out.
-                            Node(_,_,_) | VariableReference(_) | ExtendEnv(_,_) => walk(p, cnc_m),
+                            Node(..) | VariableReference(..) | ExtendEnv(..) => walk(p, cnc_m),
                             _ => Ok(<Self::Mode as WalkMode>::Elt::from_ast(&p.clone()))
                         }.map(|e| <Self::Mode as WalkMode>::Elt::to_ast(&e)),
                         cnc).lift_result()?;
@@ -177,7 +181,7 @@ impl<Mode: WalkMode<D=Self>> Dir for Positive<Mode> {
                 // HACK: recognize `Shape` as the output of `core_qq_forms::dotdotdot`:
                 walked.heal_splices(&|a| match a { Shape(ref v) => Some(v.clone()), _ => None});
 
-                Ok(<Self::Mode as WalkMode>::Elt::from_ast(&Node(f, walked, exports)))
+                Ok(<Self::Mode as WalkMode>::Elt::from_ast(&Node(f, walked, exports, span)))
             },
             orig => {
                 // All this mess is to push `Shape` down past a wrapper (i.e. `ExtendEnv`),
@@ -260,7 +264,7 @@ impl<Mode: WalkMode<D=Self> + NegativeWalkMode> Dir for Negative<Mode> {
                     .with_context(<Self::Mode as WalkMode>::Elt::from_ast(&r_fresh)))
             }
             // HACK: force walking to automatically succeed, avoiding return type muckery
-            None => (Atom(negative_ret_val()),
+            None => (Atom(negative_ret_val(), ::util::err::Span::nowhere()),
              cnc.with_context(<Self::Mode as WalkMode>::Elt::from_ast(&Trivial)))
         }
     }
@@ -272,7 +276,7 @@ impl<Mode: WalkMode<D=Self> + NegativeWalkMode> Dir for Negative<Mode> {
         let parts_actual = try!(Mode::context_match(&expected, &got, cnc.env.clone()));
 
         let its_a_trivial_ast = EnvMBE::new(); // No more walking to do
-        let expd_parts = match expected { Node(_, ref p, _) => p,  _ => &its_a_trivial_ast };
+        let expd_parts = match expected { Node(_, ref p, _, _) => p,  _ => &its_a_trivial_ast };
 
         // Continue the walk on subterms. (`context_match` does the freshening)
         // TODO: I fear that we need `map_collapse_reduce_with_marched_against`
@@ -280,7 +284,7 @@ impl<Mode: WalkMode<D=Self> + NegativeWalkMode> Dir for Negative<Mode> {
         expd_parts.map_collapse_reduce_with(&parts_actual,
             &|model: &Ast, actual: &Ast| {
                 match *model {
-                    Node(_,_,_) | VariableReference(_) | ExtendEnv(_,_) => {
+                    Node(_,_,_,_) | VariableReference(_, _) | ExtendEnv(_,_) => {
                         walk(model,
                             &cnc.with_context(<Self::Mode as WalkMode>::Elt::from_ast(actual)))
                     }
@@ -340,7 +344,8 @@ pub trait NegativeWalkMode : WalkMode {
             -> Result<EnvMBE<Ast>, <Self as WalkMode>::Err> {
         // break apart the node, and walk it element-wise
         match (expected, got) { // `pre_walk` has already freshened for us
-            (&Node(ref f, _, _), &Node(ref f_actual, ref parts_actual, _)) if *f == *f_actual => {
+            (&Node(ref f, _, _, _), &Node(ref f_actual, ref parts_actual, _, _))
+                    if *f == *f_actual => {
                 Ok(parts_actual.clone())
             }
             /* // Why did we need this? Do we still need this?